@@ -0,0 +1,373 @@
+//! Batch processing of a folder of images through a graph.
+//!
+//! Every file of the input folder that matches the filter is set in turn as
+//! the file of a Read node, and a Write node renders the graph to a path made
+//! from the output pattern. Files are spread over threads that take them from
+//! a shared queue, like tiles, each with an evaluator of its own so the
+//! cache of one file doesn't hold on to the images of the others.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use egui_snarl::{NodeId, Snarl};
+
+use crate::disk_cache::DiskCache;
+use crate::eval::{EvalError, Evaluator};
+use crate::node::DemoNode;
+use crate::ops::OpType;
+use crate::sequence::{self, FrameRange};
+
+/// Name of the report written next to the outputs unless given otherwise.
+pub const REPORT_NAME: &str = "batch_report.txt";
+
+/// Number of files processed at the same time unless given otherwise. Every
+/// file already spreads its tiles over all cores, so more jobs mostly hold
+/// more images in memory at once.
+pub const DEFAULT_JOBS: usize = 2;
+
+#[derive(Clone, Debug)]
+pub struct Batch {
+    /// Folder of the input files. Subfolders aren't searched.
+    pub input: String,
+    /// Patterns of the file names to process separated by `;`, like
+    /// `*.png;*.jpg`, or all files if empty.
+    pub filter: String,
+    /// Read node the input files are set on.
+    pub read: NodeId,
+    /// Write node that renders every file.
+    pub write: NodeId,
+    /// Path of the output files, with `{stem}` for the file name of the
+    /// input without extension, `{ext}` for its extension and `{index}` for
+    /// the position of the input among the files, counting from 1.
+    pub output: String,
+    /// Frame animated parameters are evaluated at.
+    pub frame: i64,
+    /// Number of files processed at the same time.
+    pub jobs: usize,
+}
+
+#[derive(Debug)]
+pub enum BatchError {
+    Io(io::Error),
+    /// No file of the input folder matches the filter.
+    NoFiles,
+    /// The node set as the Read or Write node is of another type.
+    WrongNode(&'static str),
+    /// Several inputs would be written to the same output file.
+    SameOutput(String),
+    /// An output would be written over one of the input files.
+    OverwritesInput(String),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Io(err) => write!(f, "{err}"),
+            BatchError::NoFiles => write!(f, "No files match the filter"),
+            BatchError::WrongNode(kind) => write!(f, "The chosen node isn't a {kind} node"),
+            BatchError::SameOutput(path) => write!(
+                f,
+                "Several files would be written to {path}; use {{stem}} or {{index}} in the output"
+            ),
+            BatchError::OverwritesInput(path) => {
+                write!(f, "{path} would overwrite one of the input files")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+impl From<io::Error> for BatchError {
+    fn from(err: io::Error) -> Self {
+        BatchError::Io(err)
+    }
+}
+
+/// Result of processing one file.
+#[derive(Debug)]
+pub struct Outcome {
+    pub input: PathBuf,
+    pub output: String,
+    pub result: Result<(), EvalError>,
+    pub duration: Duration,
+}
+
+impl Batch {
+    /// Batch with the defaults for the options: every file,
+    /// [`DEFAULT_JOBS`] jobs and the first frame.
+    pub fn new(input: &str, read: NodeId, write: NodeId, output: &str) -> Self {
+        Batch {
+            input: input.to_owned(),
+            filter: String::new(),
+            read,
+            write,
+            output: output.to_owned(),
+            frame: FrameRange::default().first,
+            jobs: DEFAULT_JOBS,
+        }
+    }
+
+    /// Files of the input folder that match the filter, by name.
+    pub fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.input)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let matches = name.to_str().is_some_and(|name| {
+                self.filter.trim().is_empty()
+                    || self
+                        .filter
+                        .split(';')
+                        .any(|pattern| glob_match(pattern.trim(), name))
+            });
+            if matches && entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Output path of the input file at `index` among the files.
+    pub fn output_path(&self, input: &Path, index: usize) -> String {
+        let part = |part: Option<&std::ffi::OsStr>| {
+            part.map_or(String::new(), |part| part.to_string_lossy().into_owned())
+        };
+        self.output
+            .replace("{stem}", &part(input.file_stem()))
+            .replace("{ext}", &part(input.extension()))
+            .replace("{index}", &(index + 1).to_string())
+    }
+
+    /// Report written next to the outputs: the folder of the output
+    /// pattern, or the current folder if the pattern has none.
+    pub fn report_path(&self) -> PathBuf {
+        Path::new(&self.output)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(REPORT_NAME)
+    }
+
+    /// Processes every file and returns the outcomes in the order of the
+    /// files. `done` is called from the processing threads as every file
+    /// finishes. Files failing don't stop the batch; only problems found
+    /// before any file is processed are errors.
+    pub fn run(
+        &self,
+        snarl: &Snarl<DemoNode>,
+        disk_cache: Option<DiskCache>,
+        done: impl Fn(&Outcome) + Sync,
+    ) -> Result<Vec<Outcome>, BatchError> {
+        let is_op = |node, op_type| match snarl.get_node(node) {
+            Some(DemoNode::Op(op)) => op.op_type == op_type,
+            _ => false,
+        };
+        if !is_op(self.read, OpType::Read) {
+            return Err(BatchError::WrongNode("Read"));
+        }
+        if !is_op(self.write, OpType::Write) {
+            return Err(BatchError::WrongNode("Write"));
+        }
+        let files = self.files()?;
+        if files.is_empty() {
+            return Err(BatchError::NoFiles);
+        }
+        let mut outputs = files
+            .iter()
+            .enumerate()
+            .map(|(index, input)| self.output_path(input, index))
+            .collect::<Vec<_>>();
+        outputs.sort();
+        if let Some(pair) = outputs.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(BatchError::SameOutput(pair[0].clone()));
+        }
+        // Compared by their real paths, so `./in/a.png` is found to be `in/a.png`
+        let inputs = files
+            .iter()
+            .filter_map(|file| fs::canonicalize(file).ok())
+            .collect::<HashSet<_>>();
+        if let Some(output) = outputs
+            .iter()
+            .find(|output| canonical_output(output).is_some_and(|path| inputs.contains(&path)))
+        {
+            return Err(BatchError::OverwritesInput(output.clone()));
+        }
+
+        let next = AtomicUsize::new(0);
+        let work = || {
+            let mut outcomes = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = files.get(index) else {
+                    break;
+                };
+                let outcome = self.process(snarl, disk_cache.clone(), input, index);
+                done(&outcome);
+                outcomes.push((index, outcome));
+            }
+            outcomes
+        };
+        let threads = self.jobs.clamp(1, files.len());
+        let mut outcomes = thread::scope(|scope| {
+            let workers = (0..threads).map(|_| scope.spawn(work)).collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Batch worker panicked"))
+                .collect::<Vec<_>>()
+        });
+        outcomes.sort_by_key(|&(index, _)| index);
+        Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+    }
+
+    /// The input or output path whose file name gets a frame token from the
+    /// name of the input, which the Read or Write node would take for an
+    /// image sequence, if any. Tokens of the output pattern itself are fine.
+    fn sequence_name(&self, input: &str, output: &str, index: usize) -> Option<String> {
+        if sequence::is_sequence(input) {
+            return Some(input.to_owned());
+        }
+        let pattern_frame = Batch {
+            output: sequence::frame_path(&self.output, self.frame),
+            ..self.clone()
+        };
+        let written = pattern_frame.output_path(Path::new(input), index);
+        (sequence::frame_path(output, self.frame) != written).then(|| output.to_owned())
+    }
+
+    fn process(
+        &self,
+        snarl: &Snarl<DemoNode>,
+        disk_cache: Option<DiskCache>,
+        input: &Path,
+        index: usize,
+    ) -> Outcome {
+        let started = Instant::now();
+        let output = self.output_path(input, index);
+        let input_path = input.to_string_lossy();
+        if let Some(path) = self.sequence_name(&input_path, &output, index) {
+            return Outcome {
+                input: input.to_owned(),
+                output,
+                result: Err(EvalError::Io(format!(
+                    "{path} has a frame token like # or $F in its name and would be taken \
+                     for an image sequence; rename the file"
+                ))),
+                duration: started.elapsed(),
+            };
+        }
+
+        let mut snarl = snarl.clone();
+        for (node, path) in [(self.read, &*input_path), (self.write, &output)] {
+            if let DemoNode::Op(op) = &mut snarl[node] {
+                op.set_path("File", path);
+            }
+        }
+        let folder = Path::new(&output).parent().unwrap_or(Path::new(""));
+        // A panic fails this file only, the other files keep going
+        let render = || {
+            let mut evaluator = Evaluator::new();
+            evaluator.set_disk_cache(disk_cache);
+            evaluator.set_frame(self.frame);
+            let frames = FrameRange::new(self.frame, self.frame);
+            evaluator.render(&snarl, self.write, frames, |_, _| {})
+        };
+        let result = fs::create_dir_all(folder)
+            .map_err(|err| EvalError::Io(format!("{}: {err}", folder.display())))
+            .and_then(|()| {
+                panic::catch_unwind(AssertUnwindSafe(render)).unwrap_or_else(|payload| {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                        .unwrap_or("unknown error");
+                    Err(EvalError::Io(format!("Processing panicked: {message}")))
+                })
+            })
+            .map(drop);
+        Outcome {
+            input: input.to_owned(),
+            output,
+            result,
+            duration: started.elapsed(),
+        }
+    }
+}
+
+/// Summary of a finished batch: a line for every file followed by the
+/// number of files that succeeded and failed.
+pub fn report(outcomes: &[Outcome], elapsed: Duration) -> String {
+    let mut report = String::new();
+    for outcome in outcomes {
+        let input = outcome.input.display();
+        let seconds = outcome.duration.as_secs_f64();
+        report += &match &outcome.result {
+            Ok(()) => format!("ok      {input} -> {} ({seconds:.2}s)\n", outcome.output),
+            Err(err) => format!("failed  {input}: {err}\n"),
+        };
+    }
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+        .count();
+    report += &format!(
+        "{} of {} files succeeded, {failed} failed, in {:.1}s\n",
+        outcomes.len() - failed,
+        outcomes.len(),
+        elapsed.as_secs_f64()
+    );
+    report
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any number of
+/// characters and `?` for one. Case is ignored, so `*.png` matches `A.PNG`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let name = name.to_lowercase().chars().collect::<Vec<_>>();
+    // Position after the last `*` and the name position it is tried at
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the last `*` take one more character
+                Some((after, tried)) => {
+                    star = Some((after, tried + 1));
+                    p = after;
+                    n = tried + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Real path of the output file `output` if its folder exists, whether or
+/// not the file itself does.
+fn canonical_output(output: &str) -> Option<PathBuf> {
+    let path = Path::new(output);
+    let folder = path
+        .parent()
+        .filter(|folder| !folder.as_os_str().is_empty());
+    Some(
+        fs::canonicalize(folder.unwrap_or(Path::new(".")))
+            .ok()?
+            .join(path.file_name()?),
+    )
+}
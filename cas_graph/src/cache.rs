@@ -0,0 +1,149 @@
+//! Outputs of evaluated nodes, kept between evaluations.
+//!
+//! Entries are keyed by the content hash of a node, which covers its
+//! parameters and the hashes of everything upstream of it. A node whose hash
+//! is in the cache isn't dirty and its outputs are reused as they are.
+//! Outputs count against a memory budget, which images take nearly all of,
+//! and the least recently used entries are dropped once it is exceeded.
+//! Outputs of frozen nodes are pinned and never dropped.
+//!
+//! Each entry records the region its images were computed for, and it only
+//! serves requests for regions inside of it.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::eval::{self, Value};
+use crate::image::Window;
+
+/// Memory the cached outputs may take unless set otherwise, 2 GiB.
+pub const DEFAULT_BUDGET: usize = 2 << 30;
+
+struct Entry {
+    outputs: Vec<Value>,
+    /// Region the outputs are complete in, `None` for the whole image.
+    region: Option<Window>,
+    bytes: usize,
+    last_used: u64,
+}
+
+pub struct OutputCache {
+    entries: HashMap<u64, Entry>,
+    /// Keys of frozen nodes, whose entries are kept regardless of the budget.
+    pinned: HashSet<u64>,
+    budget: usize,
+    used: usize,
+    /// Incremented on every access to order entries by use.
+    clock: u64,
+}
+
+impl OutputCache {
+    pub fn new(budget: usize) -> Self {
+        OutputCache {
+            entries: HashMap::new(),
+            pinned: HashSet::new(),
+            budget,
+            used: 0,
+            clock: 0,
+        }
+    }
+
+    /// Outputs stored for a node hash that are complete in `region`,
+    /// marking them as used.
+    pub fn get(&mut self, key: u64, region: Option<Window>) -> Option<Vec<Value>> {
+        self.clock += 1;
+        let entry = self
+            .entries
+            .get_mut(&key)
+            .filter(|entry| eval::covers(entry.region, region))?;
+        entry.last_used = self.clock;
+        Some(entry.outputs.clone())
+    }
+
+    /// Stores the outputs of a node computed for `region` and evicts old
+    /// entries if the outputs no longer fit. Outputs larger than the whole
+    /// budget aren't stored unless they are pinned.
+    pub fn insert(&mut self, key: u64, region: Option<Window>, outputs: Vec<Value>) {
+        let bytes = outputs.iter().map(value_bytes).sum();
+        if bytes > self.budget && !self.pinned.contains(&key) {
+            return;
+        }
+
+        self.clock += 1;
+        let entry = Entry {
+            outputs,
+            region,
+            bytes,
+            last_used: self.clock,
+        };
+        if let Some(old) = self.entries.insert(key, entry) {
+            self.used -= old.bytes;
+        }
+        self.used += bytes;
+        self.evict(self.budget);
+    }
+
+    /// Replaces the keys whose entries are never evicted.
+    pub fn set_pinned(&mut self, keys: HashSet<u64>) {
+        self.pinned = keys;
+        self.evict(self.budget);
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict(budget);
+    }
+
+    /// Memory taken by the cached outputs.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used = 0;
+    }
+
+    /// Drops least recently used entries until the outputs fit in `budget`.
+    fn evict(&mut self, budget: usize) {
+        while self.used > budget {
+            let Some((&key, _)) = self
+                .entries
+                .iter()
+                .filter(|(key, _)| !self.pinned.contains(key))
+                .min_by_key(|(_, entry)| entry.last_used)
+            else {
+                break;
+            };
+            let entry = self.entries.remove(&key).unwrap();
+            self.used -= entry.bytes;
+        }
+    }
+}
+
+impl Default for OutputCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET)
+    }
+}
+
+/// Memory of an output, including the pixels of images and the text of strings.
+pub(crate) fn value_bytes(value: &Value) -> usize {
+    let payload = match value {
+        Value::Image(image) => std::mem::size_of_val(image.data()),
+        Value::String(text) => text.len(),
+        Value::Number(_) => 0,
+    };
+    std::mem::size_of::<Value>() + payload
+}
@@ -0,0 +1,184 @@
+//! Keyframe animation of number parameters.
+//!
+//! A curve holds keys at whole frames and interpolates between them. Before
+//! the first key and after the last one, the curve holds the value of that
+//! key. Bezier segments take their shape from the tangents of the keys at
+//! both ends, given as slopes in value per frame, which is the same as a
+//! cubic Bezier with handles a third of the way along the segment.
+
+use serde::{Deserialize, Serialize};
+
+/// How a curve gets from a key to the next one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// Holds the value of the key until the next one.
+    Constant,
+    /// Straight line to the next key.
+    Linear,
+    /// Smooth curve shaped by the tangents of both keys.
+    #[default]
+    Bezier,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 3] = [
+        Interpolation::Constant,
+        Interpolation::Linear,
+        Interpolation::Bezier,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Interpolation::Constant => "Constant",
+            Interpolation::Linear => "Linear",
+            Interpolation::Bezier => "Bezier",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    pub frame: i64,
+    pub value: f64,
+    /// Interpolation of the segment to the next key.
+    pub interpolation: Interpolation,
+    /// Slope of a Bezier segment arriving at the key.
+    pub in_tangent: f64,
+    /// Slope of a Bezier segment leaving the key.
+    pub out_tangent: f64,
+}
+
+impl Key {
+    /// Bezier key with flat tangents.
+    pub const fn new(frame: i64, value: f64) -> Self {
+        Key {
+            frame,
+            value,
+            interpolation: Interpolation::Bezier,
+            in_tangent: 0.0,
+            out_tangent: 0.0,
+        }
+    }
+}
+
+/// Keys of an animated number, ordered by frame with at most one per frame.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    keys: Vec<Key>,
+}
+
+impl Curve {
+    /// Curve with a single key.
+    pub fn new(frame: i64, value: f64) -> Self {
+        Curve {
+            keys: vec![Key::new(frame, value)],
+        }
+    }
+
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Index of the key at `frame`, if there is one.
+    pub fn key_index(&self, frame: i64) -> Option<usize> {
+        self.keys.binary_search_by_key(&frame, |key| key.frame).ok()
+    }
+
+    /// Sets the value of the key at `frame`, adding a key if there is none.
+    /// New keys take the interpolation of the key before them. Returns the
+    /// index of the key.
+    pub fn set_key(&mut self, frame: i64, value: f64) -> usize {
+        match self.keys.binary_search_by_key(&frame, |key| key.frame) {
+            Ok(idx) => {
+                self.keys[idx].value = value;
+                idx
+            }
+            Err(idx) => {
+                let mut key = Key::new(frame, value);
+                if let Some(previous) = idx.checked_sub(1).map(|previous| self.keys[previous]) {
+                    key.interpolation = previous.interpolation;
+                }
+                self.keys.insert(idx, key);
+                idx
+            }
+        }
+    }
+
+    /// Removes the key at `index`. The last key of a curve isn't removed,
+    /// since a curve without keys has no value; drop the curve instead.
+    pub fn remove_key(&mut self, index: usize) {
+        if self.keys.len() > 1 {
+            self.keys.remove(index);
+        }
+    }
+
+    /// Moves the key at `index` to `frame` and `value`. The key keeps its
+    /// frame if another key is at `frame` already. Returns the new index of
+    /// the key.
+    pub fn move_key(&mut self, index: usize, frame: i64, value: f64) -> usize {
+        let mut key = self.keys[index];
+        key.value = value;
+        if self.key_index(frame).is_none() {
+            key.frame = frame;
+        }
+        self.keys.remove(index);
+        let idx = self.keys.partition_point(|other| other.frame < key.frame);
+        self.keys.insert(idx, key);
+        idx
+    }
+
+    pub fn set_interpolation(&mut self, index: usize, interpolation: Interpolation) {
+        self.keys[index].interpolation = interpolation;
+    }
+
+    pub fn set_tangents(&mut self, index: usize, in_tangent: f64, out_tangent: f64) {
+        let key = &mut self.keys[index];
+        key.in_tangent = in_tangent;
+        key.out_tangent = out_tangent;
+    }
+
+    /// Sets both tangents of the key at `index` to the slope between its
+    /// neighbours, or flat at the ends of the curve.
+    pub fn smooth_tangents(&mut self, index: usize) {
+        let slope = match (index.checked_sub(1), self.keys.get(index + 1)) {
+            (Some(previous), Some(next)) => {
+                let previous = self.keys[previous];
+                (next.value - previous.value) / (next.frame - previous.frame) as f64
+            }
+            _ => 0.0,
+        };
+        self.set_tangents(index, slope, slope);
+    }
+
+    /// Value of the curve at `frame`, which may fall between whole frames.
+    pub fn value(&self, frame: f64) -> f64 {
+        let next = self.keys.partition_point(|key| (key.frame as f64) <= frame);
+        let Some(key) = next.checked_sub(1).map(|idx| self.keys[idx]) else {
+            // Only a damaged project has a curve without keys
+            return self.keys.first().map_or(0.0, |key| key.value);
+        };
+        let Some(&next) = self.keys.get(next) else {
+            return key.value;
+        };
+
+        let span = (next.frame - key.frame) as f64;
+        let t = (frame - key.frame as f64) / span;
+        match key.interpolation {
+            Interpolation::Constant => key.value,
+            Interpolation::Linear => key.value + (next.value - key.value) * t,
+            Interpolation::Bezier => {
+                // Cubic Hermite basis with the tangents scaled to the segment
+                let (t2, t3) = (t * t, t * t * t);
+                (2.0 * t3 - 3.0 * t2 + 1.0) * key.value
+                    + (t3 - 2.0 * t2 + t) * span * key.out_tangent
+                    + (-2.0 * t3 + 3.0 * t2) * next.value
+                    + (t3 - t2) * span * next.in_tangent
+            }
+        }
+    }
+
+    /// Value of the curve at a whole frame.
+    pub fn value_at(&self, frame: i64) -> f64 {
+        self.value(frame as f64)
+    }
+}
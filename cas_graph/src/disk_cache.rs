@@ -0,0 +1,248 @@
+//! Node outputs stored on disk, so they survive between sessions.
+//!
+//! Every entry is a file named after the content hash of its node. Only
+//! outputs that took a while to compute are written, since reading a cheap
+//! result back costs more than computing it again. When the files exceed
+//! the size limit, the least recently used ones are deleted.
+//!
+//! Content hashes come from the standard library hasher, which is stable
+//! between runs of the same build. A different build may hash differently,
+//! which only means its entries are never found.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::eval::Value;
+use crate::image::{ImageBuffer, Window};
+
+/// Start of every cache file, followed by the format version.
+const MAGIC: &[u8; 8] = b"CASCACHE";
+const VERSION: u32 = 1;
+const EXTENSION: &str = "cache";
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_IMAGE: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiskCache {
+    dir: PathBuf,
+    limit: u64,
+}
+
+impl DiskCache {
+    /// Size limit unless set otherwise, 10 GiB.
+    pub const DEFAULT_LIMIT: u64 = 10 << 30;
+    /// Outputs that are computed faster are not written.
+    pub const MIN_COMPUTE_TIME: Duration = Duration::from_millis(250);
+
+    /// Cache in `dir`, which is created when the first entry is stored.
+    pub fn new(dir: impl Into<PathBuf>, limit: u64) -> Self {
+        DiskCache {
+            dir: dir.into(),
+            limit,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Outputs stored for a node hash. Missing, unreadable and outdated
+    /// entries all give `None`.
+    pub fn load(&self, key: u64) -> Option<Vec<Value>> {
+        let path = self.path(key);
+        let bytes = fs::read(&path).ok()?;
+        let outputs = decode(&bytes)?;
+        // The modification time orders entries for trimming
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(outputs)
+    }
+
+    /// Writes the outputs of a node, then deletes old entries if the cache
+    /// exceeds its limit.
+    pub fn store(&self, key: u64, outputs: &[Value]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        // Written under another name first, so a crash never leaves a partial entry
+        let partial = path.with_extension("partial");
+        let mut file = io::BufWriter::new(fs::File::create(&partial)?);
+        encode(outputs, &mut file)?;
+        file.into_inner().map_err(|err| err.into_error())?;
+        fs::rename(&partial, &path)?;
+        self.trim()
+    }
+
+    /// Deletes the least recently used entries until the cache fits its limit.
+    pub fn trim(&self) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut size = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        entries.sort_by_key(|&(_, _, used)| used);
+        for (path, len, _) in entries {
+            if size <= self.limit {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+
+    /// Total size of the entries in bytes.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, len, _)| len).sum())
+    }
+
+    /// Deletes every entry.
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, _, _) in self.entries()? {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.{EXTENSION}"))
+    }
+
+    /// Path, size and last use of every entry.
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut entries = Vec::new();
+        for entry in dir {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                let meta = fs::metadata(&path)?;
+                entries.push((path, meta.len(), meta.modified()?));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn encode(outputs: &[Value], out: &mut impl Write) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&(outputs.len() as u64).to_le_bytes())?;
+    for value in outputs {
+        match value {
+            Value::Number(number) => {
+                out.write_all(&[TAG_NUMBER])?;
+                out.write_all(&number.to_le_bytes())?;
+            }
+            Value::String(text) => {
+                out.write_all(&[TAG_STRING])?;
+                out.write_all(&(text.len() as u64).to_le_bytes())?;
+                out.write_all(text.as_bytes())?;
+            }
+            Value::Image(image) => {
+                out.write_all(&[TAG_IMAGE])?;
+                for window in [image.display_window(), image.data_window()] {
+                    out.write_all(&(window.x as i64).to_le_bytes())?;
+                    out.write_all(&(window.y as i64).to_le_bytes())?;
+                    out.write_all(&(window.width as u64).to_le_bytes())?;
+                    out.write_all(&(window.height as u64).to_le_bytes())?;
+                }
+                out.write_all(&(image.channels() as u64).to_le_bytes())?;
+                for value in image.data() {
+                    out.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<Value>> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+        return None;
+    }
+
+    let count = reader.u64()?;
+    let mut outputs = Vec::new();
+    for _ in 0..count {
+        let value = match reader.take(1)?[0] {
+            TAG_NUMBER => Value::Number(f64::from_le_bytes(reader.array()?)),
+            TAG_STRING => {
+                let len = reader.u64()? as usize;
+                Value::String(String::from_utf8(reader.take(len)?.to_vec()).ok()?)
+            }
+            TAG_IMAGE => {
+                let display = reader.window()?;
+                let data = reader.window()?;
+                let channels = reader.u64()? as usize;
+                // Checked before allocating, so a damaged size can't exhaust memory
+                let len = data
+                    .width
+                    .checked_mul(data.height)?
+                    .checked_mul(channels)?
+                    .checked_mul(4)?;
+                let pixels = reader.take(len)?;
+                let mut image = ImageBuffer::with_windows(display, data, channels);
+                for (value, bytes) in image.data_mut().iter_mut().zip(pixels.chunks_exact(4)) {
+                    *value = f32::from_le_bytes(bytes.try_into().unwrap());
+                }
+                Value::Image(Arc::new(image))
+            }
+            _ => return None,
+        };
+        outputs.push(value);
+    }
+    Some(outputs)
+}
+
+/// Reads little-endian values from the front of a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn window(&mut self) -> Option<Window> {
+        let x = i64::from_le_bytes(self.array()?);
+        let y = i64::from_le_bytes(self.array()?);
+        let width = self.u64()?;
+        let height = self.u64()?;
+        Some(Window::new(
+            x as isize,
+            y as isize,
+            width as usize,
+            height as usize,
+        ))
+    }
+}
@@ -0,0 +1,893 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use egui_snarl::{InPinId, NodeId, OutPinId, Snarl};
+
+use crate::cache::OutputCache;
+use crate::disk_cache::DiskCache;
+use crate::image::{ImageBuffer, Window};
+use crate::node::{DemoNode, PixelExprNode};
+use crate::ops::pointwise::{self, PointOp};
+use crate::ops::reformat;
+use crate::ops::resample::Filter;
+use crate::ops::transform;
+use crate::ops::{OpNode, OpType};
+use crate::sequence::{self, FrameFile, FrameRange, MissingFrames};
+use crate::stdio;
+use crate::task::{self, Task};
+
+/// Value produced by an output pin.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Image(Arc<ImageBuffer>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    /// A required input is not connected.
+    MissingInput(&'static str),
+    /// An input is connected to a pin of the wrong type.
+    WrongType(&'static str),
+    /// A property has a value the node can't use.
+    InvalidProperty(&'static str),
+    /// The node depends on its own output.
+    Cycle,
+    /// The node has no output that can be evaluated.
+    NoOutput,
+    /// The evaluation was cancelled because the graph changed.
+    Cancelled,
+    /// The output a node was frozen with is no longer stored.
+    Frozen,
+    Io(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::MissingInput(name) => write!(f, "Input \"{name}\" is not connected"),
+            EvalError::WrongType(name) => write!(f, "Input \"{name}\" has the wrong type"),
+            EvalError::InvalidProperty(name) => write!(f, "Property \"{name}\" is invalid"),
+            EvalError::Cycle => write!(f, "The graph contains a cycle"),
+            EvalError::NoOutput => write!(f, "Nothing to evaluate"),
+            EvalError::Cancelled => write!(f, "The evaluation was cancelled"),
+            EvalError::Frozen => write!(
+                f,
+                "The output the node was frozen with is gone, unfreeze the node"
+            ),
+            EvalError::Io(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Resolution the graph is evaluated at for interactive work.
+///
+/// Read nodes scale their images down and properties measured in pixels,
+/// like blur sizes and transform offsets, are scaled to match. Renders of
+/// Write nodes always use full resolution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Proxy {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+    Eighth,
+}
+
+impl Proxy {
+    pub const ALL: [Proxy; 4] = [Proxy::Full, Proxy::Half, Proxy::Quarter, Proxy::Eighth];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Proxy::Full => "Full",
+            Proxy::Half => "1/2",
+            Proxy::Quarter => "1/4",
+            Proxy::Eighth => "1/8",
+        }
+    }
+
+    pub const fn scale(self) -> f64 {
+        match self {
+            Proxy::Full => 1.0,
+            Proxy::Half => 0.5,
+            Proxy::Quarter => 0.25,
+            Proxy::Eighth => 0.125,
+        }
+    }
+
+    /// Cache key of the outputs of a node with content hash `hash` at this
+    /// scale. Full resolution uses the content hash itself.
+    fn key(self, hash: u64) -> u64 {
+        if self == Proxy::Full {
+            return hash;
+        }
+        let mut hasher = DefaultHasher::new();
+        (hash, self).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Whether `region` includes `other`, where `None` stands for the whole image.
+pub fn covers(region: Option<Window>, other: Option<Window>) -> bool {
+    match (region, other) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(region), Some(other)) => region.contains_window(&other),
+    }
+}
+
+/// Pulls values through the graph.
+///
+/// Every node gets a content hash over its parameters and the hashes of the
+/// nodes connected to its inputs. Outputs are cached by that hash between
+/// evaluations, so only nodes that changed or have a changed node upstream
+/// are evaluated again.
+///
+/// Chains of point operations, like Grade into Saturation into a Pixel
+/// Expression, run as a single pass over the image without storing the
+/// images between them. The chains of the last evaluation are reported by
+/// `Evaluator::fused`.
+///
+/// An evaluation may ask for a region of the output only. Before anything
+/// runs, the region is pulled upstream: every node reports the region of
+/// each input it needs, and a node feeding several others computes the union
+/// of what they need. Nodes then only compute pixels inside their region.
+///
+/// With a disk cache, outputs that were slow to compute
+/// are also written to disk and found again in later sessions. Freezing a
+/// node stores its output on disk. A frozen node keeps the hash it was
+/// frozen with and its output stays pinned in the cache, and it is never
+/// evaluated again, so it fails with `EvalError::Frozen` once its output is
+/// gone. When a task is current on the calling thread, every node is
+/// reported to it before it runs and evaluation stops with
+/// `EvalError::Cancelled` once the task is cancelled.
+#[derive(Default)]
+pub struct Evaluator {
+    cache: OutputCache,
+    disk_cache: Option<DiskCache>,
+    proxy: Proxy,
+    frame: i64,
+    fused: Vec<Vec<NodeId>>,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cache(&self) -> &OutputCache {
+        &self.cache
+    }
+
+    pub fn cache_mut(&mut self) -> &mut OutputCache {
+        &mut self.cache
+    }
+
+    pub fn disk_cache(&self) -> Option<&DiskCache> {
+        self.disk_cache.as_ref()
+    }
+
+    /// Enables or disables the disk cache. It is off by default.
+    pub fn set_disk_cache(&mut self, disk_cache: Option<DiskCache>) {
+        self.disk_cache = disk_cache;
+    }
+
+    pub fn proxy(&self) -> Proxy {
+        self.proxy
+    }
+
+    pub fn set_proxy(&mut self, proxy: Proxy) {
+        self.proxy = proxy;
+    }
+
+    /// Frame of image sequences that is evaluated.
+    pub fn frame(&self) -> i64 {
+        self.frame
+    }
+
+    pub fn set_frame(&mut self, frame: i64) {
+        self.frame = frame;
+    }
+
+    /// Chains of nodes the last evaluation ran as a single pass, each from
+    /// upstream to downstream.
+    pub fn fused(&self) -> &[Vec<NodeId>] {
+        &self.fused
+    }
+
+    /// Evaluates the value of an output pin at the proxy resolution.
+    /// Images only hold the pixels inside `region`, or every pixel without one.
+    pub fn evaluate(
+        &mut self,
+        snarl: &Snarl<DemoNode>,
+        pin: OutPinId,
+        region: Option<Window>,
+    ) -> Result<Value, EvalError> {
+        let mut pass = self.pass(snarl, self.proxy);
+        let value = pass.plan(pin.node, region).and_then(|()| pass.output(pin));
+        self.fused = pass.chains.into_values().collect();
+        match (value?, region) {
+            // Pixels near the edge of the region were computed from incomplete inputs
+            (Value::Image(image), Some(region))
+                if !region.contains_window(&image.data_window()) =>
+            {
+                Ok(Value::Image(Arc::new(image.crop_data(region))))
+            }
+            (value, _) => Ok(value),
+        }
+    }
+
+    /// Evaluates a Write node at full resolution and writes its source to
+    /// the file of the node. A file naming a sequence is written for every
+    /// frame of `frames`, stopping at the first error, and any other file
+    /// once for the current frame. `written` is called with the frame and
+    /// path of every file written. Returns the path of the file or sequence.
+    pub fn render(
+        &mut self,
+        snarl: &Snarl<DemoNode>,
+        node: NodeId,
+        frames: FrameRange,
+        mut written: impl FnMut(i64, &str),
+    ) -> Result<String, EvalError> {
+        if !matches!(&snarl[node], DemoNode::Op(op) if op.op_type == OpType::Write) {
+            return Err(EvalError::NoOutput);
+        }
+        let pattern = self
+            .pass(snarl, Proxy::Full)
+            .resolve(node)?
+            .path("File")
+            .to_owned();
+        if !sequence::is_sequence(&pattern) {
+            let path = self.render_frame(snarl, node)?;
+            written(self.frame, &path);
+            return Ok(path);
+        }
+
+        let current = self.frame;
+        let result = frames.frames().try_for_each(|frame| {
+            self.frame = frame;
+            let path = self.render_frame(snarl, node)?;
+            written(frame, &path);
+            Ok(())
+        });
+        self.frame = current;
+        result.map(|()| pattern)
+    }
+
+    /// Evaluates the outputs of a node at full resolution and stores them in
+    /// the disk cache, which freezing needs. Returns the content hash to
+    /// freeze the node with.
+    pub fn freeze(&mut self, snarl: &Snarl<DemoNode>, node: NodeId) -> Result<u64, EvalError> {
+        let Some(disk_cache) = self.disk_cache.clone() else {
+            return Err(EvalError::Io(
+                "Freezing a node needs the disk cache".to_owned(),
+            ));
+        };
+        let mut pass = self.pass(snarl, Proxy::Full);
+        let hash = pass.hashes.get(node)?;
+        pass.plan(node, None)?;
+        pass.output(OutPinId { node, output: 0 })?;
+        disk_cache
+            .store(hash, &pass.outputs[&node])
+            .map_err(|err| EvalError::Io(format!("{}: {err}", disk_cache.dir().display())))?;
+        Ok(hash)
+    }
+
+    /// Writes the current frame of a Write node and returns the path written.
+    fn render_frame(&mut self, snarl: &Snarl<DemoNode>, node: NodeId) -> Result<String, EvalError> {
+        let mut pass = self.pass(snarl, Proxy::Full);
+        let Value::Image(image) = pass.output(OutPinId { node, output: 0 })? else {
+            return Err(EvalError::WrongType("Source"));
+        };
+        let op = pass.resolve(node)?;
+        if op.path("File").is_empty() {
+            return Err(EvalError::InvalidProperty("File"));
+        }
+        let path = sequence::frame_path(op.path("File"), self.frame);
+        if stdio::is_stdio(&path) {
+            stdio::write_output(&image).map_err(EvalError::Io)?;
+        } else {
+            image
+                .save(&path)
+                .map_err(|err| EvalError::Io(format!("{path}: {err}")))?;
+        }
+        Ok(path)
+    }
+
+    fn pass<'a>(&'a mut self, snarl: &'a Snarl<DemoNode>, proxy: Proxy) -> Pass<'a> {
+        let frozen = snarl
+            .nodes()
+            .filter_map(|node| match node {
+                DemoNode::Op(op) => op.frozen.map(|hash| proxy.key(hash)),
+                _ => None,
+            })
+            .collect();
+        self.cache.set_pinned(frozen);
+
+        Pass {
+            snarl,
+            cache: &mut self.cache,
+            disk_cache: self.disk_cache.as_ref(),
+            hashes: ContentHashes::new(snarl, self.frame),
+            proxy,
+            frame: self.frame,
+            regions: HashMap::new(),
+            chains: HashMap::new(),
+            outputs: HashMap::new(),
+            visiting: Vec::new(),
+            upstream_time: Duration::ZERO,
+            task: task::current(),
+        }
+    }
+}
+
+/// Content hashes of the nodes of a graph.
+///
+/// The hash of a node covers its parameters and, for every connected input,
+/// the hash of the node and the output pin it is connected to. Read nodes
+/// also hash the file they read for the frame and its modification time, so
+/// nodes only differ between frames if a sequence is upstream of them.
+/// Frozen nodes use the hash they were frozen with instead.
+pub struct ContentHashes<'a> {
+    snarl: &'a Snarl<DemoNode>,
+    frame: i64,
+    /// Input pins of every node with the output pins they are connected to.
+    upstream: HashMap<NodeId, Vec<(usize, OutPinId)>>,
+    hashes: HashMap<NodeId, u64>,
+    visiting: Vec<NodeId>,
+}
+
+impl<'a> ContentHashes<'a> {
+    pub fn new(snarl: &'a Snarl<DemoNode>, frame: i64) -> Self {
+        let mut upstream = HashMap::<NodeId, Vec<(usize, OutPinId)>>::new();
+        for (out_pin, in_pin) in snarl.wires() {
+            upstream
+                .entry(in_pin.node)
+                .or_default()
+                .push((in_pin.input, out_pin));
+        }
+        for wires in upstream.values_mut() {
+            wires.sort_by_key(|&(input, _)| input);
+        }
+
+        ContentHashes {
+            snarl,
+            frame,
+            upstream,
+            hashes: HashMap::new(),
+            visiting: Vec::new(),
+        }
+    }
+
+    /// Input pins of a node with the output pins they are connected to.
+    fn upstream(&self, node: NodeId) -> &[(usize, OutPinId)] {
+        self.upstream.get(&node).map_or(&[], Vec::as_slice)
+    }
+
+    /// Content hash of a node and everything upstream of it.
+    pub fn get(&mut self, node: NodeId) -> Result<u64, EvalError> {
+        if let Some(&hash) = self.hashes.get(&node) {
+            return Ok(hash);
+        }
+        if self.visiting.contains(&node) {
+            return Err(EvalError::Cycle);
+        }
+
+        let mut hasher = DefaultHasher::new();
+        match &self.snarl[node] {
+            DemoNode::Op(OpNode {
+                frozen: Some(hash), ..
+            }) => {
+                self.hashes.insert(node, *hash);
+                return Ok(*hash);
+            }
+            DemoNode::Op(
+                op @ OpNode {
+                    op_type: OpType::Read,
+                    ..
+                },
+            ) => {
+                let missing = MissingFrames::from_index(op.choice("Missing Frames"));
+                let file = sequence::frame_file(op.path("File"), self.frame, missing);
+                match &file {
+                    // Standard input differs between runs sharing a disk cache
+                    Ok(file) if stdio::is_stdio(file.path()) => {
+                        stdio::input_hash().hash(&mut hasher)
+                    }
+                    Ok(file) => {
+                        let modified =
+                            std::fs::metadata(file.path()).and_then(|meta| meta.modified());
+                        modified.ok().hash(&mut hasher);
+                    }
+                    Err(_) => {}
+                }
+                file.hash(&mut hasher);
+            }
+            _ => {}
+        }
+        self.snarl[node].hash_content(self.frame, &mut hasher);
+
+        let wires = self.upstream(node).to_vec();
+        self.visiting.push(node);
+        let remote_hashes = wires
+            .iter()
+            .map(|(_, remote)| self.get(remote.node))
+            .collect::<Result<Vec<_>, _>>();
+        self.visiting.pop();
+        for ((input, remote), remote_hash) in wires.iter().zip(remote_hashes?) {
+            (input, remote.output, remote_hash).hash(&mut hasher);
+        }
+
+        let hash = hasher.finish();
+        self.hashes.insert(node, hash);
+        Ok(hash)
+    }
+}
+
+/// State of a single evaluation.
+struct Pass<'a> {
+    snarl: &'a Snarl<DemoNode>,
+    cache: &'a mut OutputCache,
+    disk_cache: Option<&'a DiskCache>,
+    hashes: ContentHashes<'a>,
+    proxy: Proxy,
+    frame: i64,
+    /// Region every node computes. Nodes without one compute the whole image.
+    regions: HashMap<NodeId, Window>,
+    /// Chains of point operations run as one pass, by their last node.
+    chains: HashMap<NodeId, Vec<NodeId>>,
+    outputs: HashMap<NodeId, Vec<Value>>,
+    visiting: Vec<NodeId>,
+    /// Time spent evaluating nodes so far, to tell the time a node takes
+    /// apart from the time of its inputs.
+    upstream_time: Duration,
+    task: Option<Arc<Task>>,
+}
+
+impl Pass<'_> {
+    /// Works out the region every node upstream of `node` computes so that
+    /// `node` has the pixels of `region`.
+    ///
+    /// A node is only planned once all nodes it feeds are, so it gets the
+    /// union of what they need. Frozen nodes always give the whole image
+    /// they were frozen with. Nodes in a cycle are never planned, and the
+    /// cycle is reported when they run.
+    fn plan(&mut self, node: NodeId, region: Option<Window>) -> Result<(), EvalError> {
+        // Number of nodes each upstream node feeds that aren't planned yet
+        let mut pending = HashMap::<NodeId, usize>::new();
+        let mut stack = vec![node];
+        while let Some(current) = stack.pop() {
+            for &(_, remote) in self.hashes.upstream(current) {
+                let count = pending.entry(remote.node).or_default();
+                if *count == 0 {
+                    stack.push(remote.node);
+                }
+                *count += 1;
+            }
+        }
+
+        let mut needed = HashMap::from([(node, region)]);
+        let mut ready = vec![node];
+        let mut order = Vec::new();
+        while let Some(current) = ready.pop() {
+            order.push(current);
+            let frozen = matches!(&self.snarl[current], DemoNode::Op(op) if op.frozen.is_some());
+            let region = needed[&current].filter(|_| !frozen);
+            if let Some(region) = region {
+                self.regions.insert(current, region);
+            }
+
+            let op = match &self.snarl[current] {
+                DemoNode::Op(_) if region.is_some() => Some(self.resolve(current)?),
+                _ => None,
+            };
+            for (input, remote) in self.hashes.upstream(current).to_vec() {
+                let input_region = match (&self.snarl[current], &op) {
+                    (DemoNode::Op(_), Some(op)) => op.input_region(input, region),
+                    (DemoNode::PixelExpr(_), _) if input == 0 => region,
+                    _ => None,
+                };
+                needed
+                    .entry(remote.node)
+                    .and_modify(|needed| {
+                        *needed = needed
+                            .zip(input_region)
+                            .map(|(needed, input_region)| needed.union(&input_region));
+                    })
+                    .or_insert(input_region);
+
+                let count = pending.get_mut(&remote.node).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(remote.node);
+                }
+            }
+        }
+        self.fuse(&order);
+        Ok(())
+    }
+
+    /// Finds chains of point operations among the planned nodes, which come
+    /// after all nodes they feed. A node joins the chain of the node its
+    /// output goes to when that is the only node it feeds, so its own output
+    /// is never needed. Frozen nodes keep their output and don't join.
+    fn fuse(&mut self, order: &[NodeId]) {
+        let mut consumers = HashMap::<NodeId, usize>::new();
+        for (out_pin, _) in self.snarl.wires() {
+            *consumers.entry(out_pin.node).or_default() += 1;
+        }
+        let joins = |node: NodeId| {
+            let frozen = matches!(&self.snarl[node], DemoNode::Op(op) if op.frozen.is_some());
+            is_point_op(&self.snarl[node]) && !frozen && consumers[&node] == 1
+        };
+
+        let mut fused = HashSet::new();
+        for &last in order {
+            if fused.contains(&last) || !is_point_op(&self.snarl[last]) {
+                continue;
+            }
+            let mut chain = vec![last];
+            while let Some(&(0, remote)) = self.hashes.upstream(chain[chain.len() - 1]).first() {
+                if !joins(remote.node) || chain.contains(&remote.node) {
+                    break;
+                }
+                chain.push(remote.node);
+            }
+            if chain.len() > 1 {
+                chain.reverse();
+                fused.extend(chain.iter().copied());
+                self.chains.insert(last, chain);
+            }
+        }
+    }
+
+    /// Region a node computes, `None` for the whole image.
+    fn region(&self, node: NodeId) -> Option<Window> {
+        self.regions.get(&node).copied()
+    }
+
+    fn output(&mut self, pin: OutPinId) -> Result<Value, EvalError> {
+        if !self.outputs.contains_key(&pin.node) {
+            let hash = self.hashes.get(pin.node)?;
+            let key = self.proxy.key(hash);
+            let region = self.region(pin.node);
+            let frozen = matches!(&self.snarl[pin.node], DemoNode::Op(op) if op.frozen.is_some());
+            let outputs = match self.cached(key, region) {
+                Some(outputs) => outputs,
+                None if frozen => self.frozen(hash, key)?,
+                None => {
+                    self.report(pin.node)?;
+                    let upstream_before = self.upstream_time;
+                    let start = Instant::now();
+                    self.visiting.push(pin.node);
+                    let outputs = self.node(pin.node);
+                    self.visiting.pop();
+                    let total = start.elapsed();
+                    let own_time = total.saturating_sub(self.upstream_time - upstream_before);
+                    self.upstream_time = upstream_before + total;
+                    let outputs = outputs?;
+                    // A cancelled kernel leaves its image unfinished
+                    if self.task.as_ref().is_some_and(|task| task.is_cancelled()) {
+                        return Err(EvalError::Cancelled);
+                    }
+
+                    // Only whole images are written, a region is rarely asked for again
+                    if let (Some(disk_cache), None) = (self.disk_cache, region) {
+                        if own_time >= DiskCache::MIN_COMPUTE_TIME {
+                            // The result is already in hand, a failed write only costs time later
+                            let _ = disk_cache.store(key, &outputs);
+                        }
+                    }
+                    self.cache.insert(key, region, outputs.clone());
+                    outputs
+                }
+            };
+            self.outputs.insert(pin.node, outputs);
+        }
+
+        self.outputs[&pin.node]
+            .get(pin.output)
+            .cloned()
+            .ok_or(EvalError::NoOutput)
+    }
+
+    /// Outputs stored for a content hash in memory or on disk that are
+    /// complete in `region`.
+    fn cached(&mut self, key: u64, region: Option<Window>) -> Option<Vec<Value>> {
+        if let Some(outputs) = self.cache.get(key, region) {
+            return Some(outputs);
+        }
+        let outputs = self.disk_cache?.load(key)?;
+        self.cache.insert(key, None, outputs.clone());
+        Some(outputs)
+    }
+
+    /// Outputs a node was frozen with, stored at full resolution and scaled
+    /// down for proxies. A frozen node is never evaluated, as its inputs may
+    /// have changed since it was frozen.
+    fn frozen(&mut self, hash: u64, key: u64) -> Result<Vec<Value>, EvalError> {
+        if self.proxy == Proxy::Full {
+            return Err(EvalError::Frozen);
+        }
+        let outputs = self
+            .cached(hash, None)
+            .ok_or(EvalError::Frozen)?
+            .into_iter()
+            .map(|value| match value {
+                Value::Image(image) => Value::Image(Arc::new(proxy_image(&image, self.proxy))),
+                value => value,
+            })
+            .collect::<Vec<_>>();
+        self.cache.insert(key, None, outputs.clone());
+        Ok(outputs)
+    }
+
+    /// Value connected to an input pin, if any.
+    fn input(&mut self, pin: InPinId) -> Result<Option<Value>, EvalError> {
+        match self.snarl.in_pin(pin).remotes.first() {
+            Some(remote) => {
+                let value = self.output(*remote)?;
+                // Upstream nodes are done, the node of the pin continues
+                self.report(pin.node)?;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Tells the task which node runs, or stops if the task was cancelled.
+    fn report(&self, node: NodeId) -> Result<(), EvalError> {
+        match &self.task {
+            Some(task) if task.is_cancelled() => Err(EvalError::Cancelled),
+            Some(task) => {
+                task.start_node(node);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn node(&mut self, node: NodeId) -> Result<Vec<Value>, EvalError> {
+        if let Some(chain) = self.chains.get(&node).cloned() {
+            return self.fused(&chain);
+        }
+
+        let snarl = self.snarl;
+        match &snarl[node] {
+            DemoNode::Sink | DemoNode::ShowImage(_) => Err(EvalError::NoOutput),
+            DemoNode::Number(..) => Ok(vec![Value::Number(
+                snarl[node].number_out(self.frame).unwrap(),
+            )]),
+            DemoNode::String(value) => Ok(vec![Value::String(value.clone())]),
+            DemoNode::ExprNode(expr_node) => {
+                let mut values = expr_node.values.clone();
+                for (idx, value) in values.iter_mut().enumerate() {
+                    if let Some(Value::Number(input)) = self.input(InPinId {
+                        node,
+                        input: idx + 1,
+                    })? {
+                        *value = input;
+                    }
+                }
+                Ok(vec![Value::Number(expr_node.eval_with(&values))])
+            }
+            DemoNode::PixelExpr(pixel_expr) => {
+                let src = self.input(InPinId { node, input: 0 })?;
+                let Some(Value::Image(src)) = crop_input(src, self.region(node)) else {
+                    return Err(EvalError::MissingInput("Source"));
+                };
+                let uniforms = self.uniforms(node, pixel_expr)?;
+                let image = pixel_expr.process(&src, &uniforms);
+                Ok(vec![Value::Image(Arc::new(image))])
+            }
+            DemoNode::Op(_) => {
+                let op = self.resolve(node)?;
+                match op.op_type {
+                    OpType::Read => self.read(&op, self.region(node)),
+                    OpType::Transform => self.transform(node, &op),
+                    _ => {
+                        let region = self.region(node);
+                        let mut inputs = Vec::new();
+                        for idx in 0..op.op_type.inputs().len() {
+                            let input = self.input(InPinId { node, input: idx })?;
+                            inputs.push(crop_input(input, op.input_region(idx, region)));
+                        }
+                        op.process(&inputs, region)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Values of the uniforms of a Pixel Expression node, with connected inputs applied.
+    fn uniforms(
+        &mut self,
+        node: NodeId,
+        pixel_expr: &PixelExprNode,
+    ) -> Result<Vec<f64>, EvalError> {
+        let mut uniforms = pixel_expr.values.clone();
+        for (idx, value) in uniforms.iter_mut().enumerate() {
+            if let Some(Value::Number(input)) = self.input(InPinId {
+                node,
+                input: PixelExprNode::FIRST_UNIFORM + idx,
+            })? {
+                *value = input;
+            }
+        }
+        Ok(uniforms)
+    }
+
+    /// Runs a chain found by `fuse` as a single pass over the source of its first node.
+    fn fused(&mut self, chain: &[NodeId]) -> Result<Vec<Value>, EvalError> {
+        let last = chain[chain.len() - 1];
+        let src = self.input(InPinId {
+            node: chain[0],
+            input: 0,
+        })?;
+        let src = match crop_input(src, self.region(last)) {
+            Some(Value::Image(src)) => src,
+            Some(_) => return Err(EvalError::WrongType("Source")),
+            None => return Err(EvalError::MissingInput("Source")),
+        };
+
+        let snarl = self.snarl;
+        let mut ops = Vec::new();
+        for &node in chain {
+            let op = match &snarl[node] {
+                DemoNode::PixelExpr(pixel_expr) => {
+                    let uniforms = self.uniforms(node, pixel_expr)?;
+                    PointOp::PixelExpr(pixel_expr.compile(src.display_window(), &uniforms))
+                }
+                _ => self
+                    .resolve(node)?
+                    .point_op()
+                    .expect("Only point operations are fused"),
+            };
+            ops.push(op);
+        }
+        self.report(last)?;
+        Ok(vec![Value::Image(Arc::new(pointwise::run(&src, &ops)))])
+    }
+
+    /// Copy of an image node with connected property inputs applied.
+    fn resolve(&mut self, node: NodeId) -> Result<OpNode, EvalError> {
+        let snarl = self.snarl;
+        let DemoNode::Op(op) = &snarl[node] else {
+            unreachable!("Only image nodes have properties")
+        };
+        let mut op = op.clone();
+        op.animate(self.frame);
+        let first = op.op_type.inputs().len();
+
+        for (idx, property) in op.properties.iter_mut().enumerate() {
+            let value = self.input(InPinId {
+                node,
+                input: first + idx,
+            })?;
+            if let Some(value) = value {
+                property.set_value(&value);
+            }
+        }
+        if self.proxy != Proxy::Full {
+            op.scale_spatial(self.proxy.scale());
+        }
+
+        Ok(op)
+    }
+
+    fn read(&mut self, op: &OpNode, region: Option<Window>) -> Result<Vec<Value>, EvalError> {
+        let missing = MissingFrames::from_index(op.choice("Missing Frames"));
+        let file =
+            sequence::frame_file(op.path("File"), self.frame, missing).map_err(EvalError::Io)?;
+        let path = file.path();
+        let io_error = |err| EvalError::Io(format!("{path}: {err}"));
+        let mut image = match &file {
+            FrameFile::Image(_) if stdio::is_stdio(path) => {
+                ImageBuffer::clone(&*stdio::input().map_err(EvalError::Io)?)
+            }
+            FrameFile::Image(_) => ImageBuffer::load(path).map_err(io_error)?,
+            FrameFile::Black(_) => {
+                let format = ImageBuffer::load_format(path).map_err(io_error)?;
+                ImageBuffer::with_windows(format, Window::default(), 4)
+            }
+        };
+        if self.proxy != Proxy::Full {
+            image = proxy_image(&image, self.proxy);
+        }
+        // The whole file is decoded, but nodes downstream only see the region
+        if let Some(region) = region {
+            image = image.crop_data(region);
+        }
+        Ok(vec![Value::Image(Arc::new(image))])
+    }
+
+    /// Concatenates a chain of Transform nodes so the source is resampled only once,
+    /// using the filter of the last node in the chain.
+    fn transform(&mut self, node: NodeId, op: &OpNode) -> Result<Vec<Value>, EvalError> {
+        let depth = self.visiting.len();
+        let result = self.transform_chain(node, op);
+        self.visiting.truncate(depth);
+        result
+    }
+
+    fn transform_chain(&mut self, node: NodeId, op: &OpNode) -> Result<Vec<Value>, EvalError> {
+        let mut matrix = op.transform_params().matrix();
+        let mut upstream = node;
+
+        loop {
+            let Some(&remote) = self
+                .snarl
+                .in_pin(InPinId {
+                    node: upstream,
+                    input: 0,
+                })
+                .remotes
+                .first()
+            else {
+                return Err(EvalError::MissingInput("Source"));
+            };
+
+            match &self.snarl[remote.node] {
+                DemoNode::Op(OpNode {
+                    op_type: OpType::Transform,
+                    ..
+                }) if !self.visiting.contains(&remote.node) => {
+                    let previous = self.resolve(remote.node)?;
+                    matrix = previous.transform_params().matrix().then(&matrix);
+                    upstream = remote.node;
+                    self.visiting.push(upstream);
+                }
+                _ => {
+                    let region = self
+                        .region(node)
+                        .map(|region| transform::source_region(&matrix, op.filter(), region));
+                    let Some(Value::Image(src)) = crop_input(Some(self.output(remote)?), region)
+                    else {
+                        return Err(EvalError::WrongType("Source"));
+                    };
+                    let image =
+                        transform::resample(&src, &matrix, op.filter(), src.display_window());
+                    return Ok(vec![Value::Image(Arc::new(image))]);
+                }
+            }
+        }
+    }
+}
+
+/// Image scaled down to the resolution of a proxy.
+fn proxy_image(image: &ImageBuffer, proxy: Proxy) -> ImageBuffer {
+    let scale = proxy.scale();
+    let display = image.display_window();
+    let width = (display.width as f64 * scale).ceil() as usize;
+    let height = (display.height as f64 * scale).ceil() as usize;
+    reformat::resize(image, width, height, Filter::Bilinear)
+}
+
+/// Drops the pixels of an input image outside of the region a node reads,
+/// so the node doesn't compute pixels nobody asked for.
+fn crop_input(value: Option<Value>, region: Option<Window>) -> Option<Value> {
+    match (value, region) {
+        (Some(Value::Image(image)), Some(region))
+            if !region.contains_window(&image.data_window()) =>
+        {
+            Some(Value::Image(Arc::new(image.crop_data(region))))
+        }
+        (value, _) => value,
+    }
+}
+
+/// Whether a node computes every pixel from the same pixel of its source alone.
+fn is_point_op(node: &DemoNode) -> bool {
+    match node {
+        DemoNode::PixelExpr(_) => true,
+        DemoNode::Op(op) => op.op_type.is_point_op(),
+        _ => false,
+    }
+}
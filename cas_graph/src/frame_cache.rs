@@ -0,0 +1,120 @@
+//! Evaluated outputs of the viewed node for every frame, for playback.
+//!
+//! Entries are keyed by frame number only, so the cache holds frames of one
+//! graph and has to be cleared when the graph changes. Like the output
+//! cache, each entry records the region it was computed for and only serves
+//! requests inside of it. Once the budget is exceeded, the frames farthest
+//! from the one just stored are dropped, since playback needs the frames
+//! around the current one next.
+
+use std::collections::BTreeMap;
+
+use crate::cache;
+use crate::eval::{self, Value};
+use crate::image::Window;
+
+/// Memory the cached frames may take unless set otherwise, 2 GiB.
+pub const DEFAULT_BUDGET: usize = 2 << 30;
+
+struct Entry {
+    value: Value,
+    /// Region the value is complete in, `None` for the whole image.
+    region: Option<Window>,
+    bytes: usize,
+}
+
+pub struct FrameCache {
+    entries: BTreeMap<i64, Entry>,
+    budget: usize,
+    used: usize,
+}
+
+impl FrameCache {
+    pub fn new(budget: usize) -> Self {
+        FrameCache {
+            entries: BTreeMap::new(),
+            budget,
+            used: 0,
+        }
+    }
+
+    /// Value stored for `frame` that is complete in `region`.
+    pub fn get(&self, frame: i64, region: Option<Window>) -> Option<&Value> {
+        self.entries
+            .get(&frame)
+            .filter(|entry| eval::covers(entry.region, region))
+            .map(|entry| &entry.value)
+    }
+
+    /// Stores the value of `frame` computed for `region` and drops the
+    /// frames farthest from it if the values no longer fit. Values larger
+    /// than the whole budget aren't stored.
+    pub fn insert(&mut self, frame: i64, region: Option<Window>, value: Value) {
+        let bytes = cache::value_bytes(&value);
+        if bytes > self.budget {
+            return;
+        }
+
+        let entry = Entry {
+            value,
+            region,
+            bytes,
+        };
+        if let Some(old) = self.entries.insert(frame, entry) {
+            self.used -= old.bytes;
+        }
+        self.used += bytes;
+        while self.used > self.budget {
+            let (&first, _) = self.entries.first_key_value().unwrap();
+            let (&last, _) = self.entries.last_key_value().unwrap();
+            let farthest = if frame.abs_diff(first) > frame.abs_diff(last) {
+                first
+            } else {
+                last
+            };
+            let entry = self.entries.remove(&farthest).unwrap();
+            self.used -= entry.bytes;
+        }
+    }
+
+    /// Cached frames in order.
+    pub fn frames(&self) -> impl Iterator<Item = i64> + '_ {
+        self.entries.keys().copied()
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        while self.used > budget {
+            let (_, entry) = self.entries.pop_last().unwrap();
+            self.used -= entry.bytes;
+        }
+    }
+
+    /// Memory taken by the cached frames.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used = 0;
+    }
+}
+
+impl Default for FrameCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET)
+    }
+}
@@ -0,0 +1,391 @@
+//! Floating point images with a display window and a data window.
+//!
+//! The display window is the format of the image: the canvas a viewer shows
+//! and a file on disk would have. The data window is the region where pixels
+//! are actually stored. It may be smaller than the display window, and
+//! everything outside of it is transparent black.
+//!
+//! Nodes follow the same rules when images of different sizes meet:
+//!
+//! - Point operations keep both windows of their input.
+//! - Transforms and filters keep the display window of their input. Their data
+//!   window grows to cover every pixel they can reach, clipped to the display window.
+//! - Nodes with several image inputs take the display window of their first
+//!   input. Other inputs are not scaled, they stay at their own pixel
+//!   coordinates, and the data window is the union of all data windows,
+//!   clipped to the display window. Use Reformat to conform inputs first.
+//! - Crop, Resize and Reformat are the only nodes that change the display window.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use image::ImageFormat;
+
+use crate::tile;
+
+/// Rectangle of whole pixels. `x` and `y` are the top-left corner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Window {
+    pub x: isize,
+    pub y: isize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Window {
+    pub const fn new(x: isize, y: isize, width: usize, height: usize) -> Self {
+        Window {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Window with its top-left corner at the origin.
+    pub const fn from_size(width: usize, height: usize) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    /// Smallest window containing the corners `(x0, y0)` and `(x1, y1)`, exclusive.
+    pub fn from_corners(x0: isize, y0: isize, x1: isize, y1: isize) -> Self {
+        Window {
+            x: x0,
+            y: y0,
+            width: (x1 - x0).max(0) as usize,
+            height: (y1 - y0).max(0) as usize,
+        }
+    }
+
+    /// Smallest window containing every pixel touched by the given rectangle.
+    pub fn enclosing(x0: f64, y0: f64, x1: f64, y1: f64) -> Self {
+        Self::from_corners(
+            x0.floor() as isize,
+            y0.floor() as isize,
+            x1.ceil() as isize,
+            y1.ceil() as isize,
+        )
+    }
+
+    pub const fn right(&self) -> isize {
+        self.x + self.width as isize
+    }
+
+    pub const fn bottom(&self) -> isize {
+        self.y + self.height as isize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub const fn contains(&self, x: isize, y: isize) -> bool {
+        x >= self.x && y >= self.y && x < self.right() && y < self.bottom()
+    }
+
+    /// Whether every pixel of `other` is inside the window.
+    /// An empty window is inside any window.
+    pub const fn contains_window(&self, other: &Window) -> bool {
+        other.is_empty()
+            || (other.x >= self.x
+                && other.y >= self.y
+                && other.right() <= self.right()
+                && other.bottom() <= self.bottom())
+    }
+
+    pub fn intersect(&self, other: &Window) -> Window {
+        Self::from_corners(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        )
+    }
+
+    pub fn union(&self, other: &Window) -> Window {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        Self::from_corners(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.right().max(other.right()),
+            self.bottom().max(other.bottom()),
+        )
+    }
+
+    /// Grows the window by `by` pixels on every side.
+    pub fn expand(&self, by: usize) -> Window {
+        let by = by as isize;
+        Self::from_corners(
+            self.x - by,
+            self.y - by,
+            self.right() + by,
+            self.bottom() + by,
+        )
+    }
+
+    pub const fn translate(&self, dx: isize, dy: isize) -> Window {
+        Self::new(self.x + dx, self.y + dy, self.width, self.height)
+    }
+}
+
+/// Floating point image with interleaved channels.
+///
+/// Pixels of the data window are stored row by row with the origin in the
+/// top-left corner. Images read from disk have four channels (RGBA), single
+/// channel images are used for mattes. Images have at most four channels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageBuffer {
+    display_window: Window,
+    data_window: Window,
+    channels: usize,
+    data: Vec<f32>,
+}
+
+impl ImageBuffer {
+    /// Creates an image with all channels set to zero.
+    /// Both windows start at the origin.
+    pub fn new(width: usize, height: usize, channels: usize) -> Self {
+        let window = Window::from_size(width, height);
+        Self::with_windows(window, window, channels)
+    }
+
+    /// Creates an image with all channels of the data window set to zero.
+    pub fn with_windows(display_window: Window, data_window: Window, channels: usize) -> Self {
+        ImageBuffer {
+            display_window,
+            data_window,
+            channels,
+            data: vec![0.0; data_window.width * data_window.height * channels],
+        }
+    }
+
+    /// Creates an image by calling `f` for every pixel.
+    pub fn from_fn(
+        width: usize,
+        height: usize,
+        channels: usize,
+        f: impl Fn(usize, usize, &mut [f32]) + Sync,
+    ) -> Self {
+        let window = Window::from_size(width, height);
+        Self::from_fn_windows(window, window, channels, |x, y, out| {
+            f(x as usize, y as usize, out)
+        })
+    }
+
+    /// Creates an image by calling `f` for every pixel of the data window.
+    /// `f` receives absolute pixel coordinates.
+    ///
+    /// Tiles of the image are rendered in parallel, so `f` is called from
+    /// several threads in no particular order.
+    pub fn from_fn_windows(
+        display_window: Window,
+        data_window: Window,
+        channels: usize,
+        f: impl Fn(isize, isize, &mut [f32]) + Sync,
+    ) -> Self {
+        let mut image = Self::with_windows(display_window, data_window, channels);
+        tile::fill(&mut image, f);
+        image
+    }
+
+    /// Reads an image file and converts it to four channel floating point.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, image::ImageError> {
+        Ok(Self::from_dynamic(image::open(path)?))
+    }
+
+    /// Decodes an image file held in memory, like `load`.
+    pub fn decode(bytes: &[u8], format: ImageFormat) -> Result<Self, image::ImageError> {
+        Ok(Self::from_dynamic(image::load_from_memory_with_format(
+            bytes, format,
+        )?))
+    }
+
+    fn from_dynamic(image: image::DynamicImage) -> Self {
+        let rgba = image.into_rgba32f();
+        let (width, height) = rgba.dimensions();
+        let window = Window::from_size(width as usize, height as usize);
+
+        ImageBuffer {
+            display_window: window,
+            data_window: window,
+            channels: 4,
+            data: rgba.into_raw(),
+        }
+    }
+
+    /// Display window of an image file, read without decoding the pixels.
+    pub fn load_format(path: impl AsRef<Path>) -> Result<Window, image::ImageError> {
+        let (width, height) = image::image_dimensions(path)?;
+        Ok(Window::from_size(width as usize, height as usize))
+    }
+
+    /// Writes the display window to an image file in the format of its extension.
+    ///
+    /// OpenEXR and Radiance HDR files keep the floating point values. Other
+    /// formats are clamped to 0..1 and stored with 16 bits per channel where
+    /// the format allows it and 8 bits otherwise. Single channel images are
+    /// written as opaque grey.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), image::ImageError> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path)?;
+        self.to_dynamic(format).save_with_format(path, format)
+    }
+
+    /// Encodes the display window as an image file in `format`, like `save`.
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, image::ImageError> {
+        let mut bytes = Cursor::new(Vec::new());
+        self.to_dynamic(format).write_to(&mut bytes, format)?;
+        Ok(bytes.into_inner())
+    }
+
+    /// Display window with the channels and bit depth written to `format`.
+    fn to_dynamic(&self, format: ImageFormat) -> image::DynamicImage {
+        let display = self.display_window;
+        let mut rgba = Vec::with_capacity(display.width * display.height * 4);
+        for y in display.y..display.bottom() {
+            for x in display.x..display.right() {
+                let [r, g, b, a] = self.rgba(x, y);
+                rgba.extend(if self.channels == 1 {
+                    [r, r, r, 1.0]
+                } else {
+                    [r, g, b, a]
+                });
+            }
+        }
+
+        let buffer =
+            image::Rgba32FImage::from_raw(display.width as u32, display.height as u32, rgba)
+                .expect("The buffer holds every pixel of the display window");
+        let image = image::DynamicImage::ImageRgba32F(buffer);
+        match format {
+            ImageFormat::OpenExr => image,
+            ImageFormat::Hdr => image::DynamicImage::ImageRgb32F(image.to_rgb32f()),
+            ImageFormat::Png | ImageFormat::Tiff => {
+                image::DynamicImage::ImageRgba16(image.to_rgba16())
+            }
+            ImageFormat::Jpeg => image::DynamicImage::ImageRgb8(image.to_rgb8()),
+            _ => image::DynamicImage::ImageRgba8(image.to_rgba8()),
+        }
+    }
+
+    pub fn display_window(&self) -> Window {
+        self.display_window
+    }
+
+    pub fn set_display_window(&mut self, window: Window) {
+        self.display_window = window;
+    }
+
+    pub fn data_window(&self) -> Window {
+        self.data_window
+    }
+
+    /// Width of the stored pixels, that is of the data window.
+    pub fn width(&self) -> usize {
+        self.data_window.width
+    }
+
+    /// Height of the stored pixels, that is of the data window.
+    pub fn height(&self) -> usize {
+        self.data_window.height
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    /// Pixel at `(x, y)` relative to the top-left corner of the data window.
+    pub fn pixel(&self, x: usize, y: usize) -> &[f32] {
+        let start = (y * self.data_window.width + x) * self.channels;
+        &self.data[start..start + self.channels]
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut [f32] {
+        let start = (y * self.data_window.width + x) * self.channels;
+        &mut self.data[start..start + self.channels]
+    }
+
+    /// Returns channel `c` of the pixel at absolute coordinates `(x, y)`,
+    /// or zero outside the data window.
+    pub fn get(&self, x: isize, y: isize, c: usize) -> f32 {
+        if !self.data_window.contains(x, y) {
+            return 0.0;
+        }
+        let x = (x - self.data_window.x) as usize;
+        let y = (y - self.data_window.y) as usize;
+        self.data[(y * self.data_window.width + x) * self.channels + c]
+    }
+
+    /// Returns the pixel at absolute coordinates `(x, y)` as RGBA.
+    ///
+    /// Single channel images are spread to all four channels and RGB images
+    /// are opaque, so they can be combined with RGBA images.
+    pub fn rgba(&self, x: isize, y: isize) -> [f32; 4] {
+        if !self.data_window.contains(x, y) {
+            return [0.0; 4];
+        }
+        let x = (x - self.data_window.x) as usize;
+        let y = (y - self.data_window.y) as usize;
+        pixel_rgba(self.pixel(x, y))
+    }
+
+    /// Copies the pixels inside `window` into a new image with the same display window.
+    pub fn crop_data(&self, window: Window) -> ImageBuffer {
+        let data_window = self.data_window.intersect(&window);
+        let channels = self.channels;
+        Self::from_fn_windows(self.display_window, data_window, channels, |x, y, out| {
+            for (c, value) in out.iter_mut().enumerate() {
+                *value = self.get(x, y, c);
+            }
+        })
+    }
+
+    /// Moves both windows by `(dx, dy)` without touching the pixels.
+    pub fn translate(&mut self, dx: isize, dy: isize) {
+        self.display_window = self.display_window.translate(dx, dy);
+        self.data_window = self.data_window.translate(dx, dy);
+    }
+
+    /// Converts the data window to 8 bit RGBA for display.
+    /// Single channel images are shown as opaque grey.
+    pub fn to_color_image(&self) -> egui::ColorImage {
+        let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        let mut rgba = Vec::with_capacity(self.width() * self.height() * 4);
+        for pixel in self.data.chunks_exact(self.channels) {
+            match *pixel {
+                [v] => rgba.extend([to_u8(v), to_u8(v), to_u8(v), 255]),
+                [v, a] => rgba.extend([to_u8(v), to_u8(v), to_u8(v), to_u8(a)]),
+                [r, g, b] => rgba.extend([to_u8(r), to_u8(g), to_u8(b), 255]),
+                [r, g, b, a, ..] => rgba.extend([to_u8(r), to_u8(g), to_u8(b), to_u8(a)]),
+                [] => unreachable!("Images have at least one channel"),
+            }
+        }
+
+        egui::ColorImage::from_rgba_unmultiplied([self.width(), self.height()], &rgba)
+    }
+}
+
+/// Reads a pixel of any channel count as RGBA, see `ImageBuffer::rgba`.
+pub fn pixel_rgba(pixel: &[f32]) -> [f32; 4] {
+    match *pixel {
+        [v] => [v, v, v, v],
+        [v, a] => [v, v, v, a],
+        [r, g, b] => [r, g, b, 1.0],
+        [r, g, b, a, ..] => [r, g, b, a],
+        [] => [0.0; 4],
+    }
+}
@@ -1,3 +1,19 @@
+pub mod batch;
+pub mod cache;
+pub mod curve;
+pub mod disk_cache;
+pub mod eval;
+pub mod frame_cache;
 pub mod graph_style;
+pub mod image;
 pub mod node;
 pub mod node_graph;
+pub mod node_property;
+pub mod ops;
+pub mod project;
+pub mod sequence;
+pub mod stdio;
+pub mod task;
+pub mod tile;
+pub mod validate;
+pub mod worker;
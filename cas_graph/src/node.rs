@@ -1,11 +1,26 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::curve::Curve;
+use crate::image::{ImageBuffer, Window};
+use crate::node_property::NodeProperty;
+use crate::ops::OpNode;
+use crate::tile;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum DemoNode {
     /// Node with single input.
     /// Displays the value of the input.
     Sink,
 
     /// Value node with a single output.
-    /// The value is editable in UI.
-    Number(f64),
+    /// The value is editable in UI and can be animated with a curve, which
+    /// replaces it when set.
+    Number(
+        f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")] Option<Curve>,
+    ),
 
     /// Value node with a single output.
     String(String),
@@ -16,30 +31,178 @@ pub enum DemoNode {
     /// Expression node with a single output.
     /// It has number of inputs equal to number of variables in the expression.
     ExprNode(ExprNode),
+
+    /// Per-pixel expression node with an image input and a single image output.
+    /// It has one expression per output channel and a number input for each
+    /// variable that isn't a pixel variable.
+    PixelExpr(PixelExprNode),
+
+    /// Image processing node.
+    /// Inputs and outputs depend on the type of operation.
+    Op(OpNode),
 }
 
 impl DemoNode {
-    const fn name(&self) -> &str {
+    pub const fn name(&self) -> &str {
         match self {
             DemoNode::Sink => "Sink",
-            DemoNode::Number(_) => "Number",
+            DemoNode::Number(..) => "Number",
             DemoNode::String(_) => "String",
             DemoNode::ShowImage(_) => "ShowImage",
             DemoNode::ExprNode(_) => "ExprNode",
+            DemoNode::PixelExpr(_) => "PixelExpr",
+            DemoNode::Op(op) => op.op_type.name(),
         }
     }
 
-    pub fn number_out(&self) -> f64 {
+    /// Number of input pins.
+    pub fn inputs(&self) -> usize {
         match self {
-            DemoNode::Number(value) => *value,
-            DemoNode::ExprNode(expr_node) => expr_node.eval(),
+            DemoNode::Sink | DemoNode::ShowImage(_) => 1,
+            DemoNode::Number(..) | DemoNode::String(_) => 0,
+            DemoNode::ExprNode(expr_node) => 1 + expr_node.bindings.len(),
+            DemoNode::PixelExpr(pixel_expr) => {
+                PixelExprNode::FIRST_UNIFORM + pixel_expr.uniforms.len()
+            }
+            DemoNode::Op(op) => op.op_type.inputs().len() + op.properties.len(),
+        }
+    }
+
+    /// Number of output pins.
+    pub fn outputs(&self) -> usize {
+        match self {
+            DemoNode::Sink => 0,
+            DemoNode::Number(..)
+            | DemoNode::String(_)
+            | DemoNode::ShowImage(_)
+            | DemoNode::ExprNode(_)
+            | DemoNode::PixelExpr(_) => 1,
+            DemoNode::Op(op) => op.op_type.outputs().len(),
+        }
+    }
+
+    /// Name of an input pin outside the editor. Property inputs have the
+    /// name of their property and pins that don't exist an empty name.
+    pub fn input_name(&self, idx: usize) -> &str {
+        match self {
+            DemoNode::Sink => "Input",
+            DemoNode::Number(..) | DemoNode::String(_) => "",
+            DemoNode::ShowImage(_) => "URL",
+            DemoNode::ExprNode(_) if idx == 0 => "Expression",
+            DemoNode::ExprNode(expr_node) => {
+                expr_node.bindings.get(idx - 1).map_or("", String::as_str)
+            }
+            DemoNode::PixelExpr(_) if idx == 0 => "Source",
+            DemoNode::PixelExpr(_) if idx < PixelExprNode::FIRST_UNIFORM => {
+                PixelExprNode::CHANNEL_NAMES[idx - PixelExprNode::FIRST_CHANNEL]
+            }
+            DemoNode::PixelExpr(pixel_expr) => pixel_expr
+                .uniforms
+                .get(idx - PixelExprNode::FIRST_UNIFORM)
+                .map_or("", String::as_str),
+            DemoNode::Op(op) => {
+                let inputs = op.op_type.inputs();
+                match inputs.get(idx) {
+                    Some((name, _)) => name,
+                    None => op
+                        .properties
+                        .get(idx - inputs.len())
+                        .map_or("", NodeProperty::name),
+                }
+            }
+        }
+    }
+
+    /// Name of an output pin outside the editor.
+    pub fn output_name(&self, idx: usize) -> &str {
+        match self {
+            DemoNode::Op(op) => op.op_type.outputs().get(idx).map_or("", |(name, _)| name),
+            _ => "Output",
+        }
+    }
+
+    /// Feeds everything that affects the outputs of the node at `frame` to
+    /// `state`. Two nodes with the same content produce the same outputs
+    /// from the same inputs.
+    pub fn hash_content<H: Hasher>(&self, frame: i64, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            DemoNode::Sink => {}
+            DemoNode::Number(..) => self.number_out(frame).map(f64::to_bits).hash(state),
+            DemoNode::String(value) | DemoNode::ShowImage(value) => value.hash(state),
+            DemoNode::ExprNode(expr_node) => expr_node.hash_content(state),
+            DemoNode::PixelExpr(pixel_expr) => {
+                pixel_expr.channels.hash(state);
+                hash_floats(&pixel_expr.values, state);
+            }
+            DemoNode::Op(op) => op.hash_content(frame, state),
+        }
+    }
+
+    /// Value of the number output at `frame`, or `None` for image nodes,
+    /// whose numbers are only known after evaluating the graph.
+    pub fn number_out(&self, frame: i64) -> Option<f64> {
+        match self {
+            DemoNode::Number(_, Some(curve)) => Some(curve.value_at(frame)),
+            DemoNode::Number(value, None) => Some(*value),
+            DemoNode::ExprNode(expr_node) => Some(expr_node.eval()),
+            DemoNode::Op(_) => None,
             _ => unreachable!(),
         }
     }
 
+    /// Names of the numbers of the node that can be animated, by parameter
+    /// index: the value of Number nodes and the number properties of image
+    /// nodes, whose index is the one of the property.
+    pub fn parameters(&self) -> Vec<(usize, &str)> {
+        match self {
+            DemoNode::Number(..) => vec![(0, "Value")],
+            DemoNode::Op(op) => op
+                .properties
+                .iter()
+                .enumerate()
+                .filter(|(_, property)| {
+                    matches!(property, NodeProperty::Float(_) | NodeProperty::Int(_))
+                })
+                .map(|(idx, property)| (idx, property.name()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Value of a parameter at `frame`.
+    pub fn parameter_value(&self, parameter: usize, frame: i64) -> Option<f64> {
+        match self {
+            DemoNode::Number(..) if parameter == 0 => self.number_out(frame),
+            DemoNode::Op(op) => op.properties.get(parameter)?.number(frame),
+            _ => None,
+        }
+    }
+
+    /// Curve of an animated parameter.
+    pub fn curve(&self, parameter: usize) -> Option<&Curve> {
+        match self {
+            DemoNode::Number(_, curve) if parameter == 0 => curve.as_ref(),
+            DemoNode::Op(op) => op.properties.get(parameter)?.curve(),
+            _ => None,
+        }
+    }
+
+    /// Curve of a parameter, `None` if the node has no such parameter.
+    pub fn curve_mut(&mut self, parameter: usize) -> Option<&mut Option<Curve>> {
+        match self {
+            DemoNode::Number(_, curve) if parameter == 0 => Some(curve),
+            DemoNode::Op(op) => op.properties.get_mut(parameter)?.curve_mut(),
+            _ => None,
+        }
+    }
+
     pub fn number_in(&mut self, idx: usize) -> &mut f64 {
         match self {
             DemoNode::ExprNode(expr_node) => &mut expr_node.values[idx - 1],
+            DemoNode::PixelExpr(pixel_expr) => {
+                &mut pixel_expr.values[idx - PixelExprNode::FIRST_UNIFORM]
+            }
             _ => unreachable!(),
         }
     }
@@ -48,6 +211,9 @@ impl DemoNode {
         match self {
             DemoNode::ShowImage(_) if idx == 0 => "URL",
             DemoNode::ExprNode(expr_node) => &expr_node.bindings[idx - 1],
+            DemoNode::PixelExpr(pixel_expr) => {
+                &pixel_expr.uniforms[idx - PixelExprNode::FIRST_UNIFORM]
+            }
             _ => unreachable!(),
         }
     }
@@ -73,8 +239,23 @@ impl DemoNode {
             _ => unreachable!(),
         }
     }
+
+    pub fn pixel_expr(&mut self) -> &mut PixelExprNode {
+        match self {
+            DemoNode::PixelExpr(pixel_expr) => pixel_expr,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn op_node(&mut self) -> &mut OpNode {
+        match self {
+            DemoNode::Op(op) => op,
+            _ => unreachable!(),
+        }
+    }
 }
 
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct ExprNode {
     pub text: String,
     pub bindings: Vec<String>,
@@ -93,15 +274,168 @@ impl ExprNode {
     }
 
     pub fn eval(&self) -> f64 {
-        self.expr.eval(&self.bindings, &self.values)
+        self.eval_with(&self.values)
+    }
+
+    /// Feeds the expression and the values of its variables to `state`.
+    pub fn hash_content<H: Hasher>(&self, state: &mut H) {
+        self.text.hash(state);
+        hash_floats(&self.values, state);
+    }
+
+    /// Evaluates the expression with `values` in place of the stored values.
+    pub fn eval_with(&self, values: &[f64]) -> f64 {
+        self.expr.eval(&self.bindings, values)
     }
 }
 
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PixelExprNode {
+    /// Expression text of the red, green, blue and alpha output channels.
+    pub channels: [String; 4],
+    pub exprs: [Expr; 4],
+    /// Variables of the expressions that aren't pixel variables, in order of appearance.
+    pub uniforms: Vec<String>,
+    pub values: Vec<f64>,
+}
+
+impl PixelExprNode {
+    /// Variables set from the pixel being computed, in the order passed to the expressions.
+    pub const PIXEL_VARIABLES: [&'static str; 10] =
+        ["r", "g", "b", "a", "x", "y", "u", "v", "width", "height"];
+    pub const CHANNEL_NAMES: [&'static str; 4] = ["r", "g", "b", "a"];
+
+    /// Input pin of the first channel expression. Pin 0 is the source image.
+    pub const FIRST_CHANNEL: usize = 1;
+    /// Input pin of the first uniform.
+    pub const FIRST_UNIFORM: usize = Self::FIRST_CHANNEL + 4;
+
+    pub fn new() -> Self {
+        PixelExprNode {
+            channels: Self::CHANNEL_NAMES.map(str::to_owned),
+            exprs: Self::CHANNEL_NAMES.map(|name| Expr::Var(name.to_owned())),
+            uniforms: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Parses the text of a channel. On success the uniforms are updated,
+    /// keeping the values of those still in use, and the previous uniforms are returned.
+    pub fn parse_channel(&mut self, channel: usize) -> Option<Vec<String>> {
+        self.exprs[channel] = syn::parse_str(&self.channels[channel]).ok()?;
+
+        let mut bindings = Vec::new();
+        for expr in &self.exprs {
+            expr.extend_bindings(&mut bindings);
+        }
+        bindings.retain(|name| !Self::PIXEL_VARIABLES.contains(&name.as_str()));
+
+        let values = bindings
+            .iter()
+            .map(|name| {
+                self.uniforms
+                    .iter()
+                    .position(|uniform| uniform == name)
+                    .map_or(0.0, |idx| self.values[idx])
+            })
+            .collect();
+        self.values = values;
+        Some(std::mem::replace(&mut self.uniforms, bindings))
+    }
+
+    /// Runs the expressions for every pixel of the data window of `src`.
+    /// `uniforms` holds a value for each of `self.uniforms`.
+    ///
+    /// `x` and `y` are pixel coordinates, `u` and `v` go from 0 to 1 across
+    /// the display window, and `width` and `height` are its size.
+    pub fn process(&self, src: &ImageBuffer, uniforms: &[f64]) -> ImageBuffer {
+        let program = self.compile(src.display_window(), uniforms);
+        let mut image = ImageBuffer::with_windows(src.display_window(), src.data_window(), 4);
+
+        // Every thread keeps its own arguments and evaluation stack
+        tile::fill_with(
+            &mut image,
+            || program.scratch(),
+            |scratch, x, y, out| program.eval(scratch, x, y, src.rgba(x, y), out),
+        );
+        image
+    }
+
+    /// Compiles the expressions for images with the display window `display`.
+    pub fn compile(&self, display: Window, uniforms: &[f64]) -> PixelProgram {
+        let bindings = Self::PIXEL_VARIABLES
+            .iter()
+            .map(|name| name.to_string())
+            .chain(self.uniforms.iter().cloned())
+            .collect::<Vec<_>>();
+        PixelProgram {
+            programs: self.exprs.each_ref().map(|expr| expr.compile(&bindings)),
+            uniforms: uniforms.to_vec(),
+            display,
+        }
+    }
+}
+
+/// Expressions of a Pixel Expression node compiled for one evaluation.
+pub struct PixelProgram {
+    programs: [Program; 4],
+    uniforms: Vec<f64>,
+    display: Window,
+}
+
+impl PixelProgram {
+    /// Arguments and evaluation stack, which every thread keeps its own of.
+    pub fn scratch(&self) -> (Vec<f64>, Vec<f64>) {
+        let mut args = vec![0.0; PixelExprNode::PIXEL_VARIABLES.len()];
+        args.extend_from_slice(&self.uniforms);
+        (args, Vec::new())
+    }
+
+    /// Computes the RGBA output of the pixel at `(x, y)` whose input is `rgba`.
+    pub fn eval(
+        &self,
+        (args, stack): &mut (Vec<f64>, Vec<f64>),
+        x: isize,
+        y: isize,
+        rgba: [f32; 4],
+        out: &mut [f32],
+    ) {
+        let display = self.display;
+        let (width, height) = (display.width as f64, display.height as f64);
+        args[..4].copy_from_slice(&rgba.map(f64::from));
+        args[4] = x as f64;
+        args[5] = y as f64;
+        args[6] = ((x - display.x) as f64 + 0.5) / width;
+        args[7] = ((y - display.y) as f64 + 0.5) / height;
+        args[8] = width;
+        args[9] = height;
+
+        for (value, program) in out.iter_mut().zip(&self.programs) {
+            *value = program.eval(args, stack) as f32;
+        }
+    }
+}
+
+impl Default for PixelExprNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hash_floats<H: Hasher>(values: &[f64], state: &mut H) {
+    values.len().hash(state);
+    for value in values {
+        value.to_bits().hash(state);
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 enum UnOp {
     Pos,
     Neg,
 }
 
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 enum BinOp {
     Add,
     Sub,
@@ -109,6 +443,7 @@ enum BinOp {
     Div,
 }
 
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum Expr {
     Var(String),
     Val(f64),
@@ -123,7 +458,86 @@ pub enum Expr {
     },
 }
 
+/// Instruction of a compiled expression, run on a value stack.
+#[derive(Clone, Copy, Debug)]
+enum Instr {
+    Val(f64),
+    Var(usize),
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Expression compiled for repeated evaluation, with variables resolved to argument indices.
+#[derive(Clone, Debug)]
+pub struct Program {
+    code: Vec<Instr>,
+}
+
+impl Program {
+    /// Evaluates the program. `stack` is scratch space that can be reused between calls.
+    pub fn eval(&self, args: &[f64], stack: &mut Vec<f64>) -> f64 {
+        stack.clear();
+        for instr in &self.code {
+            let value = match *instr {
+                Instr::Val(value) => value,
+                Instr::Var(idx) => args[idx],
+                Instr::Neg => -stack.pop().unwrap(),
+                Instr::Add | Instr::Sub | Instr::Mul | Instr::Div => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+                    match instr {
+                        Instr::Add => lhs + rhs,
+                        Instr::Sub => lhs - rhs,
+                        Instr::Mul => lhs * rhs,
+                        _ => lhs / rhs,
+                    }
+                }
+            };
+            stack.push(value);
+        }
+        stack.pop().unwrap_or(0.0)
+    }
+}
+
 impl Expr {
+    /// Compiles the expression. Variables missing from `bindings` evaluate to zero.
+    pub fn compile(&self, bindings: &[String]) -> Program {
+        let mut code = Vec::new();
+        self.compile_into(bindings, &mut code);
+        Program { code }
+    }
+
+    fn compile_into(&self, bindings: &[String], code: &mut Vec<Instr>) {
+        match self {
+            Expr::Var(name) => code.push(
+                bindings
+                    .iter()
+                    .position(|binding| binding == name)
+                    .map_or(Instr::Val(0.0), Instr::Var),
+            ),
+            Expr::Val(value) => code.push(Instr::Val(*value)),
+            Expr::UnOp { op, expr } => {
+                expr.compile_into(bindings, code);
+                if let UnOp::Neg = op {
+                    code.push(Instr::Neg);
+                }
+            }
+            Expr::BinOp { lhs, op, rhs } => {
+                lhs.compile_into(bindings, code);
+                rhs.compile_into(bindings, code);
+                code.push(match op {
+                    BinOp::Add => Instr::Add,
+                    BinOp::Sub => Instr::Sub,
+                    BinOp::Mul => Instr::Mul,
+                    BinOp::Div => Instr::Div,
+                });
+            }
+        }
+    }
+
     fn eval(&self, bindings: &[String], args: &[f64]) -> f64 {
         let binding_index =
             |name: &str| bindings.iter().position(|binding| binding == name).unwrap();
@@ -212,14 +626,14 @@ impl syn::parse::Parse for Expr {
                 return Ok(expr);
             }
             lhs = expr;
-        // } else if lookahead.peek(syn::LitFloat) {
-        //     let lit = input.parse::<syn::LitFloat>()?;
-        //     let value = lit.base10_parse::<f64>()?;
-        //     let expr = Expr::Val(value);
-        //     if input.is_empty() {
-        //         return Ok(expr);
-        //     }
-        //     lhs = expr;
+        } else if lookahead.peek(syn::LitFloat) {
+            let lit = input.parse::<syn::LitFloat>()?;
+            let value = lit.base10_parse::<f64>()?;
+            let expr = Expr::Val(value);
+            if input.is_empty() {
+                return Ok(expr);
+            }
+            lhs = expr;
         } else if lookahead.peek(syn::LitInt) {
             let lit = input.parse::<syn::LitInt>()?;
             let value = lit.base10_parse::<f64>()?;
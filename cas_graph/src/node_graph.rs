@@ -1,6 +1,8 @@
 #![allow(clippy::use_self)]
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::Hasher;
 
 use egui::{Color32, Ui};
 use egui_snarl::{
@@ -8,57 +10,247 @@ use egui_snarl::{
     InPin, InPinId, NodeId, OutPin, OutPinId, Snarl,
 };
 
-use crate::node::{DemoNode, ExprNode};
+use crate::curve::Curve;
+use crate::eval::Value;
+use crate::node::{DemoNode, ExprNode, PixelExprNode};
+use crate::node_property::NodeProperty;
+use crate::ops::{NodeCategory, OpNode, OpType, PinType};
+use crate::sequence::{self, FrameRange};
 
 const STRING_COLOR: Color32 = Color32::from_rgb(0x00, 0xb0, 0x00);
 const NUMBER_COLOR: Color32 = Color32::from_rgb(0xb0, 0x00, 0x00);
 const IMAGE_COLOR: Color32 = Color32::from_rgb(0xb0, 0x00, 0xb0);
 const UNTYPED_COLOR: Color32 = Color32::from_rgb(0xb0, 0xb0, 0xb0);
+const VIEWED_COLOR: Color32 = Color32::from_rgb(0xe0, 0xc0, 0x40);
+const KEY_COLOR: Color32 = Color32::from_rgb(0xe0, 0xc0, 0x40);
+const ANIMATED_COLOR: Color32 = Color32::from_rgb(0x60, 0x90, 0xd0);
 
-pub struct DemoViewer;
+pub(crate) type PinCompat = usize;
+const PIN_NUM: PinCompat = 1;
+const PIN_STR: PinCompat = 2;
+const PIN_IMG: PinCompat = 4;
+const PIN_SINK: PinCompat = PIN_NUM | PIN_STR | PIN_IMG;
 
-impl SnarlViewer<DemoNode> for DemoViewer {
-    #[inline]
-    fn connect(&mut self, from: &OutPin, to: &InPin, snarl: &mut Snarl<DemoNode>) {
-        // Validate connection
-        #[allow(clippy::match_same_arms)] // For match clarity
-        match (&snarl[from.id.node], &snarl[to.id.node]) {
-            (DemoNode::Sink, _) => {
-                unreachable!("Sink node has no outputs")
-            }
-            (_, DemoNode::Sink) => {}
-            (_, DemoNode::Number(_)) => {
-                unreachable!("Number node has no inputs")
-            }
-            (_, DemoNode::String(_)) => {
-                unreachable!("String node has no inputs")
+const fn pin_type_compat(ty: PinType) -> PinCompat {
+    match ty {
+        PinType::Number => PIN_NUM,
+        PinType::String => PIN_STR,
+        PinType::Image => PIN_IMG,
+    }
+}
+
+pub(crate) fn pin_out_compat(node: &DemoNode, output: usize) -> PinCompat {
+    match node {
+        DemoNode::Sink => 0,
+        DemoNode::String(_) => PIN_STR,
+        DemoNode::ShowImage(_) => PIN_IMG,
+        DemoNode::Number(..) | DemoNode::ExprNode(_) => PIN_NUM,
+        DemoNode::PixelExpr(_) => PIN_IMG,
+        DemoNode::Op(op) => op
+            .op_type
+            .outputs()
+            .get(output)
+            .map_or(0, |(_, ty)| pin_type_compat(*ty)),
+    }
+}
+
+pub(crate) fn pin_in_compat(node: &DemoNode, pin: usize) -> PinCompat {
+    match node {
+        DemoNode::Sink => PIN_SINK,
+        DemoNode::Number(..) | DemoNode::String(_) => 0,
+        DemoNode::ShowImage(_) => PIN_STR,
+        DemoNode::ExprNode(_) => {
+            if pin == 0 {
+                PIN_STR
+            } else {
+                PIN_NUM
             }
-            (DemoNode::Number(_), DemoNode::ShowImage(_)) => {
-                return;
+        }
+        DemoNode::PixelExpr(_) => match pin {
+            0 => PIN_IMG,
+            pin if pin < PixelExprNode::FIRST_UNIFORM => PIN_STR,
+            _ => PIN_NUM,
+        },
+        DemoNode::Op(op) => {
+            let inputs = op.op_type.inputs();
+            if let Some((_, ty)) = inputs.get(pin) {
+                return pin_type_compat(*ty);
             }
-            (DemoNode::ShowImage(_), DemoNode::ShowImage(_)) => {
-                return;
+            match op.properties.get(pin - inputs.len()) {
+                Some(NodeProperty::Float(_) | NodeProperty::Int(_)) => PIN_NUM,
+                Some(NodeProperty::Path(_) | NodeProperty::Text(_)) => PIN_STR,
+                Some(NodeProperty::Choice(_) | NodeProperty::Color(_)) | None => 0,
             }
-            (DemoNode::String(_), DemoNode::ShowImage(_)) => {}
-            (DemoNode::ExprNode(_), DemoNode::ExprNode(_)) if to.id.input == 0 => {
-                return;
+        }
+    }
+}
+
+const fn pin_color(ty: PinType) -> Color32 {
+    match ty {
+        PinType::Number => NUMBER_COLOR,
+        PinType::String => STRING_COLOR,
+        PinType::Image => IMAGE_COLOR,
+    }
+}
+
+#[derive(Default)]
+pub struct DemoViewer {
+    /// Node whose first output is shown in the image viewer.
+    pub viewed: Option<NodeId>,
+    /// Node being evaluated in the background and the fraction of it that is done.
+    pub progress: Option<(NodeId, f32)>,
+    /// Write nodes picked for rendering in their node menu, taken by the app.
+    pub render_requests: Vec<NodeId>,
+    /// Nodes picked for freezing in their node menu, taken by the app.
+    pub freeze_requests: Vec<NodeId>,
+    /// Whether the disk cache the outputs of frozen nodes are kept in is on, set by the app.
+    pub disk_cache: bool,
+    /// Chains of nodes the last evaluation ran as a single pass.
+    pub fused: Vec<Vec<NodeId>>,
+    /// Frame of image sequences the graph is evaluated at, set by the app.
+    pub frame: i64,
+    /// Frame range of a sequence on disk picked in a Read node menu, taken by the app.
+    pub frame_range_request: Option<FrameRange>,
+    /// Node and parameter index of a curve picked for the curve editor, taken by the app.
+    pub curve_request: Option<(NodeId, usize)>,
+    /// Values of expression nodes with the hash of the content and inputs
+    /// they were computed for. An expression is evaluated again only when
+    /// its hash changes.
+    expr_values: HashMap<NodeId, (u64, f64)>,
+}
+
+impl DemoViewer {
+    fn expr_value(&mut self, node: NodeId, expr_node: &ExprNode, values: &[f64]) -> f64 {
+        let mut hasher = DefaultHasher::new();
+        expr_node.hash_content(&mut hasher);
+        for value in values {
+            hasher.write_u64(value.to_bits());
+        }
+        let hash = hasher.finish();
+        match self.expr_values.get(&node) {
+            Some(&(cached, value)) if cached == hash => value,
+            _ => {
+                let value = expr_node.eval_with(values);
+                self.expr_values.insert(node, (hash, value));
+                value
             }
-            (DemoNode::ExprNode(_), DemoNode::ExprNode(_)) => {}
-            (DemoNode::Number(_), DemoNode::ExprNode(_)) if to.id.input == 0 => {
-                return;
+        }
+    }
+
+    /// Shows whether a number parameter has a key at the current frame and
+    /// toggles the key when clicked. Its menu opens the curve in the curve
+    /// editor and removes the animation, in which case the value at the
+    /// current frame is returned to keep as the value of the parameter.
+    fn key_button(
+        &mut self,
+        ui: &mut Ui,
+        node: NodeId,
+        parameter: usize,
+        curve: &mut Option<Curve>,
+        value: f64,
+    ) -> Option<f64> {
+        let frame = self.frame;
+        let (text, hover) = match curve {
+            Some(curve) if curve.key_index(frame).is_some() => (
+                egui::RichText::new("◆").color(KEY_COLOR),
+                "Key at this frame. Click to remove it",
+            ),
+            Some(_) => (
+                egui::RichText::new("◇").color(ANIMATED_COLOR),
+                "Animated. Click to set a key at this frame",
+            ),
+            None => (
+                egui::RichText::new("◇").weak(),
+                "Click to animate from this frame",
+            ),
+        };
+        let response = ui
+            .add(egui::Button::new(text).frame(false))
+            .on_hover_text(hover);
+
+        let mut removed = None;
+        if response.clicked() {
+            match curve {
+                None => *curve = Some(Curve::new(frame, value)),
+                Some(keys) => match keys.key_index(frame) {
+                    // Removing the last key removes the animation
+                    Some(_) if keys.keys().len() == 1 => {
+                        removed = Some(keys.value_at(frame));
+                        *curve = None;
+                    }
+                    Some(idx) => keys.remove_key(idx),
+                    None => {
+                        keys.set_key(frame, keys.value_at(frame));
+                    }
+                },
             }
-            (DemoNode::Number(_), DemoNode::ExprNode(_)) => {}
-            (DemoNode::String(_), DemoNode::ExprNode(_)) if to.id.input == 0 => {}
-            (DemoNode::String(_), DemoNode::ExprNode(_)) => {
-                return;
+        }
+        response.context_menu(|ui| {
+            if ui.button("Edit Curve").clicked() {
+                self.curve_request = Some((node, parameter));
+                ui.close_menu();
             }
-            (DemoNode::ShowImage(_), DemoNode::ExprNode(_)) => {
-                return;
+            if let Some(keys) = curve.as_ref() {
+                if ui.button("Delete Animation").clicked() {
+                    removed = Some(keys.value_at(frame));
+                    *curve = None;
+                    ui.close_menu();
+                }
             }
-            (DemoNode::ExprNode(_), DemoNode::ShowImage(_)) => {
-                return;
+        });
+        removed
+    }
+
+    /// Like `DemoNode::number_out`, with cached values for expression nodes.
+    /// Expressions use the numbers arriving at their inputs, which are only
+    /// shown and never stored in the graph, so the graph doesn't change when
+    /// an animated number upstream does.
+    fn number_out(&mut self, snarl: &Snarl<DemoNode>, node: NodeId) -> Option<f64> {
+        self.number_out_visiting(snarl, node, &mut Vec::new())
+    }
+
+    /// `number_out` while looking up the inputs of the expressions in
+    /// `visiting`. Inputs wired in a cycle keep their stored values.
+    fn number_out_visiting(
+        &mut self,
+        snarl: &Snarl<DemoNode>,
+        node: NodeId,
+        visiting: &mut Vec<NodeId>,
+    ) -> Option<f64> {
+        let DemoNode::ExprNode(expr_node) = &snarl[node] else {
+            return snarl[node].number_out(self.frame);
+        };
+        visiting.push(node);
+        let mut values = expr_node.values.clone();
+        for (idx, value) in values.iter_mut().enumerate() {
+            let remote = snarl.in_pin(InPinId {
+                node,
+                input: idx + 1,
+            });
+            let remote = remote
+                .remotes
+                .first()
+                .filter(|remote| !visiting.contains(&remote.node));
+            if let Some(input) =
+                remote.and_then(|remote| self.number_out_visiting(snarl, remote.node, visiting))
+            {
+                *value = input;
             }
         }
+        visiting.pop();
+        Some(self.expr_value(node, expr_node, &values))
+    }
+}
+
+impl SnarlViewer<DemoNode> for DemoViewer {
+    #[inline]
+    fn connect(&mut self, from: &OutPin, to: &InPin, snarl: &mut Snarl<DemoNode>) {
+        // Validate connection
+        let from_ty = pin_out_compat(&snarl[from.id.node], from.id.output);
+        let to_ty = pin_in_compat(&snarl[to.id.node], to.id.input);
+        if from_ty & to_ty == 0 {
+            return;
+        }
 
         for &remote in &to.remotes {
             snarl.disconnect(remote, to.id);
@@ -67,32 +259,51 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         snarl.connect(from.id, to.id);
     }
 
+    fn show_header(
+        &mut self,
+        node: NodeId,
+        _inputs: &[InPin],
+        _outputs: &[OutPin],
+        ui: &mut Ui,
+        snarl: &mut Snarl<DemoNode>,
+    ) {
+        ui.label(self.title(&snarl[node]));
+        if matches!(&snarl[node], DemoNode::Op(op) if op.frozen.is_some()) {
+            ui.weak("(frozen)");
+        }
+        if let Some(chain) = self.fused.iter().find(|chain| chain.contains(&node)) {
+            ui.weak("(fused)").on_hover_text(format!(
+                "Runs in a single pass with {} other nodes",
+                chain.len() - 1
+            ));
+        }
+        if let Some((_, fraction)) = self.progress.filter(|&(running, _)| running == node) {
+            ui.add(
+                egui::ProgressBar::new(fraction)
+                    .desired_width(60.0)
+                    .show_percentage(),
+            );
+        }
+    }
+
     fn title(&mut self, node: &DemoNode) -> String {
         match node {
             DemoNode::Sink => "Sink".to_owned(),
-            DemoNode::Number(_) => "Number".to_owned(),
+            DemoNode::Number(..) => "Number".to_owned(),
             DemoNode::String(_) => "String".to_owned(),
             DemoNode::ShowImage(_) => "Show image".to_owned(),
             DemoNode::ExprNode(_) => "Expr".to_owned(),
+            DemoNode::PixelExpr(_) => "PixelExpr".to_owned(),
+            DemoNode::Op(op) => op.op_type.name().to_owned(),
         }
     }
 
     fn inputs(&mut self, node: &DemoNode) -> usize {
-        match node {
-            DemoNode::Sink | DemoNode::ShowImage(_) => 1,
-            DemoNode::Number(_) | DemoNode::String(_) => 0,
-            DemoNode::ExprNode(expr_node) => 1 + expr_node.bindings.len(),
-        }
+        node.inputs()
     }
 
     fn outputs(&mut self, node: &DemoNode) -> usize {
-        match node {
-            DemoNode::Sink => 0,
-            DemoNode::Number(_)
-            | DemoNode::String(_)
-            | DemoNode::ShowImage(_)
-            | DemoNode::ExprNode(_) => 1,
-        }
+        node.outputs()
     }
 
     #[allow(clippy::too_many_lines)]
@@ -109,9 +320,9 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                     }
                     [remote] => match snarl[remote.node] {
                         DemoNode::Sink => unreachable!("Sink node has no outputs"),
-                        DemoNode::Number(value) => {
+                        ref node @ DemoNode::Number(..) => {
                             assert_eq!(remote.output, 0, "Number node has only one output");
-                            ui.label(format_float(value));
+                            ui.label(format_float(node.number_out(self.frame).unwrap()));
                             PinInfo::circle().with_fill(NUMBER_COLOR)
                         }
                         DemoNode::String(ref value) => {
@@ -124,9 +335,10 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                                 },
                             )
                         }
-                        DemoNode::ExprNode(ref expr) => {
+                        DemoNode::ExprNode(_) => {
                             assert_eq!(remote.output, 0, "Expr node has only one output");
-                            ui.label(format_float(expr.eval()));
+                            let value = self.number_out(snarl, remote.node).unwrap_or_default();
+                            ui.label(format_float(value));
                             PinInfo::circle().with_fill(NUMBER_COLOR)
                         }
                         DemoNode::ShowImage(ref uri) => {
@@ -137,11 +349,20 @@ impl SnarlViewer<DemoNode> for DemoViewer {
 
                             PinInfo::circle().with_fill(IMAGE_COLOR)
                         }
+                        DemoNode::PixelExpr(_) => {
+                            ui.label("Output");
+                            PinInfo::circle().with_fill(IMAGE_COLOR)
+                        }
+                        DemoNode::Op(ref op) => {
+                            let (name, ty) = op.op_type.outputs()[remote.output];
+                            ui.label(name);
+                            PinInfo::circle().with_fill(pin_color(ty))
+                        }
                     },
                     _ => unreachable!("Sink input has only one wire"),
                 }
             }
-            DemoNode::Number(_) => {
+            DemoNode::Number(..) => {
                 unreachable!("Number node has no inputs")
             }
             DemoNode::String(_) => {
@@ -239,36 +460,7 @@ impl SnarlViewer<DemoNode> for DemoViewer {
 
                         expr_node.values = new_values;
 
-                        let old_inputs = (0..old_bindings.len())
-                            .map(|idx| {
-                                snarl.in_pin(InPinId {
-                                    node: pin.id.node,
-                                    input: idx + 1,
-                                })
-                            })
-                            .collect::<Vec<_>>();
-
-                        for (idx, name) in old_bindings.iter().enumerate() {
-                            let new_idx =
-                                new_bindings.iter().position(|new_name| *new_name == *name);
-
-                            match new_idx {
-                                None => {
-                                    snarl.drop_inputs(old_inputs[idx].id);
-                                }
-                                Some(new_idx) if new_idx != idx => {
-                                    let new_in_pin = InPinId {
-                                        node: pin.id.node,
-                                        input: new_idx,
-                                    };
-                                    for &remote in &old_inputs[idx].remotes {
-                                        snarl.disconnect(remote, old_inputs[idx].id);
-                                        snarl.connect(remote, new_in_pin);
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
+                        rebind_inputs(snarl, pin.id.node, 1, &old_bindings, &new_bindings);
                     }
                 }
                 PinInfo::circle()
@@ -287,11 +479,9 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                             PinInfo::circle().with_fill(NUMBER_COLOR)
                         }
                         [remote] => {
-                            let new_value = snarl[remote.node].number_out();
-                            let node = &mut snarl[pin.id.node];
-                            ui.label(node.label_in(pin.id.input));
-                            ui.label(format_float(new_value));
-                            *node.number_in(pin.id.input) = new_value;
+                            let value = self.number_out(snarl, remote.node);
+                            ui.label(snarl[pin.id.node].label_in(pin.id.input));
+                            show_wired(ui, value.map(Value::Number).as_ref());
                             PinInfo::circle().with_fill(NUMBER_COLOR)
                         }
                         _ => unreachable!("Expr pins has only one wire"),
@@ -301,6 +491,109 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                     PinInfo::circle().with_fill(Color32::BLACK)
                 }
             }
+            DemoNode::PixelExpr(_) if pin.id.input == 0 => {
+                ui.label("Source");
+                PinInfo::circle().with_fill(IMAGE_COLOR)
+            }
+            DemoNode::PixelExpr(_) if pin.id.input < PixelExprNode::FIRST_UNIFORM => {
+                let channel = pin.id.input - PixelExprNode::FIRST_CHANNEL;
+                ui.label(format!("{} =", PixelExprNode::CHANNEL_NAMES[channel]));
+
+                let changed = match &*pin.remotes {
+                    [] => {
+                        let text = &mut snarl[pin.id.node].pixel_expr().channels[channel];
+                        egui::TextEdit::singleline(text)
+                            .clip_text(false)
+                            .desired_width(0.0)
+                            .margin(ui.spacing().item_spacing)
+                            .show(ui)
+                            .response
+                            .changed()
+                    }
+                    [remote] => {
+                        let new_string = snarl[remote.node].string_out().to_owned();
+                        ui.label(&new_string);
+
+                        let text = &mut snarl[pin.id.node].pixel_expr().channels[channel];
+                        if new_string == *text {
+                            false
+                        } else {
+                            *text = new_string;
+                            true
+                        }
+                    }
+                    _ => unreachable!("PixelExpr pins has only one wire"),
+                };
+
+                if changed {
+                    let pixel_expr = snarl[pin.id.node].pixel_expr();
+                    if let Some(old_uniforms) = pixel_expr.parse_channel(channel) {
+                        let new_uniforms = pixel_expr.uniforms.clone();
+                        rebind_inputs(
+                            snarl,
+                            pin.id.node,
+                            PixelExprNode::FIRST_UNIFORM,
+                            &old_uniforms,
+                            &new_uniforms,
+                        );
+                    }
+                }
+
+                PinInfo::circle()
+                    .with_fill(STRING_COLOR)
+                    .with_wire_style(WireStyle::AxisAligned {
+                        corner_radius: 10.0,
+                    })
+            }
+            DemoNode::PixelExpr(ref pixel_expr) => {
+                if pin.id.input - PixelExprNode::FIRST_UNIFORM < pixel_expr.uniforms.len() {
+                    match &*pin.remotes {
+                        [] => {
+                            let node = &mut snarl[pin.id.node];
+                            ui.label(node.label_in(pin.id.input));
+                            ui.add(egui::DragValue::new(node.number_in(pin.id.input)));
+                        }
+                        [remote] => {
+                            let value = self.number_out(snarl, remote.node);
+                            ui.label(snarl[pin.id.node].label_in(pin.id.input));
+                            show_wired(ui, value.map(Value::Number).as_ref());
+                        }
+                        _ => unreachable!("PixelExpr pins has only one wire"),
+                    }
+                    PinInfo::circle().with_fill(NUMBER_COLOR)
+                } else {
+                    ui.label("Removed");
+                    PinInfo::circle().with_fill(Color32::BLACK)
+                }
+            }
+            DemoNode::Op(ref op) => {
+                let inputs = op.op_type.inputs();
+                if let Some((name, ty)) = inputs.get(pin.id.input) {
+                    ui.label(*name);
+                    return PinInfo::circle().with_fill(pin_color(*ty));
+                }
+                let idx = pin.id.input - inputs.len();
+
+                // Values arriving on a wire are applied when the graph is evaluated
+                let (connected, wired) = match &*pin.remotes {
+                    [] => (false, None),
+                    [remote] => match snarl[remote.node] {
+                        DemoNode::String(ref value) => (true, Some(Value::String(value.clone()))),
+                        _ => (true, self.number_out(snarl, remote.node).map(Value::Number)),
+                    },
+                    _ => unreachable!("Op pins has only one wire"),
+                };
+
+                let property = &mut snarl[pin.id.node].op_node().properties[idx];
+                // Connected inputs override the animation, so they have no keys
+                let value = property.number(self.frame).filter(|_| !connected);
+                if let (Some(value), Some(curve)) = (value, property.curve_mut()) {
+                    if let Some(value) = self.key_button(ui, pin.id.node, idx, curve, value) {
+                        property.set_value(&Value::Number(value));
+                    }
+                }
+                show_property(ui, pin.id, property, connected, wired.as_ref(), self.frame)
+            }
         }
     }
 
@@ -310,9 +603,22 @@ impl SnarlViewer<DemoNode> for DemoViewer {
             DemoNode::Sink => {
                 unreachable!("Sink node has no outputs")
             }
-            DemoNode::Number(ref mut value) => {
+            DemoNode::Number(ref mut value, ref mut curve) => {
                 assert_eq!(pin.id.output, 0, "Number node has only one output");
-                ui.add(egui::DragValue::new(value));
+                let mut shown = curve
+                    .as_ref()
+                    .map_or(*value, |curve| curve.value_at(self.frame));
+                if ui.add(egui::DragValue::new(&mut shown)).changed() {
+                    match curve {
+                        Some(curve) => {
+                            curve.set_key(self.frame, shown);
+                        }
+                        None => *value = shown,
+                    }
+                }
+                if let Some(unanimated) = self.key_button(ui, pin.id.node, 0, curve, *value) {
+                    *value = unanimated;
+                }
                 PinInfo::circle().with_fill(NUMBER_COLOR)
             }
             DemoNode::String(ref mut value) => {
@@ -328,8 +634,8 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                         corner_radius: 10.0,
                     })
             }
-            DemoNode::ExprNode(ref expr_node) => {
-                let value = expr_node.eval();
+            DemoNode::ExprNode(_) => {
+                let value = self.number_out(snarl, pin.id.node).unwrap_or_default();
                 assert_eq!(pin.id.output, 0, "Expr node has only one output");
                 ui.label(format_float(value));
                 PinInfo::circle().with_fill(NUMBER_COLOR)
@@ -338,6 +644,15 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                 ui.allocate_at_least(egui::Vec2::ZERO, egui::Sense::hover());
                 PinInfo::circle().with_fill(IMAGE_COLOR)
             }
+            DemoNode::PixelExpr(_) => {
+                ui.label("Output");
+                PinInfo::circle().with_fill(IMAGE_COLOR)
+            }
+            DemoNode::Op(ref op) => {
+                let (name, ty) = op.op_type.outputs()[pin.id.output];
+                ui.label(name);
+                PinInfo::circle().with_fill(pin_color(ty))
+            }
         }
     }
 
@@ -348,13 +663,17 @@ impl SnarlViewer<DemoNode> for DemoViewer {
     fn show_graph_menu(&mut self, pos: egui::Pos2, ui: &mut Ui, snarl: &mut Snarl<DemoNode>) {
         ui.label("Add node");
         if ui.button("Number").clicked() {
-            snarl.insert_node(pos, DemoNode::Number(0.0));
+            snarl.insert_node(pos, DemoNode::Number(0.0, None));
             ui.close_menu();
         }
         if ui.button("Expr").clicked() {
             snarl.insert_node(pos, DemoNode::ExprNode(ExprNode::new()));
             ui.close_menu();
         }
+        if ui.button("PixelExpr").clicked() {
+            snarl.insert_node(pos, DemoNode::PixelExpr(PixelExprNode::new()));
+            ui.close_menu();
+        }
         if ui.button("String").clicked() {
             snarl.insert_node(pos, DemoNode::String(String::new()));
             ui.close_menu();
@@ -367,6 +686,16 @@ impl SnarlViewer<DemoNode> for DemoViewer {
             snarl.insert_node(pos, DemoNode::Sink);
             ui.close_menu();
         }
+        for category in NodeCategory::ALL {
+            ui.menu_button(category.name(), |ui| {
+                for op_type in OpType::ALL {
+                    if op_type.category() == category && ui.button(op_type.name()).clicked() {
+                        snarl.insert_node(pos, DemoNode::Op(OpNode::new(op_type)));
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
     }
 
     fn has_dropped_wire_menu(&mut self, _src_pins: AnyPins, _snarl: &mut Snarl<DemoNode>) -> bool {
@@ -382,39 +711,8 @@ impl SnarlViewer<DemoNode> for DemoViewer {
     ) {
         // In this demo, we create a context-aware node graph menu, and connect a wire
         // dropped on the fly based on user input to a new node created.
-        //
-        // In your implementation, you may want to define specifications for each node's
-        // pin inputs and outputs and compatibility to make this easier.
-
-        type PinCompat = usize;
-        const PIN_NUM: PinCompat = 1;
-        const PIN_STR: PinCompat = 2;
-        const PIN_IMG: PinCompat = 4;
-        const PIN_SINK: PinCompat = PIN_NUM | PIN_STR | PIN_IMG;
-
-        const fn pin_out_compat(node: &DemoNode) -> PinCompat {
-            match node {
-                DemoNode::Sink => 0,
-                DemoNode::String(_) => PIN_STR,
-                DemoNode::ShowImage(_) => PIN_IMG,
-                DemoNode::Number(_) | DemoNode::ExprNode(_) => PIN_NUM,
-            }
-        }
-
-        const fn pin_in_compat(node: &DemoNode, pin: usize) -> PinCompat {
-            match node {
-                DemoNode::Sink => PIN_SINK,
-                DemoNode::Number(_) | DemoNode::String(_) => 0,
-                DemoNode::ShowImage(_) => PIN_STR,
-                DemoNode::ExprNode(_) => {
-                    if pin == 0 {
-                        PIN_STR
-                    } else {
-                        PIN_NUM
-                    }
-                }
-            }
-        }
+
+        type Candidate = (&'static str, Box<dyn Fn() -> DemoNode>, PinCompat);
 
         ui.label("Add node");
 
@@ -426,12 +724,35 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                 );
 
                 let src_pin = src_pins[0];
-                let src_out_ty = pin_out_compat(snarl.get_node(src_pin.node).unwrap());
-                let dst_in_candidates = [
-                    ("Sink", (|| DemoNode::Sink) as fn() -> DemoNode, PIN_SINK),
-                    ("Show Image", || DemoNode::ShowImage(String::new()), PIN_STR),
-                    ("Expr", || DemoNode::ExprNode(ExprNode::new()), PIN_STR),
+                let src_out_ty =
+                    pin_out_compat(snarl.get_node(src_pin.node).unwrap(), src_pin.output);
+                let mut dst_in_candidates: Vec<Candidate> = vec![
+                    ("Sink", Box::new(|| DemoNode::Sink), PIN_SINK),
+                    (
+                        "Show Image",
+                        Box::new(|| DemoNode::ShowImage(String::new())),
+                        PIN_STR,
+                    ),
+                    (
+                        "Expr",
+                        Box::new(|| DemoNode::ExprNode(ExprNode::new())),
+                        PIN_STR,
+                    ),
+                    (
+                        "PixelExpr",
+                        Box::new(|| DemoNode::PixelExpr(PixelExprNode::new())),
+                        PIN_IMG,
+                    ),
                 ];
+                for op_type in OpType::ALL {
+                    if let Some((_, ty)) = op_type.inputs().first() {
+                        dst_in_candidates.push((
+                            op_type.name(),
+                            Box::new(move || DemoNode::Op(OpNode::new(op_type))),
+                            pin_type_compat(*ty),
+                        ));
+                    }
+                }
 
                 for (name, ctor, in_ty) in dst_in_candidates {
                     if src_out_ty & in_ty != 0 && ui.button(name).clicked() {
@@ -453,22 +774,44 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                     acc | pin_in_compat(snarl.get_node(pin.node).unwrap(), pin.input)
                 });
 
-                let dst_out_candidates = [
+                let mut dst_out_candidates: Vec<Candidate> = vec![
+                    ("Number", Box::new(|| DemoNode::Number(0., None)), PIN_NUM),
+                    (
+                        "String",
+                        Box::new(|| DemoNode::String(String::new())),
+                        PIN_STR,
+                    ),
                     (
-                        "Number",
-                        (|| DemoNode::Number(0.)) as fn() -> DemoNode,
+                        "Expr",
+                        Box::new(|| DemoNode::ExprNode(ExprNode::new())),
                         PIN_NUM,
                     ),
-                    ("String", || DemoNode::String(String::new()), PIN_STR),
-                    ("Expr", || DemoNode::ExprNode(ExprNode::new()), PIN_NUM),
-                    ("Show Image", || DemoNode::ShowImage(String::new()), PIN_IMG),
+                    (
+                        "Show Image",
+                        Box::new(|| DemoNode::ShowImage(String::new())),
+                        PIN_IMG,
+                    ),
+                    (
+                        "PixelExpr",
+                        Box::new(|| DemoNode::PixelExpr(PixelExprNode::new())),
+                        PIN_IMG,
+                    ),
                 ];
+                for op_type in OpType::ALL {
+                    if let Some((_, ty)) = op_type.outputs().first() {
+                        dst_out_candidates.push((
+                            op_type.name(),
+                            Box::new(move || DemoNode::Op(OpNode::new(op_type))),
+                            pin_type_compat(*ty),
+                        ));
+                    }
+                }
 
                 for (name, ctor, out_ty) in dst_out_candidates {
                     if all_src_types & out_ty != 0 && ui.button(name).clicked() {
                         // Create new node.
                         let new_node = ctor();
-                        let dst_ty = pin_out_compat(&new_node);
+                        let dst_ty = pin_out_compat(&new_node, 0);
 
                         let new_node = snarl.insert_node(pos, new_node);
                         let dst_pin = OutPinId {
@@ -507,7 +850,49 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         snarl: &mut Snarl<DemoNode>,
     ) {
         ui.label("Node menu");
+        if ui.button("View").clicked() {
+            self.viewed = Some(node);
+            ui.close_menu();
+        }
+        if let DemoNode::Op(op) = &snarl[node] {
+            if op.op_type == OpType::Write && ui.button("Render").clicked() {
+                self.render_requests.push(node);
+                ui.close_menu();
+            }
+            if op.op_type == OpType::Read {
+                if let Some(range) = sequence::detect_range(op.path("File")) {
+                    let label = format!("Use Frames {range}");
+                    if ui
+                        .button(label)
+                        .on_hover_text("Sets the project frame range to the frames on disk")
+                        .clicked()
+                    {
+                        self.frame_range_request = Some(range);
+                        ui.close_menu();
+                    }
+                }
+            }
+            if op.frozen.is_some() {
+                if ui.button("Unfreeze").clicked() {
+                    snarl[node].op_node().frozen = None;
+                    ui.close_menu();
+                }
+            } else if ui
+                .add_enabled(self.disk_cache, egui::Button::new("Freeze"))
+                .on_hover_text("A frozen node keeps its output when nodes upstream change")
+                .on_disabled_hover_text(
+                    "Frozen outputs are kept in the disk cache, turn it on first",
+                )
+                .clicked()
+            {
+                self.freeze_requests.push(node);
+                ui.close_menu();
+            }
+        }
         if ui.button("Remove").clicked() {
+            if self.viewed == Some(node) {
+                self.viewed = None;
+            }
             snarl.remove_node(node);
             ui.close_menu();
         }
@@ -529,7 +914,7 @@ impl SnarlViewer<DemoNode> for DemoViewer {
             DemoNode::Sink => {
                 ui.label("Displays anything connected to it");
             }
-            DemoNode::Number(_) => {
+            DemoNode::Number(..) => {
                 ui.label("Outputs integer value");
             }
             DemoNode::String(_) => {
@@ -541,6 +926,15 @@ impl SnarlViewer<DemoNode> for DemoViewer {
             DemoNode::ExprNode(_) => {
                 ui.label("Evaluates algebraic expression with input for each unique variable name");
             }
+            DemoNode::PixelExpr(_) => {
+                ui.label(
+                    "Evaluates an expression per output channel for every pixel, \
+                     with r g b a x y u v width height and an input for each other variable",
+                );
+            }
+            DemoNode::Op(ref op) => {
+                ui.label(op.op_type.description());
+            }
         }
     }
 
@@ -552,12 +946,181 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         _outputs: &[OutPin],
         snarl: &Snarl<DemoNode>,
     ) -> egui::Frame {
+        let frame = if self.viewed == Some(node) {
+            frame.stroke(egui::Stroke::new(1.0, VIEWED_COLOR))
+        } else {
+            frame
+        };
+
         match snarl[node] {
             DemoNode::Sink => frame.fill(egui::Color32::from_rgb(70, 70, 80)),
-            DemoNode::Number(_) => frame.fill(egui::Color32::from_rgb(70, 40, 40)),
+            DemoNode::Number(..) => frame.fill(egui::Color32::from_rgb(70, 40, 40)),
             DemoNode::String(_) => frame.fill(egui::Color32::from_rgb(40, 70, 40)),
             DemoNode::ShowImage(_) => frame.fill(egui::Color32::from_rgb(40, 40, 70)),
             DemoNode::ExprNode(_) => frame.fill(egui::Color32::from_rgb(70, 66, 40)),
+            DemoNode::PixelExpr(_) => frame.fill(egui::Color32::from_rgb(60, 56, 40)),
+            DemoNode::Op(ref op) => match op.op_type.category() {
+                NodeCategory::IO => frame.fill(egui::Color32::from_rgb(50, 50, 50)),
+                NodeCategory::Generate => frame.fill(egui::Color32::from_rgb(70, 60, 40)),
+                NodeCategory::Transform => frame.fill(egui::Color32::from_rgb(40, 60, 70)),
+                NodeCategory::Channel => frame.fill(egui::Color32::from_rgb(60, 45, 60)),
+                NodeCategory::Color => frame.fill(egui::Color32::from_rgb(45, 65, 65)),
+                NodeCategory::Filter => frame.fill(egui::Color32::from_rgb(70, 45, 40)),
+                NodeCategory::Keyer => frame.fill(egui::Color32::from_rgb(40, 70, 45)),
+                NodeCategory::Merge => frame.fill(egui::Color32::from_rgb(40, 50, 80)),
+                NodeCategory::Analysis => frame.fill(egui::Color32::from_rgb(55, 55, 70)),
+            },
+        }
+    }
+}
+
+/// Moves the wires of variable inputs starting at pin `first` after the variables
+/// changed from `old` to `new`. Inputs of removed variables are disconnected.
+fn rebind_inputs(
+    snarl: &mut Snarl<DemoNode>,
+    node: NodeId,
+    first: usize,
+    old: &[String],
+    new: &[String],
+) {
+    let old_inputs = (0..old.len())
+        .map(|idx| {
+            snarl.in_pin(InPinId {
+                node,
+                input: first + idx,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (idx, name) in old.iter().enumerate() {
+        match new.iter().position(|new_name| new_name == name) {
+            None => {
+                snarl.drop_inputs(old_inputs[idx].id);
+            }
+            Some(new_idx) if new_idx != idx => {
+                let new_in_pin = InPinId {
+                    node,
+                    input: first + new_idx,
+                };
+                for &remote in &old_inputs[idx].remotes {
+                    snarl.disconnect(remote, old_inputs[idx].id);
+                    snarl.connect(remote, new_in_pin);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Shows the editor of a property. Edits of an animated number set a key at `frame`.
+fn show_property(
+    ui: &mut Ui,
+    pin: InPinId,
+    property: &mut NodeProperty,
+    connected: bool,
+    wired: Option<&Value>,
+    frame: i64,
+) -> PinInfo {
+    match property {
+        NodeProperty::Float(data) => {
+            ui.label(data.name());
+            if connected {
+                show_wired(ui, wired);
+            } else {
+                let (min, max, step) = (data.min(), data.max(), data.step());
+                let mut value = data.value_at(frame);
+                let edit = egui::DragValue::new(&mut value)
+                    .range(min..=max)
+                    .speed(step);
+                if ui.add(edit).changed() {
+                    match data.curve_mut() {
+                        Some(curve) => {
+                            curve.set_key(frame, value);
+                        }
+                        None => *data.value_mut() = value,
+                    }
+                }
+            }
+            PinInfo::circle().with_fill(NUMBER_COLOR)
+        }
+        NodeProperty::Int(data) => {
+            ui.label(data.name());
+            if connected {
+                show_wired(ui, wired);
+            } else {
+                let (min, max, step) = (data.min(), data.max(), data.step());
+                let mut value = data.value_at(frame);
+                let edit = egui::DragValue::new(&mut value)
+                    .range(min..=max)
+                    .speed(step);
+                if ui.add(edit).changed() {
+                    match data.curve_mut() {
+                        Some(curve) => {
+                            curve.set_key(frame, f64::from(value));
+                        }
+                        None => *data.value_mut() = value,
+                    }
+                }
+            }
+            PinInfo::circle().with_fill(NUMBER_COLOR)
+        }
+        NodeProperty::Choice(data) => {
+            ui.label(data.name());
+            let choices = data.choices().to_vec();
+            let index = data.index_mut();
+            // Project files can hold an index past the choices
+            let selected = choices
+                .get(*index)
+                .cloned()
+                .unwrap_or_else(|| format!("#{index}"));
+            egui::ComboBox::from_id_salt(pin)
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (idx, choice) in choices.iter().enumerate() {
+                        ui.selectable_value(index, idx, choice.as_str());
+                    }
+                });
+            PinInfo::circle().with_fill(UNTYPED_COLOR)
+        }
+        NodeProperty::Path(data) => {
+            ui.label(data.name());
+            if connected {
+                show_wired(ui, wired);
+            } else {
+                egui::TextEdit::singleline(data.path_mut())
+                    .clip_text(false)
+                    .desired_width(0.0)
+                    .margin(ui.spacing().item_spacing)
+                    .show(ui);
+            }
+            PinInfo::circle()
+                .with_fill(STRING_COLOR)
+                .with_wire_style(WireStyle::AxisAligned {
+                    corner_radius: 10.0,
+                })
+        }
+        NodeProperty::Text(data) => {
+            ui.label(data.name());
+            if connected {
+                show_wired(ui, wired);
+            } else {
+                egui::TextEdit::multiline(data.text_mut())
+                    .code_editor()
+                    .desired_rows(3)
+                    .desired_width(0.0)
+                    .clip_text(false)
+                    .show(ui);
+            }
+            PinInfo::circle()
+                .with_fill(STRING_COLOR)
+                .with_wire_style(WireStyle::AxisAligned {
+                    corner_radius: 10.0,
+                })
+        }
+        NodeProperty::Color(data) => {
+            ui.label(data.name());
+            ui.color_edit_button_rgba_unmultiplied(data.rgba_mut());
+            PinInfo::circle().with_fill(UNTYPED_COLOR)
         }
     }
 }
@@ -566,3 +1129,15 @@ fn format_float(v: f64) -> String {
     let v = (v * 1000.0).round() / 1000.0;
     format!("{v}")
 }
+
+/// Shows the value arriving at a connected input.
+/// Numbers from image nodes aren't known before evaluation.
+fn show_wired(ui: &mut Ui, value: Option<&Value>) {
+    match value {
+        Some(Value::Number(value)) => ui.label(format_float(*value)),
+        Some(Value::String(value)) => ui.label(value),
+        _ => ui
+            .weak("…")
+            .on_hover_text("Known once the graph is evaluated"),
+    };
+}
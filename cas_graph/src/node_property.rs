@@ -0,0 +1,387 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::curve::Curve;
+use crate::eval::Value;
+
+/// Editable parameter of an image node.
+/// Every property gets an input pin on the node, so numbers and strings
+/// can be driven by other nodes. Numbers can also be animated with a curve.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NodeProperty {
+    Float(NumberData<f64>),
+    Int(NumberData<i32>),
+    Choice(ChoiceData),
+    Path(PathData),
+    Text(TextData),
+    Color(ColorData),
+}
+
+impl NodeProperty {
+    pub fn new_float(name: &str, min: f64, max: f64, step: f64, value: f64) -> Self {
+        NodeProperty::Float(NumberData {
+            name: name.to_string(),
+            min,
+            max,
+            step,
+            value,
+            curve: None,
+        })
+    }
+
+    pub fn new_int(name: &str, min: i32, max: i32, step: i32, value: i32) -> Self {
+        NodeProperty::Int(NumberData {
+            name: name.to_string(),
+            min,
+            max,
+            step,
+            value,
+            curve: None,
+        })
+    }
+
+    pub fn new_choice(name: &str, choices: &[&str], index: usize) -> Self {
+        NodeProperty::Choice(ChoiceData {
+            name: name.to_string(),
+            choices: choices.iter().map(|choice| choice.to_string()).collect(),
+            index,
+        })
+    }
+
+    pub fn new_path(name: &str) -> Self {
+        NodeProperty::Path(PathData {
+            name: name.to_string(),
+            path: String::new(),
+        })
+    }
+
+    /// Multi-line text property.
+    pub fn new_text(name: &str, text: &str) -> Self {
+        NodeProperty::Text(TextData {
+            name: name.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Colour property with unpremultiplied RGBA components.
+    pub fn new_color(name: &str, rgba: [f32; 4]) -> Self {
+        NodeProperty::Color(ColorData {
+            name: name.to_string(),
+            rgba,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            NodeProperty::Float(data) => data.name(),
+            NodeProperty::Int(data) => data.name(),
+            NodeProperty::Choice(data) => data.name(),
+            NodeProperty::Path(data) => data.name(),
+            NodeProperty::Text(data) => data.name(),
+            NodeProperty::Color(data) => data.name(),
+        }
+    }
+
+    /// Feeds the value of the property at `frame` to `state`. Names and
+    /// ranges are fixed by the node type and left out, and so are curves,
+    /// so an animated property hashes the same on frames with equal values.
+    pub fn hash_content<H: Hasher>(&self, frame: i64, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            NodeProperty::Float(data) => data.value_at(frame).to_bits().hash(state),
+            NodeProperty::Int(data) => data.value_at(frame).hash(state),
+            NodeProperty::Choice(data) => data.index.hash(state),
+            NodeProperty::Path(data) => data.path.hash(state),
+            NodeProperty::Text(data) => data.text.hash(state),
+            NodeProperty::Color(data) => data.rgba.map(f32::to_bits).hash(state),
+        }
+    }
+
+    /// Multiplies a number property by `factor`, kept within its range.
+    /// Other properties are unchanged.
+    pub fn scale(&mut self, factor: f64) {
+        match self {
+            NodeProperty::Float(data) => {
+                data.value = (data.value * factor).clamp(data.min, data.max);
+            }
+            NodeProperty::Int(data) => {
+                let value = (data.value as f64 * factor).round() as i32;
+                data.value = value.clamp(data.min, data.max);
+            }
+            _ => {}
+        }
+    }
+
+    /// Value of a number property at `frame`.
+    pub fn number(&self, frame: i64) -> Option<f64> {
+        match self {
+            NodeProperty::Float(data) => Some(data.value_at(frame)),
+            NodeProperty::Int(data) => Some(f64::from(data.value_at(frame))),
+            _ => None,
+        }
+    }
+
+    /// Curve of an animated number property.
+    pub fn curve(&self) -> Option<&Curve> {
+        match self {
+            NodeProperty::Float(data) => data.curve.as_ref(),
+            NodeProperty::Int(data) => data.curve.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Curve of a number property, `None` for properties that can't be animated.
+    pub fn curve_mut(&mut self) -> Option<&mut Option<Curve>> {
+        match self {
+            NodeProperty::Float(data) => Some(&mut data.curve),
+            NodeProperty::Int(data) => Some(&mut data.curve),
+            _ => None,
+        }
+    }
+
+    /// Sets an animated number property to its value at `frame`.
+    pub fn animate(&mut self, frame: i64) {
+        match self {
+            NodeProperty::Float(data) => data.value = data.value_at(frame),
+            NodeProperty::Int(data) => data.value = data.value_at(frame),
+            _ => {}
+        }
+    }
+
+    /// Value of the property at `frame` as text that `parse_value` reads back.
+    pub fn format_value(&self, frame: i64) -> String {
+        match self {
+            NodeProperty::Float(data) => data.value_at(frame).to_string(),
+            NodeProperty::Int(data) => data.value_at(frame).to_string(),
+            // A damaged project may hold an index past the choices
+            NodeProperty::Choice(data) => data
+                .choices
+                .get(data.index)
+                .cloned()
+                .unwrap_or_else(|| format!("#{}", data.index)),
+            NodeProperty::Path(data) => data.path.clone(),
+            NodeProperty::Text(data) => data.text.clone(),
+            NodeProperty::Color(data) => {
+                data.rgba.map(|component| component.to_string()).join(", ")
+            }
+        }
+    }
+
+    /// Sets the property from text: a number, the name or index of a
+    /// choice, a path or text as it is, or a colour as three or four
+    /// numbers separated by commas. Numbers are kept within their range and
+    /// lose their animation. Returns whether the text was a valid value.
+    pub fn parse_value(&mut self, text: &str) -> bool {
+        let text = text.trim();
+        match self {
+            NodeProperty::Float(data) => match text.parse::<f64>() {
+                Ok(value) if value.is_finite() => {
+                    data.value = value.clamp(data.min, data.max);
+                    data.curve = None;
+                }
+                _ => return false,
+            },
+            NodeProperty::Int(data) => match text.parse::<i32>() {
+                Ok(value) => {
+                    data.value = value.clamp(data.min, data.max);
+                    data.curve = None;
+                }
+                Err(_) => return false,
+            },
+            NodeProperty::Choice(data) => {
+                let by_name = data
+                    .choices
+                    .iter()
+                    .position(|choice| choice.eq_ignore_ascii_case(text));
+                let by_index = text.parse().ok().filter(|&idx| idx < data.choices.len());
+                match by_name.or(by_index) {
+                    Some(index) => data.index = index,
+                    None => return false,
+                }
+            }
+            NodeProperty::Path(data) => data.path = text.to_owned(),
+            NodeProperty::Text(data) => data.text = text.to_owned(),
+            NodeProperty::Color(data) => {
+                let components = text
+                    .split(',')
+                    .map(|component| component.trim().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>();
+                match components.as_deref() {
+                    Ok(&[r, g, b]) => data.rgba = [r, g, b, 1.0],
+                    Ok(&[r, g, b, a]) => data.rgba = [r, g, b, a],
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    /// Sets the property from a value arriving on its input pin. Numbers
+    /// are kept within their range; values of the wrong type and numbers
+    /// that aren't numbers are ignored.
+    pub fn set_value(&mut self, value: &Value) {
+        match (self, value) {
+            (NodeProperty::Float(data), Value::Number(value)) if !value.is_nan() => {
+                data.value = value.clamp(data.min, data.max);
+            }
+            (NodeProperty::Int(data), Value::Number(value)) if !value.is_nan() => {
+                data.value = (value.round() as i32).clamp(data.min, data.max);
+            }
+            (NodeProperty::Path(data), Value::String(value)) => data.path.clone_from(value),
+            (NodeProperty::Text(data), Value::String(value)) => data.text.clone_from(value),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NumberData<T> {
+    name: String,
+    min: T,
+    max: T,
+    step: T,
+    value: T,
+    /// Animation of the value, which replaces it when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    curve: Option<Curve>,
+}
+
+impl<T: Copy> NumberData<T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn min(&self) -> T {
+        self.min
+    }
+
+    pub fn max(&self) -> T {
+        self.max
+    }
+
+    pub fn step(&self) -> T {
+        self.step
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn curve(&self) -> Option<&Curve> {
+        self.curve.as_ref()
+    }
+
+    pub fn curve_mut(&mut self) -> &mut Option<Curve> {
+        &mut self.curve
+    }
+}
+
+impl NumberData<f64> {
+    /// Value at `frame`, from the curve if the value is animated.
+    pub fn value_at(&self, frame: i64) -> f64 {
+        match &self.curve {
+            Some(curve) => curve.value_at(frame).clamp(self.min, self.max),
+            None => self.value,
+        }
+    }
+}
+
+impl NumberData<i32> {
+    /// Value at `frame`, from the curve if the value is animated.
+    pub fn value_at(&self, frame: i64) -> i32 {
+        match &self.curve {
+            Some(curve) => (curve.value_at(frame).round() as i32).clamp(self.min, self.max),
+            None => self.value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChoiceData {
+    name: String,
+    choices: Vec<String>,
+    index: usize,
+}
+
+impl ChoiceData {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn choices(&self) -> &[String] {
+        &self.choices
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn index_mut(&mut self) -> &mut usize {
+        &mut self.index
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PathData {
+    name: String,
+    path: String,
+}
+
+impl PathData {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn path_mut(&mut self) -> &mut String {
+        &mut self.path
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TextData {
+    name: String,
+    text: String,
+}
+
+impl TextData {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn text_mut(&mut self) -> &mut String {
+        &mut self.text
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorData {
+    name: String,
+    rgba: [f32; 4],
+}
+
+impl ColorData {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rgba(&self) -> [f32; 4] {
+        self.rgba
+    }
+
+    pub fn rgba_mut(&mut self) -> &mut [f32; 4] {
+        &mut self.rgba
+    }
+}
@@ -0,0 +1,167 @@
+use crate::image::{ImageBuffer, Window};
+
+/// Source of an output channel of the Shuffle node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelSource {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Zero,
+    One,
+}
+
+impl ChannelSource {
+    pub const ALL: [ChannelSource; 6] = [
+        ChannelSource::Red,
+        ChannelSource::Green,
+        ChannelSource::Blue,
+        ChannelSource::Alpha,
+        ChannelSource::Zero,
+        ChannelSource::One,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            ChannelSource::Red => "Red",
+            ChannelSource::Green => "Green",
+            ChannelSource::Blue => "Blue",
+            ChannelSource::Alpha => "Alpha",
+            ChannelSource::Zero => "0",
+            ChannelSource::One => "1",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|source| source.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(ChannelSource::Zero)
+    }
+
+    fn pick(self, rgba: [f32; 4]) -> f32 {
+        match self {
+            ChannelSource::Red => rgba[0],
+            ChannelSource::Green => rgba[1],
+            ChannelSource::Blue => rgba[2],
+            ChannelSource::Alpha => rgba[3],
+            ChannelSource::Zero => 0.0,
+            ChannelSource::One => 1.0,
+        }
+    }
+}
+
+/// Builds an RGBA image whose channels are taken from `sources`.
+///
+/// Inputs with fewer channels are read as RGBA first, see `ImageBuffer::rgba`.
+/// A constant one covers the whole display window, so the data window grows
+/// to it.
+pub fn shuffle(src: &ImageBuffer, sources: [ChannelSource; 4]) -> ImageBuffer {
+    let display_window = src.display_window();
+    let data_window = if sources.contains(&ChannelSource::One) {
+        display_window.union(&src.data_window())
+    } else {
+        src.data_window()
+    };
+
+    ImageBuffer::from_fn_windows(display_window, data_window, 4, |x, y, out| {
+        let rgba = src.rgba(x, y);
+        for (value, source) in out.iter_mut().zip(sources) {
+            *value = source.pick(rgba);
+        }
+    })
+}
+
+/// Splits an image into one single channel image per RGBA channel.
+pub fn split(src: &ImageBuffer) -> [ImageBuffer; 4] {
+    let display_window = src.display_window();
+    let data_window = src.data_window();
+
+    std::array::from_fn(|c| {
+        ImageBuffer::from_fn_windows(display_window, data_window, 1, |x, y, out| {
+            out[0] = src.rgba(x, y)[c];
+        })
+    })
+}
+
+/// Combines single channel images into one image.
+///
+/// The first channel of each connected input is used and missing colour
+/// channels are zero. The output has an alpha channel only if `channels[3]`
+/// is connected. Returns `None` if nothing is connected.
+pub fn join(channels: [Option<&ImageBuffer>; 4]) -> Option<ImageBuffer> {
+    let first = channels.iter().flatten().next()?;
+    let display_window = first.display_window();
+    let data_window = channels
+        .iter()
+        .flatten()
+        .fold(Window::default(), |window, image| {
+            window.union(&image.data_window())
+        })
+        .intersect(&display_window);
+    let count = if channels[3].is_some() { 4 } else { 3 };
+
+    Some(ImageBuffer::from_fn_windows(
+        display_window,
+        data_window,
+        count,
+        |x, y, out| {
+            for (value, channel) in out.iter_mut().zip(channels) {
+                *value = channel.map_or(0.0, |image| image.get(x, y, 0));
+            }
+        },
+    ))
+}
+
+/// Multiplies the colour channels by alpha.
+/// Images without an alpha channel are returned unchanged.
+pub fn premultiply(src: &ImageBuffer) -> ImageBuffer {
+    map_pixels(src, premultiply_pixel)
+}
+
+/// Divides the colour channels by alpha where alpha is not zero.
+/// Images without an alpha channel are returned unchanged.
+pub fn unpremultiply(src: &ImageBuffer) -> ImageBuffer {
+    map_pixels(src, unpremultiply_pixel)
+}
+
+/// Premultiplies a single pixel, see `premultiply`.
+pub fn premultiply_pixel(pixel: &mut [f32]) {
+    map_alpha(pixel, |value, alpha| value * alpha);
+}
+
+/// Unpremultiplies a single pixel, see `unpremultiply`.
+pub fn unpremultiply_pixel(pixel: &mut [f32]) {
+    map_alpha(
+        pixel,
+        |value, alpha| {
+            if alpha == 0.0 {
+                value
+            } else {
+                value / alpha
+            }
+        },
+    );
+}
+
+fn map_pixels(src: &ImageBuffer, f: impl Fn(&mut [f32])) -> ImageBuffer {
+    let mut image = src.clone();
+    let channels = image.channels();
+    for pixel in image.data_mut().chunks_exact_mut(channels) {
+        f(pixel);
+    }
+    image
+}
+
+/// Maps the colour channels of a pixel with its alpha.
+/// Pixels without an alpha channel are left alone.
+fn map_alpha(pixel: &mut [f32], f: impl Fn(f32, f32) -> f32) {
+    if pixel.len() != 2 && pixel.len() != 4 {
+        return;
+    }
+    let (alpha, colour) = pixel.split_last_mut().unwrap();
+    for value in colour {
+        *value = f(*value, *alpha);
+    }
+}
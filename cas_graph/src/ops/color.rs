@@ -0,0 +1,77 @@
+use crate::image::ImageBuffer;
+use crate::ops::keyer::LUMA;
+
+/// Settings of the Grade node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grade {
+    /// Value black is raised to.
+    pub lift: f32,
+    /// Value white is scaled to.
+    pub gain: f32,
+    /// Added after lift and gain.
+    pub offset: f32,
+    /// Gamma applied last. Values above one brighten the midtones.
+    pub gamma: f32,
+}
+
+impl Default for Grade {
+    fn default() -> Self {
+        Grade {
+            lift: 0.0,
+            gain: 1.0,
+            offset: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Grade {
+    /// Grades a single colour component.
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value * (self.gain - self.lift) + self.lift + self.offset;
+        // Negative values have no real power, so they are only lifted and scaled
+        if value > 0.0 && self.gamma > 0.0 && self.gamma != 1.0 {
+            value.powf(1.0 / self.gamma)
+        } else {
+            value
+        }
+    }
+}
+
+/// Applies `grade` to the colour channels of `src`. Alpha passes through.
+pub fn grade(src: &ImageBuffer, grade: &Grade) -> ImageBuffer {
+    map_color(src, |rgb| rgb.map(|value| grade.apply(value)))
+}
+
+/// Scales the distance of each colour from its luminance by `amount`.
+/// Zero gives greyscale, one leaves the image unchanged.
+pub fn saturation(src: &ImageBuffer, amount: f32) -> ImageBuffer {
+    map_color(src, |rgb| saturate(rgb, amount))
+}
+
+/// Scales the distance of a colour from its luminance by `amount`.
+pub fn saturate([r, g, b]: [f32; 3], amount: f32) -> [f32; 3] {
+    let luma = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+    [r, g, b].map(|value| luma + (value - luma) * amount)
+}
+
+/// Maps the RGB of a pixel. Pixels with fewer than three channels have
+/// their first channel mapped as grey.
+pub fn map_color_pixel(pixel: &mut [f32], f: impl Fn([f32; 3]) -> [f32; 3]) {
+    if pixel.len() >= 3 {
+        let rgb = f([pixel[0], pixel[1], pixel[2]]);
+        pixel[..3].copy_from_slice(&rgb);
+    } else {
+        pixel[0] = f([pixel[0]; 3])[0];
+    }
+}
+
+/// Maps the RGB of every pixel, see `map_color_pixel`.
+fn map_color(src: &ImageBuffer, f: impl Fn([f32; 3]) -> [f32; 3]) -> ImageBuffer {
+    let mut image = src.clone();
+    let channels = image.channels();
+    for pixel in image.data_mut().chunks_exact_mut(channels) {
+        map_color_pixel(pixel, &f);
+    }
+    image
+}
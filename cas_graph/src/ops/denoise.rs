@@ -0,0 +1,276 @@
+use crate::image::{ImageBuffer, Window};
+use crate::ops::keyer::LUMA;
+use crate::tile;
+
+const CB_SCALE: f32 = 1.8556;
+const CR_SCALE: f32 = 1.5748;
+
+/// Median of a square window of each radius. A radius of zero leaves the component as is.
+pub fn median(src: &ImageBuffer, luma_radius: usize, chroma_radius: usize) -> ImageBuffer {
+    filter_ycbcr(src, |plane, component| {
+        let radius = if component == 0 {
+            luma_radius
+        } else {
+            chroma_radius
+        };
+        (radius > 0).then(|| plane.median(radius))
+    })
+}
+
+/// Edge preserving blur that averages neighbours weighted by both their
+/// distance and their difference in value. A range sigma of zero leaves
+/// the component as is.
+pub fn bilateral(
+    src: &ImageBuffer,
+    spatial_sigma: f32,
+    luma_range: f32,
+    chroma_range: f32,
+) -> ImageBuffer {
+    filter_ycbcr(src, |plane, component| {
+        let range = if component == 0 {
+            luma_range
+        } else {
+            chroma_range
+        };
+        (range > 0.0 && spatial_sigma > 0.0).then(|| plane.bilateral(spatial_sigma, range))
+    })
+}
+
+/// Non-local means: averages pixels of the search window whose surrounding
+/// patches look alike, so repeated texture is kept while noise averages out.
+/// A strength of zero leaves the component as is.
+pub fn non_local_means(
+    src: &ImageBuffer,
+    patch_radius: usize,
+    search_radius: usize,
+    luma_strength: f32,
+    chroma_strength: f32,
+) -> ImageBuffer {
+    filter_ycbcr(src, |plane, component| {
+        let strength = if component == 0 {
+            luma_strength
+        } else {
+            chroma_strength
+        };
+        (strength > 0.0).then(|| plane.non_local_means(patch_radius, search_radius, strength))
+    })
+}
+
+/// Splits `src` into luma and chroma, replaces the components for which `f`
+/// returns a plane and converts back. Single channel images only have luma.
+///
+/// Scanner grain is mostly chroma noise, which can be smoothed much harder
+/// than luma without visible loss of detail, so every denoise filter takes
+/// separate strengths for the two. Alpha and the data window are unchanged.
+fn filter_ycbcr(
+    src: &ImageBuffer,
+    mut f: impl FnMut(&Plane, usize) -> Option<Plane>,
+) -> ImageBuffer {
+    let channels = src.channels();
+    let colour = channels >= 3;
+    let window = src.data_window();
+    let (width, height) = (window.width, window.height);
+
+    let mut planes = if colour {
+        let mut planes = [0, 1, 2].map(|_| Plane::new(width, height));
+        for (idx, pixel) in src.data().chunks_exact(channels).enumerate() {
+            let y = LUMA[0] * pixel[0] + LUMA[1] * pixel[1] + LUMA[2] * pixel[2];
+            planes[0].data[idx] = y;
+            planes[1].data[idx] = (pixel[2] - y) / CB_SCALE;
+            planes[2].data[idx] = (pixel[0] - y) / CR_SCALE;
+        }
+        planes.to_vec()
+    } else {
+        let mut plane = Plane::new(width, height);
+        for (value, pixel) in plane.data.iter_mut().zip(src.data().chunks_exact(channels)) {
+            *value = pixel[0];
+        }
+        vec![plane]
+    };
+
+    for (component, plane) in planes.iter_mut().enumerate() {
+        if let Some(filtered) = f(plane, component) {
+            *plane = filtered;
+        }
+    }
+
+    let mut image = src.clone();
+    for (idx, pixel) in image.data_mut().chunks_exact_mut(channels).enumerate() {
+        if colour {
+            let (y, cb, cr) = (
+                planes[0].data[idx],
+                planes[1].data[idx],
+                planes[2].data[idx],
+            );
+            let r = y + CR_SCALE * cr;
+            let b = y + CB_SCALE * cb;
+            pixel[0] = r;
+            pixel[1] = (y - LUMA[0] * r - LUMA[2] * b) / LUMA[1];
+            pixel[2] = b;
+        } else {
+            pixel[0] = planes[0].data[idx];
+        }
+    }
+    image
+}
+
+/// One component of the data window. Reads beyond its edges repeat the edge.
+#[derive(Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Plane {
+    fn new(width: usize, height: usize) -> Plane {
+        Plane {
+            width,
+            height,
+            data: vec![0.0; width * height],
+        }
+    }
+
+    fn get(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.data[y * self.width + x]
+    }
+
+    /// Plane of `f` called with per-tile scratch state for every pixel,
+    /// spread over the worker threads.
+    fn map<S>(
+        &self,
+        init: impl Fn() -> S + Sync,
+        f: impl Fn(&mut S, isize, isize) -> f32 + Sync,
+    ) -> Plane {
+        self.map_tiles(|tile, out| {
+            let mut scratch = init();
+            for (idx, value) in out.iter_mut().enumerate() {
+                let x = tile.x + (idx % tile.width) as isize;
+                let y = tile.y + (idx / tile.width) as isize;
+                *value = f(&mut scratch, x, y);
+            }
+        })
+    }
+
+    /// Plane of `f` called for every tile with its window and pixels.
+    fn map_tiles(&self, f: impl Fn(Window, &mut [f32]) + Sync) -> Plane {
+        let mut image = ImageBuffer::new(self.width, self.height, 1);
+        tile::fill_tiles(&mut image, f);
+        Plane {
+            data: image.data().to_vec(),
+            ..*self
+        }
+    }
+
+    fn median(&self, radius: usize) -> Plane {
+        let r = radius as isize;
+        let init = || Vec::with_capacity((2 * radius + 1).pow(2));
+        self.map(init, |window, x, y| {
+            window.clear();
+            for dy in -r..=r {
+                window.extend((-r..=r).map(|dx| self.get(x + dx, y + dy)));
+            }
+            let mid = window.len() / 2;
+            *window.select_nth_unstable_by(mid, f32::total_cmp).1
+        })
+    }
+
+    fn bilateral(&self, spatial_sigma: f32, range_sigma: f32) -> Plane {
+        let r = (spatial_sigma * 2.0).ceil() as isize;
+        let size = (2 * r + 1) as usize;
+        let spatial = (0..size * size)
+            .map(|idx| {
+                let (dx, dy) = ((idx % size) as isize - r, (idx / size) as isize - r);
+                (-((dx * dx + dy * dy) as f32) / (2.0 * spatial_sigma * spatial_sigma)).exp()
+            })
+            .collect::<Vec<_>>();
+        let range_scale = -1.0 / (2.0 * range_sigma * range_sigma);
+
+        self.map(
+            || (),
+            |_, x, y| {
+                let centre = self.get(x, y);
+                let (mut sum, mut total) = (0.0, 0.0);
+                for (idx, weight) in spatial.iter().enumerate() {
+                    let (dx, dy) = ((idx % size) as isize - r, (idx / size) as isize - r);
+                    let value = self.get(x + dx, y + dy);
+                    let diff = value - centre;
+                    let weight = weight * (diff * diff * range_scale).exp();
+                    sum += value * weight;
+                    total += weight;
+                }
+                sum / total
+            },
+        )
+    }
+
+    /// For every offset of the search window, the patch distances of all
+    /// pixels of a tile come from one summed area table of squared
+    /// differences, so the cost doesn't depend on the patch size. The table
+    /// covers the tile and a halo of the patch radius around it.
+    fn non_local_means(&self, patch_radius: usize, search_radius: usize, strength: f32) -> Plane {
+        let bounds = Window::from_size(self.width, self.height);
+        let search = search_radius as isize;
+        let scale = -1.0 / (strength * strength);
+
+        self.map_tiles(|tile, out| {
+            let halo = tile.expand(patch_radius).intersect(&bounds);
+            let (width, height) = (halo.width, halo.height);
+            let mut sum = vec![0.0f32; tile.width * tile.height];
+            let mut total = vec![0.0f32; tile.width * tile.height];
+            // Summed area table of the halo with a zero row and column in front
+            let mut table = vec![0.0f64; (width + 1) * (height + 1)];
+            // Table corners and area of the patch around every pixel of the tile
+            let at =
+                |x: isize, y: isize| (y - halo.y) as usize * (width + 1) + (x - halo.x) as usize;
+            let patches = (0..tile.width * tile.height)
+                .map(|idx| {
+                    let x = tile.x + (idx % tile.width) as isize;
+                    let y = tile.y + (idx / tile.width) as isize;
+                    let p = Window::new(x, y, 1, 1)
+                        .expand(patch_radius)
+                        .intersect(&bounds);
+                    let corners = [
+                        at(p.right(), p.bottom()),
+                        at(p.right(), p.y),
+                        at(p.x, p.bottom()),
+                        at(p.x, p.y),
+                    ];
+                    (corners, 1.0 / (p.width * p.height) as f32)
+                })
+                .collect::<Vec<_>>();
+
+            for dy in -search..=search {
+                for dx in -search..=search {
+                    for y in 0..height {
+                        let mut row = 0.0;
+                        for x in 0..width {
+                            let (sx, sy) = (halo.x + x as isize, halo.y + y as isize);
+                            let diff = self.get(sx, sy) - self.get(sx + dx, sy + dy);
+                            row += (diff * diff) as f64;
+                            table[(y + 1) * (width + 1) + x + 1] =
+                                table[y * (width + 1) + x + 1] + row;
+                        }
+                    }
+
+                    for (idx, (sum, total)) in sum.iter_mut().zip(&mut total).enumerate() {
+                        let x = tile.x + (idx % tile.width) as isize;
+                        let y = tile.y + (idx / tile.width) as isize;
+                        let ([a, b, c, d], inv_area) = patches[idx];
+                        let distance = table[a] - table[b] - table[c] + table[d];
+                        let weight = (distance as f32 * inv_area * scale).exp();
+
+                        *sum += weight * self.get(x + dx, y + dy);
+                        *total += weight;
+                    }
+                }
+            }
+
+            for (out, (sum, total)) in out.iter_mut().zip(sum.iter().zip(&total)) {
+                *out = sum / total;
+            }
+        })
+    }
+}
@@ -0,0 +1,180 @@
+use crate::image::ImageBuffer;
+use crate::ops::filter;
+use crate::ops::keyer::LUMA;
+
+/// Operator of the Edge Detect node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeMethod {
+    Sobel,
+    Prewitt,
+    Laplacian,
+    /// Thin edges from non-maximum suppression and hysteresis thresholds.
+    Canny,
+}
+
+impl EdgeMethod {
+    pub const ALL: [EdgeMethod; 4] = [
+        EdgeMethod::Sobel,
+        EdgeMethod::Prewitt,
+        EdgeMethod::Laplacian,
+        EdgeMethod::Canny,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            EdgeMethod::Sobel => "Sobel",
+            EdgeMethod::Prewitt => "Prewitt",
+            EdgeMethod::Laplacian => "Laplacian",
+            EdgeMethod::Canny => "Canny",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|method| method.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(EdgeMethod::Sobel)
+    }
+}
+
+/// Settings of the Canny method.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Canny {
+    /// Blur applied before taking the gradient.
+    pub sigma: f64,
+    /// Gradient magnitude below which edges are dropped.
+    pub low: f32,
+    /// Gradient magnitude above which edges are kept. Edges in between are
+    /// kept only if they connect to a strong edge.
+    pub high: f32,
+}
+
+/// Detects edges in the luminance of `src` and returns a single channel image.
+///
+/// Sobel and Prewitt output the gradient magnitude, Laplacian the absolute
+/// second derivative and Canny one for edge pixels and zero elsewhere.
+pub fn edge_detect(src: &ImageBuffer, method: EdgeMethod, canny: &Canny) -> ImageBuffer {
+    let luma = luminance(src);
+    match method {
+        EdgeMethod::Sobel => gradient(&luma, 2.0).0,
+        EdgeMethod::Prewitt => gradient(&luma, 1.0).0,
+        EdgeMethod::Laplacian => {
+            let kernel = filter::Kernel {
+                width: 3,
+                height: 3,
+                weights: vec![0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0],
+            };
+            let mut image = filter::convolve(&luma, &kernel);
+            for value in image.data_mut() {
+                *value = value.abs();
+            }
+            image
+        }
+        EdgeMethod::Canny => canny_edges(&filter::gaussian_blur(&luma, canny.sigma), canny),
+    }
+}
+
+fn luminance(src: &ImageBuffer) -> ImageBuffer {
+    ImageBuffer::from_fn_windows(src.display_window(), src.data_window(), 1, |x, y, out| {
+        let [r, g, b, _] = src.rgba(x, y);
+        out[0] = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+    })
+}
+
+/// Gradient magnitude and direction in radians of a single channel image,
+/// with 3x3 kernels weighing the centre row or column by `centre`.
+fn gradient(src: &ImageBuffer, centre: f32) -> (ImageBuffer, Vec<f32>) {
+    let window = src.data_window().expand(1).intersect(&src.display_window());
+    let norm = 1.0 / (2.0 + centre);
+
+    // Magnitude and direction, split once every tile is done
+    let both = ImageBuffer::from_fn_windows(src.display_window(), window, 2, |x, y, out| {
+        let p = |dx: isize, dy: isize| src.get(x + dx, y + dy, 0);
+        let gx = (p(1, -1) + centre * p(1, 0) + p(1, 1) - p(-1, -1) - centre * p(-1, 0) - p(-1, 1))
+            * norm;
+        let gy = (p(-1, 1) + centre * p(0, 1) + p(1, 1) - p(-1, -1) - centre * p(0, -1) - p(1, -1))
+            * norm;
+        out[0] = gx.hypot(gy);
+        out[1] = gy.atan2(gx);
+    });
+    let magnitude = ImageBuffer::from_fn_windows(src.display_window(), window, 1, |x, y, out| {
+        out[0] = both.get(x, y, 0);
+    });
+    let directions = both.data().iter().skip(1).step_by(2).copied().collect();
+    (magnitude, directions)
+}
+
+fn canny_edges(src: &ImageBuffer, settings: &Canny) -> ImageBuffer {
+    let (magnitude, directions) = gradient(src, 2.0);
+    let window = magnitude.data_window();
+    let (width, height) = (window.width, window.height);
+    let at = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+            0.0
+        } else {
+            magnitude.data()[y as usize * width + x as usize]
+        }
+    };
+
+    // Keep only local maxima across the edge
+    let mut strength = vec![0.0f32; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let value = magnitude.data()[idx];
+            if value < settings.low {
+                continue;
+            }
+            let angle = directions[idx].to_degrees().rem_euclid(180.0);
+            let (dx, dy) = match angle {
+                a if !(22.5..157.5).contains(&a) => (1, 0),
+                a if a < 67.5 => (1, 1),
+                a if a < 112.5 => (0, 1),
+                _ => (-1, 1),
+            };
+            let (x, y) = (x as isize, y as isize);
+            if value >= at(x + dx, y + dy) && value >= at(x - dx, y - dy) {
+                strength[idx] = value;
+            }
+        }
+    }
+
+    // Grow from strong edges into connected weak ones
+    let mut edges = vec![false; width * height];
+    let mut stack = (0..width * height)
+        .filter(|&idx| strength[idx] >= settings.high)
+        .collect::<Vec<_>>();
+    for &idx in &stack {
+        edges[idx] = true;
+    }
+    while let Some(idx) = stack.pop() {
+        let (x, y) = ((idx % width) as isize, (idx / width) as isize);
+        for (dx, dy) in [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ] {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                continue;
+            }
+            let next = ny as usize * width + nx as usize;
+            if !edges[next] && strength[next] > 0.0 {
+                edges[next] = true;
+                stack.push(next);
+            }
+        }
+    }
+
+    let mut image = ImageBuffer::with_windows(src.display_window(), window, 1);
+    for (value, edge) in image.data_mut().iter_mut().zip(edges) {
+        *value = if edge { 1.0 } else { 0.0 };
+    }
+    image
+}
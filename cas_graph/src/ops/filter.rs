@@ -0,0 +1,167 @@
+use crate::image::{ImageBuffer, Window};
+
+/// Largest width and height of a kernel. Every output pixel reads every
+/// weight, so larger kernels take too long to be useful.
+pub const MAX_KERNEL_SIZE: usize = 63;
+
+/// Weights of a convolution, stored row by row.
+/// The centre of the kernel is at `(width / 2, height / 2)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Kernel {
+    pub width: usize,
+    pub height: usize,
+    pub weights: Vec<f32>,
+}
+
+impl Kernel {
+    /// Parses a square kernel from numbers separated by whitespace, commas or semicolons.
+    /// Returns `None` if the count isn't an odd square up to [`MAX_KERNEL_SIZE`]
+    /// wide or a number is invalid.
+    pub fn parse(text: &str) -> Option<Kernel> {
+        let weights = text
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|word| !word.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<f32>, _>>()
+            .ok()?;
+
+        let size = (weights.len() as f64).sqrt().round() as usize;
+        if size * size != weights.len() || !valid_size(size) {
+            return None;
+        }
+        Some(Kernel {
+            width: size,
+            height: size,
+            weights,
+        })
+    }
+
+    /// Kernel from the first channel of the data window of an image.
+    /// Returns `None` unless the data window is an odd square up to
+    /// [`MAX_KERNEL_SIZE`] wide, which keeps the centre on a pixel.
+    pub fn from_image(image: &ImageBuffer) -> Option<Kernel> {
+        let (width, height) = (image.width(), image.height());
+        if width != height || !valid_size(width) {
+            return None;
+        }
+        let channels = image.channels();
+        Some(Kernel {
+            width,
+            height,
+            weights: image.data().iter().step_by(channels).copied().collect(),
+        })
+    }
+
+    /// Scales the weights to sum to one, unless they sum to zero like edge kernels do.
+    pub fn normalize(&mut self) {
+        let sum: f32 = self.weights.iter().sum();
+        if sum.abs() > 1e-6 {
+            for weight in &mut self.weights {
+                *weight /= sum;
+            }
+        }
+    }
+}
+
+/// Whether a square kernel `size` wide has a centre pixel and isn't too large.
+fn valid_size(size: usize) -> bool {
+    !size.is_multiple_of(2) && size <= MAX_KERNEL_SIZE
+}
+
+/// Reach of a gaussian of standard deviation `sigma` in pixels, three sigma.
+pub fn gaussian_radius(sigma: f64) -> usize {
+    (sigma * 3.0).ceil().max(0.0) as usize
+}
+
+/// Normalized 1D gaussian of standard deviation `sigma`, three sigma wide on each side.
+pub fn gaussian_kernel(sigma: f64) -> Vec<f32> {
+    let radius = gaussian_radius(sigma) as isize;
+    let weights = (-radius..=radius)
+        .map(|x| (-(x * x) as f64 / (2.0 * sigma * sigma)).exp() as f32)
+        .collect::<Vec<_>>();
+    let sum: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / sum).collect()
+}
+
+/// Applies `kernel` to every channel. The kernel is not flipped, so its top
+/// row weighs the pixels above.
+///
+/// The data window grows by the kernel radius, clipped to the display window.
+pub fn convolve(src: &ImageBuffer, kernel: &Kernel) -> ImageBuffer {
+    let (rx, ry) = (kernel.width / 2, kernel.height / 2);
+    let display_window = src.display_window();
+    let data = src.data_window();
+    let data_window = Window::from_corners(
+        data.x - rx as isize,
+        data.y - ry as isize,
+        data.right() + (kernel.width - 1 - rx) as isize,
+        data.bottom() + (kernel.height - 1 - ry) as isize,
+    )
+    .intersect(&display_window);
+
+    ImageBuffer::from_fn_windows(display_window, data_window, src.channels(), |x, y, out| {
+        out.fill(0.0);
+        for (ky, row) in kernel.weights.chunks_exact(kernel.width).enumerate() {
+            let sy = y + ky as isize - ry as isize;
+            for (kx, &weight) in row.iter().enumerate() {
+                if weight == 0.0 {
+                    continue;
+                }
+                let sx = x + kx as isize - rx as isize;
+                for (c, value) in out.iter_mut().enumerate() {
+                    *value += src.get(sx, sy, c) * weight;
+                }
+            }
+        }
+    })
+}
+
+/// Gaussian blur of standard deviation `sigma`, as two 1D passes.
+pub fn gaussian_blur(src: &ImageBuffer, sigma: f64) -> ImageBuffer {
+    if sigma <= 0.0 {
+        return src.clone();
+    }
+    let weights = gaussian_kernel(sigma);
+    let size = weights.len();
+
+    let horizontal = Kernel {
+        width: size,
+        height: 1,
+        weights: weights.clone(),
+    };
+    let vertical = Kernel {
+        width: 1,
+        height: size,
+        weights,
+    };
+    convolve(&convolve(src, &horizontal), &vertical)
+}
+
+/// Adds `amount` times the difference between the image and its blur of `radius`.
+pub fn sharpen(src: &ImageBuffer, amount: f32, radius: f64) -> ImageBuffer {
+    unsharp_mask(src, amount, radius, 0.0)
+}
+
+/// Like `sharpen`, but leaves pixels alone where the difference to the
+/// blurred image is below `threshold`, so flat noisy areas aren't sharpened.
+pub fn unsharp_mask(src: &ImageBuffer, amount: f32, radius: f64, threshold: f32) -> ImageBuffer {
+    let blurred = gaussian_blur(src, radius);
+    let channels = src.channels();
+
+    ImageBuffer::from_fn_windows(
+        src.display_window(),
+        src.data_window(),
+        channels,
+        |x, y, out| {
+            for (c, value) in out.iter_mut().enumerate() {
+                let original = src.get(x, y, c);
+                let detail = original - blurred.get(x, y, c);
+                *value = if detail.abs() < threshold {
+                    original
+                } else {
+                    original + detail * amount
+                };
+            }
+        },
+    )
+}
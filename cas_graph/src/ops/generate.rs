@@ -0,0 +1,274 @@
+use crate::image::{ImageBuffer, Window};
+
+/// Shape of the Ramp node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RampType {
+    /// Blends along the line from the start to the end point.
+    Linear,
+    /// Blends outwards from the start point, reaching the end colour at the end point.
+    Radial,
+}
+
+impl RampType {
+    pub const ALL: [RampType; 2] = [RampType::Linear, RampType::Radial];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            RampType::Linear => "Linear",
+            RampType::Radial => "Radial",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|ty| ty.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(RampType::Linear)
+    }
+}
+
+/// Basis function of the Noise node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseType {
+    Value,
+    Perlin,
+    Simplex,
+}
+
+impl NoiseType {
+    pub const ALL: [NoiseType; 3] = [NoiseType::Value, NoiseType::Perlin, NoiseType::Simplex];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            NoiseType::Value => "Value",
+            NoiseType::Perlin => "Perlin",
+            NoiseType::Simplex => "Simplex",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|ty| ty.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(NoiseType::Value)
+    }
+
+    /// Noise at `(x, y)` in the range `0..=1`, with features about one unit apart.
+    fn sample(self, x: f64, y: f64, seed: u32) -> f64 {
+        match self {
+            NoiseType::Value => value_noise(x, y, seed),
+            NoiseType::Perlin => perlin_noise(x, y, seed) * 0.5 + 0.5,
+            NoiseType::Simplex => simplex_noise(x, y, seed) * 0.5 + 0.5,
+        }
+    }
+}
+
+/// Settings of the Noise node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Noise {
+    pub noise_type: NoiseType,
+    pub seed: u32,
+    /// Size of the first octave's features in pixels.
+    pub scale: f64,
+    pub octaves: u32,
+    /// Frequency multiplier between octaves.
+    pub lacunarity: f64,
+    /// Amplitude multiplier between octaves.
+    pub gain: f64,
+}
+
+/// Image of `width` x `height` pixels made by calling `f` for every pixel.
+/// With a region, only the pixels inside it are generated.
+fn generate(
+    width: usize,
+    height: usize,
+    channels: usize,
+    region: Option<Window>,
+    f: impl Fn(usize, usize, &mut [f32]) + Sync,
+) -> ImageBuffer {
+    let format = Window::from_size(width, height);
+    let data_window = region.map_or(format, |region| region.intersect(&format));
+    ImageBuffer::from_fn_windows(format, data_window, channels, |x, y, out| {
+        f(x as usize, y as usize, out)
+    })
+}
+
+/// Image filled with a single colour.
+pub fn constant(
+    width: usize,
+    height: usize,
+    color: [f32; 4],
+    region: Option<Window>,
+) -> ImageBuffer {
+    generate(width, height, 4, region, |_, _, out| {
+        out.copy_from_slice(&color)
+    })
+}
+
+/// Gradient between two colours. Points are in pixels.
+pub fn ramp(
+    width: usize,
+    height: usize,
+    ramp_type: RampType,
+    start: (f64, f64),
+    end: (f64, f64),
+    colors: [[f32; 4]; 2],
+    region: Option<Window>,
+) -> ImageBuffer {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_sq = (dx * dx + dy * dy).max(1e-12);
+
+    generate(width, height, 4, region, |x, y, out| {
+        let (px, py) = (x as f64 + 0.5 - start.0, y as f64 + 0.5 - start.1);
+        let t = match ramp_type {
+            RampType::Linear => (px * dx + py * dy) / length_sq,
+            RampType::Radial => ((px * px + py * py) / length_sq).sqrt(),
+        }
+        .clamp(0.0, 1.0) as f32;
+
+        for (c, value) in out.iter_mut().enumerate() {
+            *value = colors[0][c] + (colors[1][c] - colors[0][c]) * t;
+        }
+    })
+}
+
+/// Squares of `size` pixels alternating between two colours,
+/// starting with the first one in the top-left corner.
+pub fn checkerboard(
+    width: usize,
+    height: usize,
+    size: usize,
+    colors: [[f32; 4]; 2],
+    region: Option<Window>,
+) -> ImageBuffer {
+    let size = size.max(1);
+    generate(width, height, 4, region, |x, y, out| {
+        out.copy_from_slice(&colors[(x / size + y / size) % 2]);
+    })
+}
+
+/// Single channel fractal noise in the range `0..=1`.
+/// The same seed always produces the same image.
+pub fn noise(width: usize, height: usize, settings: &Noise, region: Option<Window>) -> ImageBuffer {
+    let scale = settings.scale.max(1e-3);
+    let octaves = settings.octaves.max(1);
+
+    generate(width, height, 1, region, |x, y, out| {
+        let (x, y) = ((x as f64 + 0.5) / scale, (y as f64 + 0.5) / scale);
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+
+        for octave in 0..octaves {
+            let seed = settings.seed.wrapping_add(octave.wrapping_mul(0x9e37_79b9));
+            sum += amplitude
+                * settings
+                    .noise_type
+                    .sample(x * frequency, y * frequency, seed);
+            total += amplitude;
+            frequency *= settings.lacunarity;
+            amplitude *= settings.gain;
+        }
+
+        out[0] = if total > 0.0 {
+            (sum / total) as f32
+        } else {
+            0.0
+        };
+    })
+}
+
+/// Integer hash of a lattice point.
+fn hash(x: i64, y: i64, seed: u32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x27d4_eb2d)
+        ^ (y as u32).wrapping_mul(0x1656_67b1)
+        ^ seed.wrapping_mul(0x9e37_79b9);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^ (h >> 16)
+}
+
+/// Unit gradient of a lattice point.
+fn gradient(x: i64, y: i64, seed: u32) -> (f64, f64) {
+    let angle = hash(x, y, seed) as f64 / u32::MAX as f64 * std::f64::consts::TAU;
+    (angle.cos(), angle.sin())
+}
+
+/// Quintic fade curve with zero first and second derivatives at 0 and 1.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Smoothly interpolated random values at lattice points, in `0..=1`.
+fn value_noise(x: f64, y: f64, seed: u32) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let (u, v) = (fade(x - x0), fade(y - y0));
+    let value = |dx, dy| hash(ix + dx, iy + dy, seed) as f64 / u32::MAX as f64;
+
+    lerp(
+        lerp(value(0, 0), value(1, 0), u),
+        lerp(value(0, 1), value(1, 1), u),
+        v,
+    )
+}
+
+/// Gradient noise in about `-1..=1`.
+fn perlin_noise(x: f64, y: f64, seed: u32) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let (fx, fy) = (x - x0, y - y0);
+    let dot = |dx: i64, dy: i64| {
+        let (gx, gy) = gradient(ix + dx, iy + dy, seed);
+        gx * (fx - dx as f64) + gy * (fy - dy as f64)
+    };
+
+    let (u, v) = (fade(fx), fade(fy));
+    // Unit gradients reach at most 1/sqrt(2) in 2D
+    lerp(
+        lerp(dot(0, 0), dot(1, 0), u),
+        lerp(dot(0, 1), dot(1, 1), u),
+        v,
+    ) * std::f64::consts::SQRT_2
+}
+
+/// Simplex noise in about `-1..=1`, with fewer axis aligned artefacts than Perlin noise.
+fn simplex_noise(x: f64, y: f64, seed: u32) -> f64 {
+    const F2: f64 = 0.366_025_403_784_438_6; // (sqrt(3) - 1) / 2
+    const G2: f64 = 0.211_324_865_405_187_1; // (3 - sqrt(3)) / 6
+
+    // Skew into the simplex grid to find the containing triangle
+    let s = (x + y) * F2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * G2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+    let corners = [
+        (0, 0, x0, y0),
+        (i1, j1, x0 - i1 as f64 + G2, y0 - j1 as f64 + G2),
+        (1, 1, x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2),
+    ];
+
+    let (i, j) = (i as i64, j as i64);
+    let sum: f64 = corners
+        .iter()
+        .map(|&(di, dj, dx, dy)| {
+            let falloff = 0.5 - dx * dx - dy * dy;
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+            let (gx, gy) = gradient(i + di, j + dj, seed);
+            falloff.powi(4) * (gx * dx + gy * dy)
+        })
+        .sum();
+
+    (sum * 70.0).clamp(-1.0, 1.0)
+}
@@ -0,0 +1,140 @@
+use crate::image::ImageBuffer;
+use crate::ops::morphology::{self, Element, MorphChannel, MorphOp};
+
+/// Rec. 709 luma weights.
+pub const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Settings of the Chroma Key node. The key colour is unpremultiplied RGB.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChromaKey {
+    pub key: [f32; 3],
+    /// Chroma distance, relative to the saturation of the key, below which
+    /// pixels are fully keyed out.
+    pub tolerance: f32,
+    /// Width of the transition from keyed out to opaque.
+    pub softness: f32,
+    /// How much of the key colour's dominant channel is removed from the foreground.
+    pub despill: f32,
+    /// Pixels to shrink the matte by. Negative values grow it.
+    pub choke: i32,
+}
+
+/// Settings of the Luma Key node. Luminance between `low` and `high` is kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LumaKey {
+    pub low: f32,
+    pub low_softness: f32,
+    pub high: f32,
+    pub high_softness: f32,
+    pub choke: i32,
+}
+
+/// Pulls a matte from the distance between each pixel and the key colour in
+/// the CbCr plane, so brightness changes across the screen don't matter.
+///
+/// Returns the despilled foreground premultiplied by the matte, and the matte
+/// itself as a single channel image.
+pub fn chroma_key(src: &ImageBuffer, settings: &ChromaKey) -> (ImageBuffer, ImageBuffer) {
+    let (_, key_cb, key_cr) = ycbcr(settings.key);
+    let saturation = key_cb.hypot(key_cr).max(1e-3);
+    let dominant = (0..3)
+        .max_by(|&a, &b| settings.key[a].total_cmp(&settings.key[b]))
+        .unwrap_or(1);
+
+    let mut matte = matte(src, |[r, g, b, _]| {
+        let (_, cb, cr) = ycbcr([r, g, b]);
+        let distance = (cb - key_cb).hypot(cr - key_cr) / saturation;
+        smoothstep(
+            settings.tolerance,
+            settings.tolerance + settings.softness,
+            distance,
+        )
+    });
+    choke(&mut matte, settings.choke);
+
+    let output = premultiply(src, &matte, |rgb| {
+        let others = (rgb.iter().sum::<f32>() - rgb[dominant]) / 2.0;
+        let spill = (rgb[dominant] - others).max(0.0);
+        rgb[dominant] -= spill * settings.despill;
+    });
+    (output, matte)
+}
+
+/// Pulls a matte from the luminance of each pixel.
+///
+/// Returns the input premultiplied by the matte, and the matte itself as a
+/// single channel image.
+pub fn luma_key(src: &ImageBuffer, settings: &LumaKey) -> (ImageBuffer, ImageBuffer) {
+    let mut matte = matte(src, |[r, g, b, _]| {
+        let luma = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+        let above = smoothstep(settings.low - settings.low_softness, settings.low, luma);
+        let below = 1.0 - smoothstep(settings.high, settings.high + settings.high_softness, luma);
+        above * below
+    });
+    choke(&mut matte, settings.choke);
+
+    let output = premultiply(src, &matte, |_| {});
+    (output, matte)
+}
+
+/// Converts linear RGB to Rec. 709 luma and blue/red difference chroma.
+fn ycbcr([r, g, b]: [f32; 3]) -> (f32, f32, f32) {
+    let y = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+    (y, (b - y) / 1.8556, (r - y) / 1.5748)
+}
+
+/// Hermite step from 0 at `edge0` to 1 at `edge1`, a hard step if they are equal.
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Single channel image of `f` evaluated on the unpremultiplied pixels of `src`,
+/// multiplied by the alpha of `src`.
+fn matte(src: &ImageBuffer, f: impl Fn([f32; 4]) -> f32 + Sync) -> ImageBuffer {
+    ImageBuffer::from_fn_windows(src.display_window(), src.data_window(), 1, |x, y, out| {
+        let [r, g, b, a] = src.rgba(x, y);
+        let unpremult = if a > 0.0 { 1.0 / a } else { 1.0 };
+        out[0] = f([r * unpremult, g * unpremult, b * unpremult, a]) * a;
+    })
+}
+
+/// RGBA image of `src` with `spill` applied to its unpremultiplied colour and
+/// `matte` as alpha.
+fn premultiply(
+    src: &ImageBuffer,
+    matte: &ImageBuffer,
+    spill: impl Fn(&mut [f32; 3]) + Sync,
+) -> ImageBuffer {
+    ImageBuffer::from_fn_windows(src.display_window(), src.data_window(), 4, |x, y, out| {
+        let [r, g, b, a] = src.rgba(x, y);
+        let unpremult = if a > 0.0 { 1.0 / a } else { 1.0 };
+        let mut rgb = [r * unpremult, g * unpremult, b * unpremult];
+        spill(&mut rgb);
+
+        let alpha = matte.get(x, y, 0);
+        out.copy_from_slice(&[rgb[0] * alpha, rgb[1] * alpha, rgb[2] * alpha, alpha]);
+    })
+}
+
+/// Shrinks a single channel matte by `radius` pixels, or grows it if `radius` is negative.
+fn choke(matte: &mut ImageBuffer, radius: i32) {
+    if radius == 0 {
+        return;
+    }
+    let op = if radius > 0 {
+        MorphOp::Erode
+    } else {
+        MorphOp::Dilate
+    };
+    *matte = morphology::morphology(
+        matte,
+        op,
+        Element::Box,
+        radius.unsigned_abs() as f64,
+        MorphChannel::All,
+    );
+}
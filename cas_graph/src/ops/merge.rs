@@ -0,0 +1,88 @@
+use crate::image::ImageBuffer;
+
+/// Compositing operation of the Merge node. `A` is the foreground, `B` the background.
+/// Colours are expected to be premultiplied by alpha.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeOp {
+    Over,
+    Under,
+    Plus,
+    Minus,
+    Multiply,
+    Screen,
+    Difference,
+    In,
+    Out,
+}
+
+impl MergeOp {
+    pub const ALL: [MergeOp; 9] = [
+        MergeOp::Over,
+        MergeOp::Under,
+        MergeOp::Plus,
+        MergeOp::Minus,
+        MergeOp::Multiply,
+        MergeOp::Screen,
+        MergeOp::Difference,
+        MergeOp::In,
+        MergeOp::Out,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            MergeOp::Over => "Over",
+            MergeOp::Under => "Under",
+            MergeOp::Plus => "Plus",
+            MergeOp::Minus => "Minus",
+            MergeOp::Multiply => "Multiply",
+            MergeOp::Screen => "Screen",
+            MergeOp::Difference => "Difference",
+            MergeOp::In => "In",
+            MergeOp::Out => "Out",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|op| op.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(MergeOp::Over)
+    }
+
+    fn apply(self, a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+        let (aa, ba) = (a[3], b[3]);
+        std::array::from_fn(|c| match self {
+            MergeOp::Over => a[c] + b[c] * (1.0 - aa),
+            MergeOp::Under => b[c] + a[c] * (1.0 - ba),
+            MergeOp::Plus => a[c] + b[c],
+            MergeOp::Minus => a[c] - b[c],
+            MergeOp::Multiply => a[c] * b[c],
+            MergeOp::Screen => a[c] + b[c] - a[c] * b[c],
+            MergeOp::Difference => (a[c] - b[c]).abs(),
+            MergeOp::In => a[c] * ba,
+            MergeOp::Out => a[c] * (1.0 - ba),
+        })
+    }
+}
+
+/// Combines `a` onto `b`, blending the result with `b` by `mix`.
+///
+/// The output has four channels and the display window of `b`. Its data
+/// window is the union of both data windows clipped to that display window.
+pub fn merge(a: &ImageBuffer, b: &ImageBuffer, op: MergeOp, mix: f32) -> ImageBuffer {
+    let display_window = b.display_window();
+    let data_window = a
+        .data_window()
+        .union(&b.data_window())
+        .intersect(&display_window);
+
+    ImageBuffer::from_fn_windows(display_window, data_window, 4, |x, y, out| {
+        let a = a.rgba(x, y);
+        let b = b.rgba(x, y);
+        let merged = op.apply(a, b);
+        for (c, value) in out.iter_mut().enumerate() {
+            *value = b[c] + (merged[c] - b[c]) * mix;
+        }
+    })
+}
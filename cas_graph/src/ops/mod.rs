@@ -0,0 +1,1078 @@
+pub mod channel;
+pub mod color;
+pub mod denoise;
+pub mod edge;
+pub mod filter;
+pub mod generate;
+pub mod keyer;
+pub mod merge;
+pub mod morphology;
+pub mod pointwise;
+pub mod reformat;
+pub mod resample;
+pub mod stats;
+pub mod transform;
+
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::eval::{EvalError, Value};
+use crate::image::{ImageBuffer, Window};
+use crate::node_property::NodeProperty;
+use crate::sequence::MissingFrames;
+use channel::ChannelSource;
+use color::Grade;
+use edge::{Canny, EdgeMethod};
+use filter::Kernel;
+use generate::{Noise, NoiseType, RampType};
+use keyer::{ChromaKey, LumaKey};
+use merge::MergeOp;
+use morphology::{Element, MorphChannel, MorphOp};
+use pointwise::PointOp;
+use reformat::ReformatMode;
+use resample::Filter;
+use stats::StatsChannel;
+use transform::TransformParams;
+
+/// Largest width or height accepted by format properties, also the limit of
+/// sizes arriving on a wire or from a project file.
+const MAX_SIZE: i32 = 65536;
+
+/// Kind of value carried by a pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinType {
+    Number,
+    String,
+    Image,
+}
+
+/// The available categories that are shown in the graph menu.
+/// Each node must belong to exactly one category.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeCategory {
+    IO,
+    Generate,
+    Transform,
+    Channel,
+    Color,
+    Filter,
+    Keyer,
+    Merge,
+    Analysis,
+}
+
+impl NodeCategory {
+    pub const ALL: [NodeCategory; 9] = [
+        NodeCategory::IO,
+        NodeCategory::Generate,
+        NodeCategory::Transform,
+        NodeCategory::Channel,
+        NodeCategory::Color,
+        NodeCategory::Filter,
+        NodeCategory::Keyer,
+        NodeCategory::Merge,
+        NodeCategory::Analysis,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            NodeCategory::IO => "IO",
+            NodeCategory::Generate => "Generate",
+            NodeCategory::Transform => "Transform",
+            NodeCategory::Channel => "Channel",
+            NodeCategory::Color => "Color",
+            NodeCategory::Filter => "Filter",
+            NodeCategory::Keyer => "Keyer",
+            NodeCategory::Merge => "Merge",
+            NodeCategory::Analysis => "Analysis",
+        }
+    }
+}
+
+/// The types of image processing nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OpType {
+    Read,
+    Write,
+    Constant,
+    Ramp,
+    Checkerboard,
+    Noise,
+    Transform,
+    Crop,
+    Resize,
+    Reformat,
+    Shuffle,
+    Split,
+    Join,
+    Premultiply,
+    Unpremultiply,
+    Grade,
+    Saturation,
+    Blur,
+    Sharpen,
+    UnsharpMask,
+    EdgeDetect,
+    Convolve,
+    Morphology,
+    Median,
+    Bilateral,
+    NonLocalMeans,
+    ChromaKey,
+    LumaKey,
+    Merge,
+    ImageStats,
+    Sample,
+}
+
+impl OpType {
+    pub const ALL: [OpType; 31] = [
+        OpType::Read,
+        OpType::Write,
+        OpType::Constant,
+        OpType::Ramp,
+        OpType::Checkerboard,
+        OpType::Noise,
+        OpType::Transform,
+        OpType::Crop,
+        OpType::Resize,
+        OpType::Reformat,
+        OpType::Shuffle,
+        OpType::Split,
+        OpType::Join,
+        OpType::Premultiply,
+        OpType::Unpremultiply,
+        OpType::Grade,
+        OpType::Saturation,
+        OpType::Blur,
+        OpType::Sharpen,
+        OpType::UnsharpMask,
+        OpType::EdgeDetect,
+        OpType::Convolve,
+        OpType::Morphology,
+        OpType::Median,
+        OpType::Bilateral,
+        OpType::NonLocalMeans,
+        OpType::ChromaKey,
+        OpType::LumaKey,
+        OpType::Merge,
+        OpType::ImageStats,
+        OpType::Sample,
+    ];
+
+    /// The display name of a node.
+    pub const fn name(self) -> &'static str {
+        match self {
+            OpType::Read => "Read",
+            OpType::Write => "Write",
+            OpType::Constant => "Constant",
+            OpType::Ramp => "Ramp",
+            OpType::Checkerboard => "Checkerboard",
+            OpType::Noise => "Noise",
+            OpType::Transform => "Transform",
+            OpType::Crop => "Crop",
+            OpType::Resize => "Resize",
+            OpType::Reformat => "Reformat",
+            OpType::Shuffle => "Shuffle",
+            OpType::Split => "Split",
+            OpType::Join => "Join",
+            OpType::Premultiply => "Premultiply",
+            OpType::Unpremultiply => "Unpremultiply",
+            OpType::Grade => "Grade",
+            OpType::Saturation => "Saturation",
+            OpType::Blur => "Blur",
+            OpType::Sharpen => "Sharpen",
+            OpType::UnsharpMask => "Unsharp Mask",
+            OpType::EdgeDetect => "Edge Detect",
+            OpType::Convolve => "Convolve",
+            OpType::Morphology => "Morphology",
+            OpType::Median => "Median",
+            OpType::Bilateral => "Bilateral",
+            OpType::NonLocalMeans => "Non-Local Means",
+            OpType::ChromaKey => "Chroma Key",
+            OpType::LumaKey => "Luma Key",
+            OpType::Merge => "Merge",
+            OpType::ImageStats => "Image Stats",
+            OpType::Sample => "Sample",
+        }
+    }
+
+    /// The category a node belongs to.
+    pub const fn category(self) -> NodeCategory {
+        match self {
+            OpType::Read | OpType::Write => NodeCategory::IO,
+            OpType::Constant | OpType::Ramp | OpType::Checkerboard | OpType::Noise => {
+                NodeCategory::Generate
+            }
+            OpType::Transform | OpType::Crop | OpType::Resize | OpType::Reformat => {
+                NodeCategory::Transform
+            }
+            OpType::Shuffle
+            | OpType::Split
+            | OpType::Join
+            | OpType::Premultiply
+            | OpType::Unpremultiply => NodeCategory::Channel,
+            OpType::Grade | OpType::Saturation => NodeCategory::Color,
+            OpType::Blur
+            | OpType::Sharpen
+            | OpType::UnsharpMask
+            | OpType::EdgeDetect
+            | OpType::Convolve
+            | OpType::Morphology
+            | OpType::Median
+            | OpType::Bilateral
+            | OpType::NonLocalMeans => NodeCategory::Filter,
+            OpType::ChromaKey | OpType::LumaKey => NodeCategory::Keyer,
+            OpType::Merge => NodeCategory::Merge,
+            OpType::ImageStats | OpType::Sample => NodeCategory::Analysis,
+        }
+    }
+
+    pub const fn description(self) -> &'static str {
+        match self {
+            OpType::Read => "Reads an image file or a frame of an image sequence",
+            OpType::Write => "Writes its source to an image file or sequence when rendered",
+            OpType::Constant => "Fills an image with a single colour",
+            OpType::Ramp => "Blends between two colours along a line or around a point",
+            OpType::Checkerboard => "Alternates squares of two colours",
+            OpType::Noise => "Generates seeded fractal noise as a single channel image",
+            OpType::Transform => "Translates, rotates, scales and skews an image around a pivot",
+            OpType::Crop => {
+                "Restricts the image to a box, optionally making the box the new format"
+            }
+            OpType::Resize => "Scales the image to a new size",
+            OpType::Reformat => "Fits the image into a new format",
+            OpType::Shuffle => "Routes any input channel, or a constant, to each output channel",
+            OpType::Split => "Splits an image into single channel images",
+            OpType::Join => "Combines single channel images into one image",
+            OpType::Premultiply => "Multiplies the colour channels by alpha",
+            OpType::Unpremultiply => "Divides the colour channels by alpha",
+            OpType::Grade => "Adjusts black, white and midtones of the colour channels",
+            OpType::Saturation => "Moves colours towards or away from grey",
+            OpType::Blur => "Blurs the image with a gaussian",
+            OpType::Sharpen => "Boosts detail finer than the radius",
+            OpType::UnsharpMask => {
+                "Boosts detail finer than the radius where it exceeds a threshold"
+            }
+            OpType::EdgeDetect => "Outputs the edges of the image luminance as a single channel",
+            OpType::Convolve => "Applies a kernel typed into the node or read from an image",
+            OpType::Morphology => "Erodes, dilates, opens or closes a channel, usually alpha",
+            OpType::Median => "Removes specks and dust with a median of luma and chroma",
+            OpType::Bilateral => "Smooths noise in luma and chroma while keeping edges sharp",
+            OpType::NonLocalMeans => {
+                "Removes grain by averaging similar patches of luma and chroma"
+            }
+            OpType::ChromaKey => "Pulls a matte from a key colour and removes its spill",
+            OpType::LumaKey => "Pulls a matte from a range of luminance",
+            OpType::Merge => "Composites A onto B, keeping the format of B",
+            OpType::ImageStats => "Measures a channel over the whole frame as numbers",
+            OpType::Sample => "Reads the colour of one pixel as numbers",
+        }
+    }
+
+    /// The graph inputs of the node, in front of the property inputs.
+    pub const fn inputs(self) -> &'static [(&'static str, PinType)] {
+        match self {
+            OpType::Read
+            | OpType::Constant
+            | OpType::Ramp
+            | OpType::Checkerboard
+            | OpType::Noise => &[],
+            OpType::Transform
+            | OpType::Crop
+            | OpType::Resize
+            | OpType::Reformat
+            | OpType::Shuffle
+            | OpType::Split
+            | OpType::Premultiply
+            | OpType::Unpremultiply
+            | OpType::Grade
+            | OpType::Saturation
+            | OpType::Blur
+            | OpType::Sharpen
+            | OpType::UnsharpMask
+            | OpType::EdgeDetect
+            | OpType::Morphology
+            | OpType::Median
+            | OpType::Bilateral
+            | OpType::NonLocalMeans
+            | OpType::ChromaKey
+            | OpType::LumaKey
+            | OpType::ImageStats
+            | OpType::Sample
+            | OpType::Write => &[("Source", PinType::Image)],
+            OpType::Convolve => &[("Source", PinType::Image), ("Kernel", PinType::Image)],
+            OpType::Join => &[
+                ("Red", PinType::Image),
+                ("Green", PinType::Image),
+                ("Blue", PinType::Image),
+                ("Alpha", PinType::Image),
+            ],
+            OpType::Merge => &[("B", PinType::Image), ("A", PinType::Image)],
+        }
+    }
+
+    /// The graph outputs of the node.
+    pub const fn outputs(self) -> &'static [(&'static str, PinType)] {
+        match self {
+            OpType::Read
+            | OpType::Write
+            | OpType::Constant
+            | OpType::Ramp
+            | OpType::Checkerboard
+            | OpType::Noise
+            | OpType::Transform
+            | OpType::Crop
+            | OpType::Resize
+            | OpType::Reformat
+            | OpType::Shuffle
+            | OpType::Join
+            | OpType::Premultiply
+            | OpType::Unpremultiply
+            | OpType::Grade
+            | OpType::Saturation
+            | OpType::Blur
+            | OpType::Sharpen
+            | OpType::UnsharpMask
+            | OpType::EdgeDetect
+            | OpType::Convolve
+            | OpType::Morphology
+            | OpType::Median
+            | OpType::Bilateral
+            | OpType::NonLocalMeans
+            | OpType::Merge => &[("Output", PinType::Image)],
+            OpType::Split => &[
+                ("Red", PinType::Image),
+                ("Green", PinType::Image),
+                ("Blue", PinType::Image),
+                ("Alpha", PinType::Image),
+            ],
+            OpType::ChromaKey | OpType::LumaKey => {
+                &[("Output", PinType::Image), ("Matte", PinType::Image)]
+            }
+            OpType::ImageStats => &[
+                ("Min", PinType::Number),
+                ("Max", PinType::Number),
+                ("Mean", PinType::Number),
+                ("Median", PinType::Number),
+                ("Std Dev", PinType::Number),
+            ],
+            OpType::Sample => &[
+                ("Red", PinType::Number),
+                ("Green", PinType::Number),
+                ("Blue", PinType::Number),
+                ("Alpha", PinType::Number),
+            ],
+        }
+    }
+
+    /// Properties measured in pixels, which the proxy scale applies to.
+    pub const fn spatial_properties(self) -> &'static [&'static str] {
+        match self {
+            OpType::Constant | OpType::Reformat => &["Width", "Height"],
+            OpType::Ramp => &["Width", "Height", "Start X", "Start Y", "End X", "End Y"],
+            OpType::Checkerboard => &["Width", "Height", "Size"],
+            OpType::Noise => &["Width", "Height", "Scale"],
+            OpType::Transform => &["Translate X", "Translate Y", "Pivot X", "Pivot Y"],
+            OpType::Crop => &["X", "Y", "Width", "Height"],
+            OpType::Resize => &["Width", "Height"],
+            OpType::Blur => &["Size"],
+            OpType::Sharpen | OpType::UnsharpMask | OpType::Morphology => &["Radius"],
+            OpType::EdgeDetect => &["Sigma"],
+            OpType::Median => &["Luma Radius", "Chroma Radius"],
+            OpType::Bilateral => &["Spatial Sigma"],
+            OpType::NonLocalMeans => &["Search Radius"],
+            OpType::ChromaKey | OpType::LumaKey => &["Choke"],
+            OpType::Sample => &["X", "Y"],
+            OpType::Read
+            | OpType::Write
+            | OpType::Shuffle
+            | OpType::Split
+            | OpType::Join
+            | OpType::Premultiply
+            | OpType::Unpremultiply
+            | OpType::Grade
+            | OpType::Saturation
+            | OpType::Convolve
+            | OpType::Merge
+            | OpType::ImageStats => &[],
+        }
+    }
+
+    /// Whether the type computes every pixel from the same pixel of its
+    /// input alone, so a chain of them can run as a single pass.
+    pub const fn is_point_op(self) -> bool {
+        matches!(
+            self,
+            OpType::Grade | OpType::Saturation | OpType::Premultiply | OpType::Unpremultiply
+        )
+    }
+
+    /// The properties of a newly created node.
+    pub fn properties(self) -> Vec<NodeProperty> {
+        match self {
+            OpType::Read => vec![
+                NodeProperty::new_path("File"),
+                NodeProperty::new_choice("Missing Frames", &MissingFrames::names(), 0),
+            ],
+            OpType::Write => vec![NodeProperty::new_path("File")],
+            OpType::Constant => vec![
+                NodeProperty::new_int("Width", 1, MAX_SIZE, 1, 1920),
+                NodeProperty::new_int("Height", 1, MAX_SIZE, 1, 1080),
+                NodeProperty::new_color("Color", [0.5, 0.5, 0.5, 1.0]),
+            ],
+            OpType::Ramp => vec![
+                NodeProperty::new_int("Width", 1, MAX_SIZE, 1, 1920),
+                NodeProperty::new_int("Height", 1, MAX_SIZE, 1, 1080),
+                NodeProperty::new_choice("Type", &RampType::names(), 0),
+                NodeProperty::new_float("Start X", f64::MIN, f64::MAX, 1.0, 0.0),
+                NodeProperty::new_float("Start Y", f64::MIN, f64::MAX, 1.0, 540.0),
+                NodeProperty::new_float("End X", f64::MIN, f64::MAX, 1.0, 1920.0),
+                NodeProperty::new_float("End Y", f64::MIN, f64::MAX, 1.0, 540.0),
+                NodeProperty::new_color("Start Color", [0.0, 0.0, 0.0, 1.0]),
+                NodeProperty::new_color("End Color", [1.0, 1.0, 1.0, 1.0]),
+            ],
+            OpType::Checkerboard => vec![
+                NodeProperty::new_int("Width", 1, MAX_SIZE, 1, 1920),
+                NodeProperty::new_int("Height", 1, MAX_SIZE, 1, 1080),
+                NodeProperty::new_int("Size", 1, MAX_SIZE, 1, 64),
+                NodeProperty::new_color("Color A", [0.1, 0.1, 0.1, 1.0]),
+                NodeProperty::new_color("Color B", [0.5, 0.5, 0.5, 1.0]),
+            ],
+            OpType::Noise => vec![
+                NodeProperty::new_int("Width", 1, MAX_SIZE, 1, 1920),
+                NodeProperty::new_int("Height", 1, MAX_SIZE, 1, 1080),
+                NodeProperty::new_choice("Type", &NoiseType::names(), 1),
+                NodeProperty::new_int("Seed", 0, i32::MAX, 1, 0),
+                NodeProperty::new_float("Scale", 1.0, 10000.0, 1.0, 100.0),
+                NodeProperty::new_int("Octaves", 1, 12, 1, 4),
+                NodeProperty::new_float("Lacunarity", 1.0, 8.0, 0.01, 2.0),
+                NodeProperty::new_float("Gain", 0.0, 1.0, 0.01, 0.5),
+            ],
+            OpType::Transform => vec![
+                NodeProperty::new_float("Translate X", f64::MIN, f64::MAX, 1.0, 0.0),
+                NodeProperty::new_float("Translate Y", f64::MIN, f64::MAX, 1.0, 0.0),
+                NodeProperty::new_float("Rotate", -360.0, 360.0, 0.5, 0.0),
+                NodeProperty::new_float("Scale X", 0.0, 100.0, 0.01, 1.0),
+                NodeProperty::new_float("Scale Y", 0.0, 100.0, 0.01, 1.0),
+                NodeProperty::new_float("Skew X", -10.0, 10.0, 0.01, 0.0),
+                NodeProperty::new_float("Skew Y", -10.0, 10.0, 0.01, 0.0),
+                NodeProperty::new_float("Pivot X", f64::MIN, f64::MAX, 1.0, 0.0),
+                NodeProperty::new_float("Pivot Y", f64::MIN, f64::MAX, 1.0, 0.0),
+                NodeProperty::new_choice("Filter", &Filter::names(), 3),
+            ],
+            OpType::Crop => vec![
+                NodeProperty::new_int("X", -MAX_SIZE, MAX_SIZE, 1, 0),
+                NodeProperty::new_int("Y", -MAX_SIZE, MAX_SIZE, 1, 0),
+                NodeProperty::new_int("Width", 0, MAX_SIZE, 1, 1920),
+                NodeProperty::new_int("Height", 0, MAX_SIZE, 1, 1080),
+                NodeProperty::new_choice("Format", &["Crop to box", "Keep"], 0),
+            ],
+            OpType::Resize => vec![
+                NodeProperty::new_choice("Mode", &["Size", "Scale"], 0),
+                NodeProperty::new_int("Width", 1, MAX_SIZE, 1, 1920),
+                NodeProperty::new_int("Height", 1, MAX_SIZE, 1, 1080),
+                NodeProperty::new_float("Scale", 0.0, 100.0, 0.01, 0.5),
+                NodeProperty::new_choice("Filter", &Filter::names(), 3),
+            ],
+            OpType::Reformat => vec![
+                NodeProperty::new_int("Width", 1, MAX_SIZE, 1, 1920),
+                NodeProperty::new_int("Height", 1, MAX_SIZE, 1, 1080),
+                NodeProperty::new_choice("Resize", &ReformatMode::names(), 0),
+                NodeProperty::new_choice("Filter", &Filter::names(), 3),
+            ],
+            OpType::Shuffle => vec![
+                NodeProperty::new_choice("Red", &ChannelSource::names(), 0),
+                NodeProperty::new_choice("Green", &ChannelSource::names(), 1),
+                NodeProperty::new_choice("Blue", &ChannelSource::names(), 2),
+                NodeProperty::new_choice("Alpha", &ChannelSource::names(), 3),
+            ],
+            OpType::Split | OpType::Join | OpType::Premultiply | OpType::Unpremultiply => {
+                Vec::new()
+            }
+            OpType::Grade => vec![
+                NodeProperty::new_float("Lift", -1.0, 1.0, 0.001, 0.0),
+                NodeProperty::new_float("Gain", 0.0, 10.0, 0.001, 1.0),
+                NodeProperty::new_float("Offset", -1.0, 1.0, 0.001, 0.0),
+                NodeProperty::new_float("Gamma", 0.1, 10.0, 0.001, 1.0),
+            ],
+            OpType::Saturation => vec![NodeProperty::new_float("Saturation", 0.0, 4.0, 0.01, 1.0)],
+            OpType::Blur => vec![NodeProperty::new_float("Size", 0.0, 500.0, 0.1, 2.0)],
+            OpType::Sharpen => vec![
+                NodeProperty::new_float("Amount", 0.0, 10.0, 0.01, 0.5),
+                NodeProperty::new_float("Radius", 0.1, 100.0, 0.1, 1.0),
+            ],
+            OpType::UnsharpMask => vec![
+                NodeProperty::new_float("Amount", 0.0, 10.0, 0.01, 1.0),
+                NodeProperty::new_float("Radius", 0.1, 100.0, 0.1, 2.0),
+                NodeProperty::new_float("Threshold", 0.0, 1.0, 0.001, 0.02),
+            ],
+            OpType::EdgeDetect => vec![
+                NodeProperty::new_choice("Method", &EdgeMethod::names(), 0),
+                NodeProperty::new_float("Sigma", 0.0, 20.0, 0.1, 1.4),
+                NodeProperty::new_float("Low", 0.0, 1.0, 0.001, 0.05),
+                NodeProperty::new_float("High", 0.0, 1.0, 0.001, 0.15),
+            ],
+            OpType::Convolve => vec![
+                NodeProperty::new_text("Kernel", "0 -1 0\n-1 5 -1\n0 -1 0"),
+                NodeProperty::new_choice("Normalize", &["Off", "On"], 1),
+            ],
+            OpType::Morphology => vec![
+                NodeProperty::new_choice("Operation", &MorphOp::names(), 0),
+                NodeProperty::new_choice("Element", &Element::names(), 1),
+                NodeProperty::new_float("Radius", 0.0, 500.0, 0.1, 1.0),
+                NodeProperty::new_choice("Channel", &MorphChannel::names(), 0),
+            ],
+            OpType::Median => vec![
+                NodeProperty::new_int("Luma Radius", 0, 20, 1, 1),
+                NodeProperty::new_int("Chroma Radius", 0, 20, 1, 2),
+            ],
+            OpType::Bilateral => vec![
+                NodeProperty::new_float("Spatial Sigma", 0.1, 20.0, 0.1, 2.0),
+                NodeProperty::new_float("Luma Range", 0.0, 1.0, 0.001, 0.05),
+                NodeProperty::new_float("Chroma Range", 0.0, 1.0, 0.001, 0.1),
+            ],
+            OpType::NonLocalMeans => vec![
+                NodeProperty::new_int("Patch Size", 1, 15, 2, 3),
+                NodeProperty::new_int("Search Radius", 1, 30, 1, 7),
+                NodeProperty::new_float("Luma Strength", 0.0, 1.0, 0.001, 0.03),
+                NodeProperty::new_float("Chroma Strength", 0.0, 1.0, 0.001, 0.06),
+            ],
+            OpType::ChromaKey => vec![
+                NodeProperty::new_color("Key Color", [0.0, 1.0, 0.0, 1.0]),
+                NodeProperty::new_float("Tolerance", 0.0, 2.0, 0.01, 0.3),
+                NodeProperty::new_float("Softness", 0.0, 2.0, 0.01, 0.3),
+                NodeProperty::new_float("Despill", 0.0, 1.0, 0.01, 1.0),
+                NodeProperty::new_int("Choke", -50, 50, 1, 0),
+            ],
+            OpType::LumaKey => vec![
+                NodeProperty::new_float("Low", 0.0, 1.0, 0.01, 0.5),
+                NodeProperty::new_float("Low Softness", 0.0, 1.0, 0.01, 0.1),
+                NodeProperty::new_float("High", 0.0, 100.0, 0.01, 100.0),
+                NodeProperty::new_float("High Softness", 0.0, 100.0, 0.01, 0.0),
+                NodeProperty::new_int("Choke", -50, 50, 1, 0),
+            ],
+            OpType::Merge => vec![
+                NodeProperty::new_choice("Operation", &MergeOp::names(), 0),
+                NodeProperty::new_float("Mix", 0.0, 1.0, 0.01, 1.0),
+            ],
+            OpType::ImageStats => vec![NodeProperty::new_choice(
+                "Channel",
+                &StatsChannel::names(),
+                4,
+            )],
+            OpType::Sample => vec![
+                NodeProperty::new_float("X", -MAX_SIZE as f64, MAX_SIZE as f64, 1.0, 0.0),
+                NodeProperty::new_float("Y", -MAX_SIZE as f64, MAX_SIZE as f64, 1.0, 0.0),
+            ],
+        }
+    }
+}
+
+/// Image processing node.
+/// Its input pins are the graph inputs of the type followed by one pin per property.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpNode {
+    pub op_type: OpType,
+    pub properties: Vec<NodeProperty>,
+    /// Content hash the output is pinned to while the node is frozen.
+    /// A frozen node ignores changes upstream until it is unfrozen.
+    #[serde(default)]
+    pub frozen: Option<u64>,
+}
+
+impl OpNode {
+    pub fn new(op_type: OpType) -> Self {
+        OpNode {
+            op_type,
+            properties: op_type.properties(),
+            frozen: None,
+        }
+    }
+
+    /// Feeds the type and property values at `frame` to `state`.
+    pub fn hash_content<H: Hasher>(&self, frame: i64, state: &mut H) {
+        self.op_type.hash(state);
+        for property in &self.properties {
+            property.hash_content(frame, state);
+        }
+    }
+
+    /// Sets the animated properties to their values at `frame`.
+    pub fn animate(&mut self, frame: i64) {
+        for property in &mut self.properties {
+            property.animate(frame);
+        }
+    }
+
+    /// Multiplies the properties measured in pixels by `scale`.
+    pub fn scale_spatial(&mut self, scale: f64) {
+        for name in self.op_type.spatial_properties() {
+            if let Some(property) = self.property_mut(name) {
+                property.scale(scale);
+            }
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&NodeProperty> {
+        self.properties
+            .iter()
+            .find(|property| property.name() == name)
+    }
+
+    pub fn property_mut(&mut self, name: &str) -> Option<&mut NodeProperty> {
+        self.properties
+            .iter_mut()
+            .find(|property| property.name() == name)
+    }
+
+    /// Value of a number property, or zero if the node has no such property.
+    pub fn float(&self, name: &str) -> f64 {
+        match self.property(name) {
+            Some(NodeProperty::Float(data)) => data.value(),
+            Some(NodeProperty::Int(data)) => data.value() as f64,
+            _ => 0.0,
+        }
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f64) {
+        match self.property_mut(name) {
+            Some(NodeProperty::Float(data)) => *data.value_mut() = value,
+            Some(NodeProperty::Int(data)) => *data.value_mut() = value.round() as i32,
+            _ => {}
+        }
+    }
+
+    pub fn choice(&self, name: &str) -> usize {
+        match self.property(name) {
+            Some(NodeProperty::Choice(data)) => data.index(),
+            _ => 0,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> &str {
+        match self.property(name) {
+            Some(NodeProperty::Path(data)) => data.path(),
+            _ => "",
+        }
+    }
+
+    pub fn set_path(&mut self, name: &str, path: &str) {
+        if let Some(NodeProperty::Path(data)) = self.property_mut(name) {
+            *data.path_mut() = path.to_owned();
+        }
+    }
+
+    /// Value of a colour property, or transparent black if the node has no such property.
+    pub fn color(&self, name: &str) -> [f32; 4] {
+        match self.property(name) {
+            Some(NodeProperty::Color(data)) => data.rgba(),
+            _ => [0.0; 4],
+        }
+    }
+
+    pub fn text(&self, name: &str) -> &str {
+        match self.property(name) {
+            Some(NodeProperty::Text(data)) => data.text(),
+            _ => "",
+        }
+    }
+
+    pub fn transform_params(&self) -> TransformParams {
+        TransformParams {
+            translate: (self.float("Translate X"), self.float("Translate Y")),
+            rotate: self.float("Rotate"),
+            scale: (self.float("Scale X"), self.float("Scale Y")),
+            skew: (self.float("Skew X"), self.float("Skew Y")),
+            pivot: (self.float("Pivot X"), self.float("Pivot Y")),
+        }
+    }
+
+    pub fn set_transform_params(&mut self, params: &TransformParams) {
+        self.set_float("Translate X", params.translate.0);
+        self.set_float("Translate Y", params.translate.1);
+        self.set_float("Rotate", params.rotate);
+        self.set_float("Scale X", params.scale.0);
+        self.set_float("Scale Y", params.scale.1);
+        self.set_float("Skew X", params.skew.0);
+        self.set_float("Skew Y", params.skew.1);
+        self.set_float("Pivot X", params.pivot.0);
+        self.set_float("Pivot Y", params.pivot.1);
+    }
+
+    pub fn filter(&self) -> Filter {
+        Filter::from_index(self.choice("Filter"))
+    }
+
+    /// Value of a size property, at least one pixel and at most `MAX_SIZE`.
+    fn size(&self, name: &str) -> usize {
+        self.float(name).max(1.0).min(MAX_SIZE as f64) as usize
+    }
+
+    /// Region of graph input `input` that the pixels of `region` in the
+    /// output depend on. `None` stands for the whole image.
+    ///
+    /// Filters grow the region by the distance they reach and transforms map
+    /// it through their inverse. Property inputs, nodes that look at the
+    /// whole frame and nodes whose reach depends on the size of an input
+    /// always need the whole image.
+    pub fn input_region(&self, input: usize, region: Option<Window>) -> Option<Window> {
+        if input >= self.op_type.inputs().len() {
+            return None;
+        }
+        let margin = match self.op_type {
+            OpType::Read
+            | OpType::Constant
+            | OpType::Ramp
+            | OpType::Checkerboard
+            | OpType::Noise => return None,
+            OpType::Transform => {
+                let matrix = self.transform_params().matrix();
+                return Some(transform::source_region(&matrix, self.filter(), region?));
+            }
+            OpType::Crop => {
+                let window = self.crop_window();
+                // Cropping to the box moves it to the origin
+                let region = match self.choice("Format") {
+                    0 => region.map(|region| region.translate(window.x, window.y)),
+                    _ => region,
+                };
+                return Some(region.map_or(window, |region| region.intersect(&window)));
+            }
+            OpType::Sample => {
+                let (x, y) = (self.float("X").floor(), self.float("Y").floor());
+                return Some(Window::new(x as isize, y as isize, 1, 1));
+            }
+            // Resize and Reformat scale the format of their input, which is
+            // only known once it is evaluated. A kernel image has an unknown size.
+            OpType::Resize | OpType::Reformat | OpType::Convolve | OpType::ImageStats => {
+                return None
+            }
+            OpType::EdgeDetect => match EdgeMethod::from_index(self.choice("Method")) {
+                // Hysteresis follows edges across the whole frame
+                EdgeMethod::Canny => return None,
+                _ => 1,
+            },
+            OpType::Blur => filter::gaussian_radius(self.float("Size")),
+            OpType::Sharpen | OpType::UnsharpMask => filter::gaussian_radius(self.float("Radius")),
+            OpType::Morphology => {
+                let radius = self.float("Radius").max(0.0).ceil() as usize;
+                match MorphOp::from_index(self.choice("Operation")) {
+                    MorphOp::Open | MorphOp::Close => 2 * radius,
+                    _ => radius,
+                }
+            }
+            OpType::Median => self.float("Luma Radius").max(self.float("Chroma Radius")) as usize,
+            OpType::Bilateral => (self.float("Spatial Sigma") * 2.0).ceil() as usize,
+            OpType::NonLocalMeans => {
+                self.size("Search Radius") + self.size("Patch Size").saturating_sub(1) / 2
+            }
+            OpType::ChromaKey | OpType::LumaKey => self.float("Choke").abs() as usize,
+            OpType::Write
+            | OpType::Shuffle
+            | OpType::Split
+            | OpType::Join
+            | OpType::Premultiply
+            | OpType::Unpremultiply
+            | OpType::Grade
+            | OpType::Saturation
+            | OpType::Merge => 0,
+        };
+        Some(region?.expand(margin))
+    }
+
+    /// Point operation of the node, for types where `OpType::is_point_op` holds.
+    pub fn point_op(&self) -> Option<PointOp> {
+        match self.op_type {
+            OpType::Grade => Some(PointOp::Grade(self.grade())),
+            OpType::Saturation => Some(PointOp::Saturation(self.float("Saturation") as f32)),
+            OpType::Premultiply => Some(PointOp::Premultiply),
+            OpType::Unpremultiply => Some(PointOp::Unpremultiply),
+            _ => None,
+        }
+    }
+
+    fn grade(&self) -> Grade {
+        Grade {
+            lift: self.float("Lift") as f32,
+            gain: self.float("Gain") as f32,
+            offset: self.float("Offset") as f32,
+            gamma: self.float("Gamma") as f32,
+        }
+    }
+
+    fn crop_window(&self) -> Window {
+        Window::new(
+            self.float("X") as isize,
+            self.float("Y") as isize,
+            self.float("Width").max(0.0) as usize,
+            self.float("Height").max(0.0) as usize,
+        )
+    }
+
+    /// Runs the node on already evaluated graph inputs. Generators only
+    /// compute the pixels inside `region`, other nodes compute the pixels
+    /// their inputs cover. Read nodes are evaluated by the `Evaluator`,
+    /// which applies the proxy scale.
+    pub fn process(
+        &self,
+        inputs: &[Option<Value>],
+        region: Option<Window>,
+    ) -> Result<Vec<Value>, EvalError> {
+        let image = match self.op_type {
+            OpType::Read => unreachable!("Read nodes are evaluated by the evaluator"),
+            // Writing happens when the node is rendered, the image only passes through
+            OpType::Write => {
+                return Ok(vec![Value::Image(
+                    image_input(inputs, 0, "Source")?.clone(),
+                )])
+            }
+            OpType::Constant => generate::constant(
+                self.size("Width"),
+                self.size("Height"),
+                self.color("Color"),
+                region,
+            ),
+            OpType::Ramp => generate::ramp(
+                self.size("Width"),
+                self.size("Height"),
+                RampType::from_index(self.choice("Type")),
+                (self.float("Start X"), self.float("Start Y")),
+                (self.float("End X"), self.float("End Y")),
+                [self.color("Start Color"), self.color("End Color")],
+                region,
+            ),
+            OpType::Checkerboard => generate::checkerboard(
+                self.size("Width"),
+                self.size("Height"),
+                self.size("Size"),
+                [self.color("Color A"), self.color("Color B")],
+                region,
+            ),
+            OpType::Noise => {
+                let settings = Noise {
+                    noise_type: NoiseType::from_index(self.choice("Type")),
+                    seed: self.float("Seed") as u32,
+                    scale: self.float("Scale"),
+                    octaves: self.float("Octaves") as u32,
+                    lacunarity: self.float("Lacunarity"),
+                    gain: self.float("Gain"),
+                };
+                generate::noise(self.size("Width"), self.size("Height"), &settings, region)
+            }
+            OpType::Transform => {
+                let src = image_input(inputs, 0, "Source")?;
+                let matrix = self.transform_params().matrix();
+                transform::resample(src, &matrix, self.filter(), src.display_window())
+            }
+            OpType::Crop => {
+                let src = image_input(inputs, 0, "Source")?;
+                reformat::crop(src, self.crop_window(), self.choice("Format") == 0)
+            }
+            OpType::Resize => {
+                let src = image_input(inputs, 0, "Source")?;
+                let (width, height) = if self.choice("Mode") == 0 {
+                    (self.size("Width"), self.size("Height"))
+                } else {
+                    let scale = self.float("Scale");
+                    let format = src.display_window();
+                    (
+                        ((format.width as f64 * scale).round() as usize).max(1),
+                        ((format.height as f64 * scale).round() as usize).max(1),
+                    )
+                };
+                reformat::resize(src, width, height, self.filter())
+            }
+            OpType::Reformat => {
+                let src = image_input(inputs, 0, "Source")?;
+                let mode = ReformatMode::from_index(self.choice("Resize"));
+                reformat::reformat(
+                    src,
+                    self.size("Width"),
+                    self.size("Height"),
+                    mode,
+                    self.filter(),
+                )
+            }
+            OpType::Shuffle => {
+                let src = image_input(inputs, 0, "Source")?;
+                let sources = ["Red", "Green", "Blue", "Alpha"]
+                    .map(|name| ChannelSource::from_index(self.choice(name)));
+                channel::shuffle(src, sources)
+            }
+            OpType::Split => {
+                let src = image_input(inputs, 0, "Source")?;
+                return Ok(channel::split(src)
+                    .into_iter()
+                    .map(|image| Value::Image(Arc::new(image)))
+                    .collect());
+            }
+            OpType::Join => {
+                let mut channels = [None; 4];
+                for (idx, (name, _)) in self.op_type.inputs().iter().enumerate() {
+                    channels[idx] = optional_image_input(inputs, idx, name)?.map(|image| &**image);
+                }
+                channel::join(channels).ok_or(EvalError::MissingInput("Red"))?
+            }
+            OpType::Premultiply => channel::premultiply(image_input(inputs, 0, "Source")?),
+            OpType::Unpremultiply => channel::unpremultiply(image_input(inputs, 0, "Source")?),
+            OpType::Grade => color::grade(image_input(inputs, 0, "Source")?, &self.grade()),
+            OpType::Saturation => color::saturation(
+                image_input(inputs, 0, "Source")?,
+                self.float("Saturation") as f32,
+            ),
+            OpType::Blur => {
+                filter::gaussian_blur(image_input(inputs, 0, "Source")?, self.float("Size"))
+            }
+            OpType::Sharpen => filter::sharpen(
+                image_input(inputs, 0, "Source")?,
+                self.float("Amount") as f32,
+                self.float("Radius"),
+            ),
+            OpType::UnsharpMask => filter::unsharp_mask(
+                image_input(inputs, 0, "Source")?,
+                self.float("Amount") as f32,
+                self.float("Radius"),
+                self.float("Threshold") as f32,
+            ),
+            OpType::EdgeDetect => {
+                let src = image_input(inputs, 0, "Source")?;
+                let canny = Canny {
+                    sigma: self.float("Sigma"),
+                    low: self.float("Low") as f32,
+                    high: self.float("High") as f32,
+                };
+                edge::edge_detect(src, EdgeMethod::from_index(self.choice("Method")), &canny)
+            }
+            OpType::Convolve => {
+                let src = image_input(inputs, 0, "Source")?;
+                let mut kernel = match optional_image_input(inputs, 1, "Kernel")? {
+                    Some(image) => {
+                        Kernel::from_image(image).ok_or(EvalError::InvalidProperty("Kernel"))?
+                    }
+                    None => Kernel::parse(self.text("Kernel"))
+                        .ok_or(EvalError::InvalidProperty("Kernel"))?,
+                };
+                if self.choice("Normalize") == 1 {
+                    kernel.normalize();
+                }
+                filter::convolve(src, &kernel)
+            }
+            OpType::Morphology => morphology::morphology(
+                image_input(inputs, 0, "Source")?,
+                MorphOp::from_index(self.choice("Operation")),
+                Element::from_index(self.choice("Element")),
+                self.float("Radius"),
+                MorphChannel::from_index(self.choice("Channel")),
+            ),
+            OpType::Median => denoise::median(
+                image_input(inputs, 0, "Source")?,
+                self.float("Luma Radius").max(0.0) as usize,
+                self.float("Chroma Radius").max(0.0) as usize,
+            ),
+            OpType::Bilateral => denoise::bilateral(
+                image_input(inputs, 0, "Source")?,
+                self.float("Spatial Sigma") as f32,
+                self.float("Luma Range") as f32,
+                self.float("Chroma Range") as f32,
+            ),
+            OpType::NonLocalMeans => denoise::non_local_means(
+                image_input(inputs, 0, "Source")?,
+                // An even patch size rounds down to the odd size below
+                self.size("Patch Size").saturating_sub(1) / 2,
+                self.size("Search Radius"),
+                self.float("Luma Strength") as f32,
+                self.float("Chroma Strength") as f32,
+            ),
+            OpType::ChromaKey => {
+                let src = image_input(inputs, 0, "Source")?;
+                let [r, g, b, _] = self.color("Key Color");
+                let settings = ChromaKey {
+                    key: [r, g, b],
+                    tolerance: self.float("Tolerance") as f32,
+                    softness: self.float("Softness") as f32,
+                    despill: self.float("Despill") as f32,
+                    choke: self.float("Choke") as i32,
+                };
+                let (output, matte) = keyer::chroma_key(src, &settings);
+                return Ok(vec![
+                    Value::Image(Arc::new(output)),
+                    Value::Image(Arc::new(matte)),
+                ]);
+            }
+            OpType::LumaKey => {
+                let src = image_input(inputs, 0, "Source")?;
+                let settings = LumaKey {
+                    low: self.float("Low") as f32,
+                    low_softness: self.float("Low Softness") as f32,
+                    high: self.float("High") as f32,
+                    high_softness: self.float("High Softness") as f32,
+                    choke: self.float("Choke") as i32,
+                };
+                let (output, matte) = keyer::luma_key(src, &settings);
+                return Ok(vec![
+                    Value::Image(Arc::new(output)),
+                    Value::Image(Arc::new(matte)),
+                ]);
+            }
+            OpType::Merge => {
+                let b = image_input(inputs, 0, "B")?;
+                match optional_image_input(inputs, 1, "A")? {
+                    Some(a) => {
+                        let op = MergeOp::from_index(self.choice("Operation"));
+                        merge::merge(a, b, op, self.float("Mix") as f32)
+                    }
+                    None => return Ok(vec![Value::Image(b.clone())]),
+                }
+            }
+            OpType::ImageStats => {
+                let src = image_input(inputs, 0, "Source")?;
+                let stats = stats::stats(src, StatsChannel::from_index(self.choice("Channel")));
+                return Ok([
+                    stats.min,
+                    stats.max,
+                    stats.mean,
+                    stats.median,
+                    stats.std_dev,
+                ]
+                .into_iter()
+                .map(Value::Number)
+                .collect());
+            }
+            OpType::Sample => {
+                let src = image_input(inputs, 0, "Source")?;
+                let rgba = stats::sample(src, self.float("X"), self.float("Y"));
+                return Ok(rgba
+                    .into_iter()
+                    .map(|value| Value::Number(value as f64))
+                    .collect());
+            }
+        };
+
+        Ok(vec![Value::Image(Arc::new(image))])
+    }
+}
+
+/// Returns the image connected to graph input `idx`.
+pub fn image_input<'a>(
+    inputs: &'a [Option<Value>],
+    idx: usize,
+    name: &'static str,
+) -> Result<&'a Arc<ImageBuffer>, EvalError> {
+    optional_image_input(inputs, idx, name)?.ok_or(EvalError::MissingInput(name))
+}
+
+/// Returns the image connected to graph input `idx`, if any.
+pub fn optional_image_input<'a>(
+    inputs: &'a [Option<Value>],
+    idx: usize,
+    name: &'static str,
+) -> Result<Option<&'a Arc<ImageBuffer>>, EvalError> {
+    match inputs.get(idx) {
+        Some(Some(Value::Image(image))) => Ok(Some(image)),
+        Some(Some(_)) => Err(EvalError::WrongType(name)),
+        _ => Ok(None),
+    }
+}
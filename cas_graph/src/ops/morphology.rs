@@ -0,0 +1,409 @@
+use crate::image::{ImageBuffer, Window};
+
+/// Operation of the Morphology node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MorphOp {
+    /// Shrinks bright areas.
+    Erode,
+    /// Grows bright areas.
+    Dilate,
+    /// Erode then dilate, removing bright specks smaller than the element.
+    Open,
+    /// Dilate then erode, filling dark holes smaller than the element.
+    Close,
+}
+
+impl MorphOp {
+    pub const ALL: [MorphOp; 4] = [
+        MorphOp::Erode,
+        MorphOp::Dilate,
+        MorphOp::Open,
+        MorphOp::Close,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            MorphOp::Erode => "Erode",
+            MorphOp::Dilate => "Dilate",
+            MorphOp::Open => "Open",
+            MorphOp::Close => "Close",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|op| op.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(MorphOp::Erode)
+    }
+}
+
+/// Structuring element of the Morphology node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Element {
+    Box,
+    Disk,
+    Diamond,
+}
+
+impl Element {
+    pub const ALL: [Element; 3] = [Element::Box, Element::Disk, Element::Diamond];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Element::Box => "Box",
+            Element::Disk => "Disk",
+            Element::Diamond => "Diamond",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|element| element.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(Element::Box)
+    }
+
+    /// Half width of the row `dy` pixels from the centre of an element of `radius`.
+    fn half_width(self, radius: usize, dy: usize) -> usize {
+        match self {
+            Element::Box => radius,
+            Element::Disk => ((radius * radius - dy * dy) as f64).sqrt().floor() as usize,
+            Element::Diamond => radius - dy,
+        }
+    }
+}
+
+/// Channels the Morphology node works on. The others pass through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MorphChannel {
+    Alpha,
+    Red,
+    Green,
+    Blue,
+    All,
+}
+
+impl MorphChannel {
+    pub const ALL: [MorphChannel; 5] = [
+        MorphChannel::Alpha,
+        MorphChannel::Red,
+        MorphChannel::Green,
+        MorphChannel::Blue,
+        MorphChannel::All,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            MorphChannel::Alpha => "Alpha",
+            MorphChannel::Red => "Red",
+            MorphChannel::Green => "Green",
+            MorphChannel::Blue => "Blue",
+            MorphChannel::All => "All",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|channel| channel.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(MorphChannel::Alpha)
+    }
+
+    /// Channel indices of an image with `channels` channels.
+    /// Alpha is the last channel, so single channel mattes work with any choice.
+    fn indices(self, channels: usize) -> Vec<usize> {
+        let last = channels - 1;
+        match self {
+            MorphChannel::Alpha => vec![last],
+            MorphChannel::Red => vec![0],
+            MorphChannel::Green => vec![1.min(last)],
+            MorphChannel::Blue => vec![2.min(last)],
+            MorphChannel::All => (0..channels).collect(),
+        }
+    }
+}
+
+/// Applies a morphological operation to the chosen channels of `src`.
+///
+/// Box elements cost the same for any radius. Disks and diamonds are split
+/// into rows, so they cost one box filter pass per row of the element.
+/// A fractional radius blends the results of the two nearest whole radii.
+///
+/// Pixels beyond the display window repeat its edge, so a matte covering the
+/// whole frame doesn't erode at the border.
+pub fn morphology(
+    src: &ImageBuffer,
+    op: MorphOp,
+    element: Element,
+    radius: f64,
+    channel: MorphChannel,
+) -> ImageBuffer {
+    let radius = radius.max(0.0);
+    let (low, high) = (radius.floor() as usize, radius.ceil() as usize);
+    let display_window = src.display_window();
+    let data_window = match op {
+        MorphOp::Erode | MorphOp::Open => src.data_window(),
+        MorphOp::Dilate | MorphOp::Close => {
+            src.data_window().expand(high).intersect(&display_window)
+        }
+    };
+
+    let mut image =
+        ImageBuffer::from_fn_windows(display_window, data_window, src.channels(), |x, y, out| {
+            for (c, value) in out.iter_mut().enumerate() {
+                *value = src.get(x, y, c);
+            }
+        });
+
+    for c in channel.indices(src.channels()) {
+        let plane = Plane::from_channel(&image, c);
+        let filtered = if low == high {
+            plane.apply(op, element, low)
+        } else {
+            let t = (radius - low as f64) as f32;
+            let a = plane.apply(op, element, low);
+            let b = plane.apply(op, element, high);
+            Plane {
+                data: a
+                    .data
+                    .iter()
+                    .zip(&b.data)
+                    .map(|(a, b)| a + (b - a) * t)
+                    .collect(),
+                ..a
+            }
+        };
+        filtered.write_channel(&mut image, c);
+    }
+    image
+}
+
+/// Which of the two extrema a pass keeps.
+#[derive(Clone, Copy)]
+enum Pick {
+    Min,
+    Max,
+}
+
+impl Pick {
+    fn apply(self, a: f32, b: f32) -> f32 {
+        match self {
+            Pick::Min => a.min(b),
+            Pick::Max => a.max(b),
+        }
+    }
+}
+
+/// One channel of the data window. Edges that lie on the display window are
+/// extended by repetition, the others with zeros.
+#[derive(Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    /// Whether the left, top, right and bottom edges repeat.
+    clamp: [bool; 4],
+    data: Vec<f32>,
+}
+
+impl Plane {
+    fn from_channel(image: &ImageBuffer, c: usize) -> Plane {
+        let data = image.data_window();
+        let display = image.display_window();
+        let channels = image.channels();
+        Plane {
+            width: data.width,
+            height: data.height,
+            clamp: edges_on(&data, &display),
+            data: image
+                .data()
+                .iter()
+                .skip(c)
+                .step_by(channels)
+                .copied()
+                .collect(),
+        }
+    }
+
+    fn write_channel(&self, image: &mut ImageBuffer, c: usize) {
+        let channels = image.channels();
+        for (pixel, value) in image.data_mut().chunks_exact_mut(channels).zip(&self.data) {
+            pixel[c] = *value;
+        }
+    }
+
+    fn apply(&self, op: MorphOp, element: Element, radius: usize) -> Plane {
+        match op {
+            MorphOp::Erode => self.filter(element, radius, Pick::Min),
+            MorphOp::Dilate => self.filter(element, radius, Pick::Max),
+            MorphOp::Open => {
+                self.filter(element, radius, Pick::Min)
+                    .filter(element, radius, Pick::Max)
+            }
+            MorphOp::Close => {
+                self.filter(element, radius, Pick::Max)
+                    .filter(element, radius, Pick::Min)
+            }
+        }
+    }
+
+    fn filter(&self, element: Element, radius: usize, pick: Pick) -> Plane {
+        if radius == 0 || self.data.is_empty() {
+            return self.clone();
+        }
+        match element {
+            Element::Box => self
+                .filter_rows(radius, pick)
+                .transpose()
+                .filter_rows(radius, pick)
+                .transpose(),
+            Element::Disk | Element::Diamond => self.filter_element(element, radius, pick),
+        }
+    }
+
+    /// Running extremum of `2 * radius + 1` pixels along every row.
+    fn filter_rows(&self, radius: usize, pick: Pick) -> Plane {
+        let mut scratch = Scratch::default();
+        let mut data = Vec::with_capacity(self.data.len());
+        for row in self.data.chunks_exact(self.width) {
+            data.extend_from_slice(scratch.run(row, radius, pick, self.clamp[0], self.clamp[2]));
+        }
+        Plane {
+            data,
+            ..self.clone()
+        }
+    }
+
+    /// Extremum over the element as the extremum of its rows, each a 1D window
+    /// of the row's own half width.
+    fn filter_element(&self, element: Element, radius: usize, pick: Pick) -> Plane {
+        let mut scratch = Scratch::default();
+        let zero = vec![0.0; self.width];
+        let mut data = vec![
+            match pick {
+                Pick::Min => f32::INFINITY,
+                Pick::Max => f32::NEG_INFINITY,
+            };
+            self.data.len()
+        ];
+
+        for dy in -(radius as isize)..=radius as isize {
+            let half_width = element.half_width(radius, dy.unsigned_abs());
+            for y in 0..self.height {
+                let sy = y as isize + dy;
+                let row = if sy < 0 {
+                    if self.clamp[1] {
+                        self.row(0)
+                    } else {
+                        &zero
+                    }
+                } else if sy >= self.height as isize {
+                    if self.clamp[3] {
+                        self.row(self.height - 1)
+                    } else {
+                        &zero
+                    }
+                } else {
+                    self.row(sy as usize)
+                };
+
+                let filtered = scratch.run(row, half_width, pick, self.clamp[0], self.clamp[2]);
+                let out = &mut data[y * self.width..(y + 1) * self.width];
+                for (out, value) in out.iter_mut().zip(filtered) {
+                    *out = pick.apply(*out, *value);
+                }
+            }
+        }
+
+        Plane {
+            data,
+            ..self.clone()
+        }
+    }
+
+    fn row(&self, y: usize) -> &[f32] {
+        &self.data[y * self.width..(y + 1) * self.width]
+    }
+
+    fn transpose(&self) -> Plane {
+        let mut data = Vec::with_capacity(self.data.len());
+        for x in 0..self.width {
+            data.extend((0..self.height).map(|y| self.data[y * self.width + x]));
+        }
+        let [left, top, right, bottom] = self.clamp;
+        Plane {
+            width: self.height,
+            height: self.width,
+            clamp: [top, left, bottom, right],
+            data,
+        }
+    }
+}
+
+/// Buffers of the van Herk/Gil-Werman running extremum, reused between rows.
+#[derive(Default)]
+struct Scratch {
+    padded: Vec<f32>,
+    prefix: Vec<f32>,
+    suffix: Vec<f32>,
+    out: Vec<f32>,
+}
+
+impl Scratch {
+    /// Extremum of the window of `radius` around each value of `row`, with
+    /// three comparisons per value regardless of the radius.
+    fn run(
+        &mut self,
+        row: &[f32],
+        radius: usize,
+        pick: Pick,
+        clamp_lo: bool,
+        clamp_hi: bool,
+    ) -> &[f32] {
+        let size = 2 * radius + 1;
+        let lo = if clamp_lo { row[0] } else { 0.0 };
+        let hi = if clamp_hi { row[row.len() - 1] } else { 0.0 };
+
+        self.padded.clear();
+        self.padded.resize(radius, lo);
+        self.padded.extend_from_slice(row);
+        self.padded.resize(row.len() + 2 * radius, hi);
+        let n = self.padded.len();
+
+        // Extremum from the start of each block of `size` values, and to its end
+        self.prefix.clear();
+        self.prefix.extend_from_slice(&self.padded);
+        self.suffix.clear();
+        self.suffix.extend_from_slice(&self.padded);
+        for i in 1..n {
+            if i % size != 0 {
+                self.prefix[i] = pick.apply(self.prefix[i], self.prefix[i - 1]);
+            }
+        }
+        for i in (0..n - 1).rev() {
+            if (i + 1) % size != 0 {
+                self.suffix[i] = pick.apply(self.suffix[i], self.suffix[i + 1]);
+            }
+        }
+
+        // The window [i, i + size) spans at most two blocks
+        self.out.clear();
+        self.out
+            .extend((0..row.len()).map(|i| pick.apply(self.suffix[i], self.prefix[i + size - 1])));
+        &self.out
+    }
+}
+
+/// Whether each edge of `data` lies on or beyond the matching edge of `display`,
+/// in the order left, top, right, bottom.
+fn edges_on(data: &Window, display: &Window) -> [bool; 4] {
+    [
+        data.x <= display.x,
+        data.y <= display.y,
+        data.right() >= display.right(),
+        data.bottom() >= display.bottom(),
+    ]
+}
@@ -0,0 +1,145 @@
+//! Chains of point operations run as a single pass.
+//!
+//! Point operations compute every pixel from the same pixel of their input
+//! and keep both windows of it. When several follow each other, every pixel
+//! goes through the whole chain before the next one is read, so none of the
+//! images between the nodes is stored. Each stage does the arithmetic of its
+//! node alone, so the output is identical to running the nodes one by one.
+
+use crate::image::{self, ImageBuffer};
+use crate::node::PixelProgram;
+use crate::ops::channel;
+use crate::ops::color::{self, Grade};
+use crate::tile;
+
+/// A stage of a fused chain.
+pub enum PointOp {
+    Grade(Grade),
+    Saturation(f32),
+    Premultiply,
+    Unpremultiply,
+    /// Compiled Pixel Expression node, which always outputs RGBA.
+    PixelExpr(PixelProgram),
+}
+
+/// Runs `ops` in order on every pixel of the data window of `src`.
+pub fn run(src: &ImageBuffer, ops: &[PointOp]) -> ImageBuffer {
+    let channels = ops.iter().fold(src.channels(), |channels, op| match op {
+        PointOp::PixelExpr(_) => 4,
+        _ => channels,
+    });
+    let mut image = ImageBuffer::with_windows(src.display_window(), src.data_window(), channels);
+
+    // Pixel expressions keep their arguments and stack per thread
+    let init = || {
+        ops.iter()
+            .map(|op| match op {
+                PointOp::PixelExpr(program) => program.scratch(),
+                _ => Default::default(),
+            })
+            .collect::<Vec<_>>()
+    };
+    tile::fill_with(&mut image, init, |scratch, x, y, out| {
+        let mut len = src.channels();
+        let mut pixel = [0.0; 4];
+        for (c, value) in pixel[..len].iter_mut().enumerate() {
+            *value = src.get(x, y, c);
+        }
+
+        for (op, scratch) in ops.iter().zip(scratch.iter_mut()) {
+            let current = &mut pixel[..len];
+            match op {
+                PointOp::Grade(grade) => {
+                    color::map_color_pixel(current, |rgb| rgb.map(|value| grade.apply(value)));
+                }
+                PointOp::Saturation(amount) => {
+                    color::map_color_pixel(current, |rgb| color::saturate(rgb, *amount));
+                }
+                PointOp::Premultiply => channel::premultiply_pixel(current),
+                PointOp::Unpremultiply => channel::unpremultiply_pixel(current),
+                PointOp::PixelExpr(program) => {
+                    let rgba = image::pixel_rgba(current);
+                    program.eval(scratch, x, y, rgba, &mut pixel);
+                    len = 4;
+                }
+            }
+        }
+        out.copy_from_slice(&pixel[..len]);
+    });
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+    use egui_snarl::{InPinId, NodeId, OutPinId, Snarl};
+
+    use crate::eval::{Evaluator, Value};
+    use crate::node::{DemoNode, PixelExprNode};
+    use crate::ops::{OpNode, OpType};
+
+    /// Renders `source` through Grade, Saturation and a Pixel Expression.
+    /// Unless `fused`, Grade and Saturation also feed a node each that
+    /// isn't evaluated, which keeps them out of the chain.
+    fn render(source: OpType, fused: bool) -> (usize, Vec<f32>) {
+        let mut snarl = Snarl::new();
+        let op = |snarl: &mut Snarl<DemoNode>, op_type, values: &[(&str, f64)]| {
+            let mut op = OpNode::new(op_type);
+            for &(name, value) in values {
+                op.set_float(name, value);
+            }
+            snarl.insert_node(pos2(0.0, 0.0), DemoNode::Op(op))
+        };
+        let connect = |snarl: &mut Snarl<DemoNode>, from: NodeId, to: NodeId| {
+            snarl.connect(
+                OutPinId {
+                    node: from,
+                    output: 0,
+                },
+                InPinId { node: to, input: 0 },
+            );
+        };
+
+        let source = op(&mut snarl, source, &[("Width", 37.0), ("Height", 23.0)]);
+        let grade = op(
+            &mut snarl,
+            OpType::Grade,
+            &[("Lift", 0.1), ("Gain", 1.7), ("Gamma", 0.6)],
+        );
+        let saturation = op(&mut snarl, OpType::Saturation, &[("Saturation", 1.8)]);
+        let mut pixel_expr = PixelExprNode::new();
+        pixel_expr.channels[0] = "r * 0.5 + g - u".to_owned();
+        pixel_expr.channels[3] = "a * 0.8 + b * v".to_owned();
+        pixel_expr.parse_channel(0).unwrap();
+        pixel_expr.parse_channel(3).unwrap();
+        let pixel_expr = snarl.insert_node(pos2(0.0, 0.0), DemoNode::PixelExpr(pixel_expr));
+        connect(&mut snarl, source, grade);
+        connect(&mut snarl, grade, saturation);
+        connect(&mut snarl, saturation, pixel_expr);
+        if !fused {
+            for node in [grade, saturation] {
+                let extra = op(&mut snarl, OpType::Saturation, &[]);
+                connect(&mut snarl, node, extra);
+            }
+        }
+
+        let mut evaluator = Evaluator::new();
+        let pin = OutPinId {
+            node: pixel_expr,
+            output: 0,
+        };
+        let Ok(Value::Image(image)) = evaluator.evaluate(&snarl, pin, None) else {
+            panic!("The chain didn't render an image");
+        };
+        assert_eq!(evaluator.fused().len(), usize::from(fused));
+        (image.channels(), image.data().to_vec())
+    }
+
+    #[test]
+    fn fused_chain_matches_nodes_run_one_by_one() {
+        // Noise has a single channel, Ramp has four
+        for source in [OpType::Noise, OpType::Ramp] {
+            assert_eq!(render(source, true), render(source, false), "{source:?}");
+        }
+    }
+}
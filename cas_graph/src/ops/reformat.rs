@@ -0,0 +1,103 @@
+use crate::image::{ImageBuffer, Window};
+use crate::ops::resample::Filter;
+use crate::ops::transform::{self, Affine};
+
+/// How Reformat fits the input format into the output format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReformatMode {
+    /// Scales uniformly so the whole input is visible.
+    Fit,
+    /// Scales uniformly so the output is covered, cropping the input.
+    Fill,
+    /// Scales each axis separately to match the output exactly.
+    Distort,
+    /// Keeps the pixel size and centres the input.
+    Center,
+}
+
+impl ReformatMode {
+    pub const ALL: [ReformatMode; 4] = [
+        ReformatMode::Fit,
+        ReformatMode::Fill,
+        ReformatMode::Distort,
+        ReformatMode::Center,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            ReformatMode::Fit => "Fit",
+            ReformatMode::Fill => "Fill",
+            ReformatMode::Distort => "Distort",
+            ReformatMode::Center => "Center",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|mode| mode.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(ReformatMode::Fit)
+    }
+}
+
+/// Restricts the image to `window`.
+///
+/// With `set_format` the display window becomes the crop box, moved to the
+/// origin. Otherwise only the data window is clipped.
+pub fn crop(src: &ImageBuffer, window: Window, set_format: bool) -> ImageBuffer {
+    let mut image = src.crop_data(window);
+    if set_format {
+        image.set_display_window(window);
+        image.translate(-window.x, -window.y);
+    }
+    image
+}
+
+/// Scales the display window of `src` to `width` x `height`.
+pub fn resize(src: &ImageBuffer, width: usize, height: usize, filter: Filter) -> ImageBuffer {
+    reformat(src, width, height, ReformatMode::Distort, filter)
+}
+
+/// Places `src` into a new format of `width` x `height` pixels.
+pub fn reformat(
+    src: &ImageBuffer,
+    width: usize,
+    height: usize,
+    mode: ReformatMode,
+    filter: Filter,
+) -> ImageBuffer {
+    let from = src.display_window();
+    let format = Window::from_size(width, height);
+    if from.is_empty() || format.is_empty() {
+        return ImageBuffer::with_windows(format, Window::default(), src.channels());
+    }
+
+    let fit_x = width as f64 / from.width as f64;
+    let fit_y = height as f64 / from.height as f64;
+    let (scale_x, scale_y) = match mode {
+        ReformatMode::Fit => (fit_x.min(fit_y), fit_x.min(fit_y)),
+        ReformatMode::Fill => (fit_x.max(fit_y), fit_x.max(fit_y)),
+        ReformatMode::Distort => (fit_x, fit_y),
+        ReformatMode::Center => (1.0, 1.0),
+    };
+
+    let offset_x = (width as f64 - from.width as f64 * scale_x) / 2.0;
+    let offset_y = (height as f64 - from.height as f64 * scale_y) / 2.0;
+
+    if mode == ReformatMode::Center {
+        // Whole pixel offsets don't need filtering
+        let dx = offset_x.floor() as isize - from.x;
+        let dy = offset_y.floor() as isize - from.y;
+        let mut image = src.clone();
+        image.translate(dx, dy);
+        image.set_display_window(format);
+        return image.crop_data(format);
+    }
+
+    let matrix = Affine::translate(-(from.x as f64), -(from.y as f64))
+        .then(&Affine::scale(scale_x, scale_y))
+        .then(&Affine::translate(offset_x, offset_y));
+
+    transform::resample(src, &matrix, filter, format)
+}
@@ -0,0 +1,110 @@
+use crate::image::ImageBuffer;
+use crate::ops::keyer::LUMA;
+
+/// Channel measured by the Image Stats node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsChannel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Luma,
+}
+
+impl StatsChannel {
+    pub const ALL: [StatsChannel; 5] = [
+        StatsChannel::Red,
+        StatsChannel::Green,
+        StatsChannel::Blue,
+        StatsChannel::Alpha,
+        StatsChannel::Luma,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            StatsChannel::Red => "Red",
+            StatsChannel::Green => "Green",
+            StatsChannel::Blue => "Blue",
+            StatsChannel::Alpha => "Alpha",
+            StatsChannel::Luma => "Luma",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|channel| channel.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(StatsChannel::Red)
+    }
+
+    fn value(self, [r, g, b, a]: [f32; 4]) -> f32 {
+        match self {
+            StatsChannel::Red => r,
+            StatsChannel::Green => g,
+            StatsChannel::Blue => b,
+            StatsChannel::Alpha => a,
+            StatsChannel::Luma => LUMA[0] * r + LUMA[1] * g + LUMA[2] * b,
+        }
+    }
+}
+
+/// Summary of the values of one channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ImageStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+}
+
+/// Statistics of a channel over the display window of `src`. Pixels outside
+/// the data window count as zero. An empty display window gives all zeros.
+pub fn stats(src: &ImageBuffer, channel: StatsChannel) -> ImageStats {
+    let display = src.display_window();
+    let mut values = Vec::with_capacity(display.width * display.height);
+    for y in display.y..display.bottom() {
+        values.extend((display.x..display.right()).map(|x| channel.value(src.rgba(x, y))));
+    }
+    if values.is_empty() {
+        return ImageStats::default();
+    }
+
+    let count = values.len() as f64;
+    let mean = values.iter().map(|&v| v as f64).sum::<f64>() / count;
+    let variance = values
+        .iter()
+        .map(|&v| (v as f64 - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    let (min, max) = values
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+
+    let even = values.len() % 2 == 0;
+    let mid = values.len() / 2;
+    let (below, &mut upper, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    // Even counts average the two middle values
+    let median = if even {
+        let lower = below.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        (lower as f64 + upper as f64) / 2.0
+    } else {
+        upper as f64
+    };
+
+    ImageStats {
+        min: min as f64,
+        max: max as f64,
+        mean,
+        median,
+        std_dev: variance.sqrt(),
+    }
+}
+
+/// RGBA of the pixel containing the point `(x, y)`, in absolute coordinates.
+pub fn sample(src: &ImageBuffer, x: f64, y: f64) -> [f32; 4] {
+    src.rgba(x.floor() as isize, y.floor() as isize)
+}
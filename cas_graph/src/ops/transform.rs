@@ -0,0 +1,220 @@
+use crate::image::{ImageBuffer, Window};
+use crate::ops::resample::{self, Filter};
+
+/// 2D affine transform mapping `(x, y)` to
+/// `(a * x + b * y + c, d * x + e * y + f)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 0.0,
+        e: 1.0,
+        f: 0.0,
+    };
+
+    pub const fn translate(x: f64, y: f64) -> Self {
+        Affine {
+            c: x,
+            f: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub const fn scale(x: f64, y: f64) -> Self {
+        Affine {
+            a: x,
+            e: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Counter-clockwise rotation as seen on screen, where y points down.
+    pub fn rotate(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Affine {
+            a: cos,
+            b: sin,
+            d: -sin,
+            e: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Shear with `x` offset by `skew_x * y` and `y` offset by `skew_y * x`.
+    pub const fn skew(skew_x: f64, skew_y: f64) -> Self {
+        Affine {
+            b: skew_x,
+            d: skew_y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Returns the transform that applies `self` first and then `next`.
+    pub fn then(&self, next: &Affine) -> Self {
+        Affine {
+            a: next.a * self.a + next.b * self.d,
+            b: next.a * self.b + next.b * self.e,
+            c: next.a * self.c + next.b * self.f + next.c,
+            d: next.d * self.a + next.e * self.d,
+            e: next.d * self.b + next.e * self.e,
+            f: next.d * self.c + next.e * self.f + next.f,
+        }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.a * self.e - self.b * self.d
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        let a = self.e / det;
+        let b = -self.b / det;
+        let d = -self.d / det;
+        let e = self.a / det;
+        Some(Affine {
+            a,
+            b,
+            c: -(a * self.c + b * self.f),
+            d,
+            e,
+            f: -(d * self.c + e * self.f),
+        })
+    }
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.a * x + self.b * y + self.c,
+            self.d * x + self.e * y + self.f,
+        )
+    }
+
+    /// Applies the linear part only, ignoring translation.
+    pub fn apply_vector(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.a * x + self.b * y, self.d * x + self.e * y)
+    }
+
+    /// Largest factor by which the transform stretches a distance.
+    pub fn max_scale(&self) -> f64 {
+        self.a.hypot(self.d).max(self.b.hypot(self.e))
+    }
+
+    /// Bounding window of `window` after the transform.
+    pub fn transform_window(&self, window: &Window) -> Window {
+        let (x0, y0) = (window.x as f64, window.y as f64);
+        let (x1, y1) = (window.right() as f64, window.bottom() as f64);
+        let corners = [
+            self.apply(x0, y0),
+            self.apply(x1, y0),
+            self.apply(x0, y1),
+            self.apply(x1, y1),
+        ];
+
+        let (mut min_x, mut min_y) = corners[0];
+        let (mut max_x, mut max_y) = corners[0];
+        for (x, y) in corners {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        Window::enclosing(min_x, min_y, max_x, max_y)
+    }
+}
+
+/// Parameters of the Transform node, in pixels and degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransformParams {
+    pub translate: (f64, f64),
+    pub rotate: f64,
+    pub scale: (f64, f64),
+    pub skew: (f64, f64),
+    pub pivot: (f64, f64),
+}
+
+impl TransformParams {
+    /// Scale, skew and rotation happen around the pivot, then the result is translated.
+    pub fn matrix(&self) -> Affine {
+        let (px, py) = self.pivot;
+        let (tx, ty) = self.translate;
+
+        Affine::translate(-px, -py)
+            .then(&Affine::scale(self.scale.0, self.scale.1))
+            .then(&Affine::skew(self.skew.0, self.skew.1))
+            .then(&Affine::rotate(self.rotate))
+            .then(&Affine::translate(px + tx, py + ty))
+    }
+
+    /// Moves the pivot without moving the image by compensating the translation.
+    pub fn set_pivot(&mut self, pivot: (f64, f64)) {
+        let linear = Affine {
+            c: 0.0,
+            f: 0.0,
+            ..self.matrix()
+        };
+        let (dx, dy) = (self.pivot.0 - pivot.0, self.pivot.1 - pivot.1);
+        let (ldx, ldy) = linear.apply_vector(dx, dy);
+
+        self.translate.0 += dx - ldx;
+        self.translate.1 += dy - ldy;
+        self.pivot = pivot;
+    }
+}
+
+/// Region of the source that resampling through `matrix` reads for the
+/// pixels of `region`: the region mapped through the inverse transform,
+/// plus the filter footprint.
+pub fn source_region(matrix: &Affine, filter: Filter, region: Window) -> Window {
+    let Some(inverse) = matrix.inverse() else {
+        return Window::default();
+    };
+    // The footprint of a sample grows with the minification, as in `resample`
+    let scale = inverse.a.hypot(inverse.b).max(inverse.d.hypot(inverse.e));
+    let support = (filter.support() * scale.max(1.0)).ceil() as usize + 1;
+    inverse.transform_window(&region).expand(support)
+}
+
+/// Resamples `src` through `matrix` into an image with the given display window.
+///
+/// Every output pixel is mapped back through the inverse transform and
+/// reconstructed with `filter`, so a chain of transforms concatenated into a
+/// single matrix is only filtered once. The data window covers the transformed
+/// source data plus the filter support, clipped to the display window.
+pub fn resample(
+    src: &ImageBuffer,
+    matrix: &Affine,
+    filter: Filter,
+    display_window: Window,
+) -> ImageBuffer {
+    let channels = src.channels();
+    let Some(inverse) = matrix.inverse() else {
+        return ImageBuffer::with_windows(display_window, Window::default(), channels);
+    };
+
+    let support = (filter.support() * matrix.max_scale().max(1.0)).ceil() as usize;
+    let data_window = matrix
+        .transform_window(&src.data_window())
+        .expand(support)
+        .intersect(&display_window);
+
+    let scale = (inverse.a.hypot(inverse.b), inverse.d.hypot(inverse.e));
+
+    ImageBuffer::from_fn_windows(display_window, data_window, channels, |x, y, out| {
+        let (sx, sy) = inverse.apply(x as f64 + 0.5, y as f64 + 0.5);
+        resample::sample(src, sx, sy, scale, filter, out);
+    })
+}
@@ -0,0 +1,230 @@
+//! Project files, which store the graph and its settings as RON.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use egui_snarl::{NodeId, Snarl};
+use serde::{Deserialize, Serialize};
+
+use crate::disk_cache::DiskCache;
+use crate::node::DemoNode;
+use crate::ops::OpType;
+use crate::sequence::{FrameRange, Playback};
+
+/// Extension of project files.
+pub const EXTENSION: &str = "csc";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Project {
+    pub snarl: Snarl<DemoNode>,
+    /// Whether slow node outputs are kept on disk next to the project file.
+    #[serde(default)]
+    pub disk_cache: bool,
+    /// Frames of image sequences that are rendered.
+    #[serde(default)]
+    pub frame_range: FrameRange,
+    /// Frame shown in the viewer.
+    #[serde(default)]
+    pub frame: i64,
+    #[serde(default)]
+    pub playback: Playback,
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(io::Error),
+    Format(String),
+    /// No node has the name given.
+    UnknownNode(String),
+    /// A parameter given doesn't exist or can't take the value given.
+    Parameter(String),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectError::Io(err) => write!(f, "{err}"),
+            ProjectError::Format(message) => write!(f, "Project format error: {message}"),
+            ProjectError::UnknownNode(name) => write!(f, "No node is named {name}"),
+            ProjectError::Parameter(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl From<io::Error> for ProjectError {
+    fn from(err: io::Error) -> Self {
+        ProjectError::Io(err)
+    }
+}
+
+impl Project {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProjectError> {
+        let text = std::fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|err| ProjectError::Format(err.to_string()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProjectError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| ProjectError::Format(err.to_string()))?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Names the nodes are referred to by outside the editor, see `node_names`.
+    pub fn node_names(&self) -> Vec<(NodeId, String)> {
+        node_names(&self.snarl)
+    }
+
+    /// Name of a node from `node_names`.
+    pub fn node_name(&self, node: NodeId) -> String {
+        self.node_names()
+            .into_iter()
+            .find(|&(named, _)| named == node)
+            .map_or_else(String::new, |(_, name)| name)
+    }
+
+    /// Node with a name from `node_names`, ignoring case.
+    pub fn find_node(&self, name: &str) -> Result<NodeId, ProjectError> {
+        self.node_names()
+            .into_iter()
+            .find(|(_, node_name)| node_name.eq_ignore_ascii_case(name))
+            .map(|(node, _)| node)
+            .ok_or_else(|| ProjectError::UnknownNode(name.to_owned()))
+    }
+
+    /// Write nodes in order of their ids.
+    pub fn write_nodes(&self) -> Vec<NodeId> {
+        self.op_nodes(OpType::Write)
+    }
+
+    /// Image nodes of type `op_type` in order of their ids.
+    pub fn op_nodes(&self, op_type: OpType) -> Vec<NodeId> {
+        self.snarl
+            .node_ids()
+            .filter(|(_, node)| matches!(node, DemoNode::Op(op) if op.op_type == op_type))
+            .map(|(node, _)| node)
+            .collect()
+    }
+
+    /// Applies an assignment like `Grade1.Gain=1.5` to the graph.
+    ///
+    /// Parameter names ignore case and spaces and are those `inspect` shows,
+    /// like `Number1.Value`. Without a node name, the parameter has to belong
+    /// to a single node, and the name of a Number or String node sets its
+    /// value. Animated parameters lose their curve.
+    pub fn set_parameter(&mut self, assignment: &str) -> Result<(), ProjectError> {
+        let (target, value) = assignment.split_once('=').ok_or_else(|| {
+            ProjectError::Parameter(format!("`{assignment}` isn't of the form name=value"))
+        })?;
+        let (node, parameter) = match target.split_once('.') {
+            Some((name, parameter)) => (self.find_node(name)?, Some(parameter)),
+            None => match self.find_node(target) {
+                Ok(node) => (node, None),
+                Err(_) => (self.node_with_parameter(target)?, Some(target)),
+            },
+        };
+
+        let invalid = || ProjectError::Parameter(format!("invalid value `{value}` for {target}"));
+        let missing = |parameter: &str| {
+            let name = target.split_once('.').map_or(target, |(name, _)| name);
+            ProjectError::Parameter(format!("{name} has no parameter {parameter}"))
+        };
+        let named = |name| parameter.is_none_or(|parameter| same_name(parameter, name));
+        match (&mut self.snarl[node], parameter) {
+            (DemoNode::Number(number, curve), _) if named("Value") => {
+                *number = value.trim().parse().map_err(|_| invalid())?;
+                *curve = None;
+            }
+            (DemoNode::String(text), _) if named("Value") => *text = value.to_owned(),
+            (DemoNode::ShowImage(uri), Some(_)) if named("URL") => *uri = value.to_owned(),
+            (DemoNode::Op(op), Some(parameter)) => {
+                let property = op
+                    .properties
+                    .iter_mut()
+                    .find(|property| same_name(property.name(), parameter))
+                    .ok_or_else(|| missing(parameter))?;
+                if !property.parse_value(value) {
+                    return Err(invalid());
+                }
+            }
+            (
+                DemoNode::Number(..) | DemoNode::String(_) | DemoNode::ShowImage(_),
+                Some(parameter),
+            ) => return Err(missing(parameter)),
+            _ => {
+                return Err(ProjectError::Parameter(format!(
+                    "{target} can't be set directly"
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// The only node with a parameter named `parameter`.
+    fn node_with_parameter(&self, parameter: &str) -> Result<NodeId, ProjectError> {
+        let mut nodes = self.snarl.node_ids().filter(|(_, node)| match node {
+            DemoNode::Number(..) | DemoNode::String(_) => same_name(parameter, "Value"),
+            DemoNode::ShowImage(_) => same_name(parameter, "URL"),
+            DemoNode::Op(op) => op
+                .properties
+                .iter()
+                .any(|property| same_name(property.name(), parameter)),
+            _ => false,
+        });
+        match (nodes.next(), nodes.next()) {
+            (Some((node, _)), None) => Ok(node),
+            (None, _) => Err(ProjectError::Parameter(format!(
+                "no node has a parameter {parameter}"
+            ))),
+            (Some((first, _)), Some((second, _))) => {
+                let (first, second) = (self.node_name(first), self.node_name(second));
+                Err(ProjectError::Parameter(format!(
+                    "{parameter} is a parameter of several nodes, like {first} and {second}; name the node as in {first}.{parameter}"
+                )))
+            }
+        }
+    }
+
+    /// Directory of the disk cache of the project at `path`, next to the
+    /// project file: `shot.csc` keeps its cache in `shot.cache`.
+    pub fn cache_dir(path: impl AsRef<Path>) -> PathBuf {
+        path.as_ref().with_extension("cache")
+    }
+
+    /// Disk cache of the project at `path`, if it is enabled.
+    pub fn disk_cache(&self, path: impl AsRef<Path>) -> Option<DiskCache> {
+        self.disk_cache
+            .then(|| DiskCache::new(Self::cache_dir(path), DiskCache::DEFAULT_LIMIT))
+    }
+}
+
+/// Names the nodes of `snarl` are referred to by outside the editor: the
+/// type of the node without spaces followed by a count of the nodes of that
+/// type in order of their ids, like `Read1` or `UnsharpMask2`.
+pub fn node_names(snarl: &Snarl<DemoNode>) -> Vec<(NodeId, String)> {
+    let mut counts = HashMap::<String, usize>::new();
+    snarl
+        .node_ids()
+        .map(|(node, value)| {
+            let name = value.name().replace(' ', "");
+            let count = counts.entry(name.clone()).or_default();
+            *count += 1;
+            (node, format!("{name}{count}"))
+        })
+        .collect()
+}
+
+/// Whether two parameter names are the same, ignoring case and spaces.
+fn same_name(name: &str, other: &str) -> bool {
+    let normalize = |name: &str| {
+        name.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    normalize(name) == normalize(other)
+}
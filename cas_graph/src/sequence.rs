@@ -0,0 +1,366 @@
+//! Image sequences: file paths with a frame number token.
+//!
+//! A path names a sequence when it contains one of these tokens, which is
+//! replaced by the frame number:
+//!
+//! - `####`: as many digits as there are `#`, padded with zeros.
+//! - `%04d` or `%d`: printf style, padded to the given width.
+//! - `$F4` or `$F`: padded to the given width, or not padded. `$F` followed
+//!   by a letter, as in `$Final`, isn't a token.
+//!
+//! Tokens are only looked for in the file name, not in its folders, and
+//! only the first token of a file name is replaced. Negative frames keep
+//! their sign in front of the padded digits.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Inclusive range of frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FrameRange {
+    pub first: i64,
+    pub last: i64,
+}
+
+impl FrameRange {
+    pub const fn new(first: i64, last: i64) -> Self {
+        FrameRange { first, last }
+    }
+
+    /// Number of frames, zero if `last` is before `first`.
+    pub const fn len(&self) -> u64 {
+        if self.last < self.first {
+            0
+        } else {
+            self.last.abs_diff(self.first) + 1
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.last < self.first
+    }
+
+    pub const fn contains(&self, frame: i64) -> bool {
+        frame >= self.first && frame <= self.last
+    }
+
+    pub fn clamp(&self, frame: i64) -> i64 {
+        frame.clamp(self.first, self.last.max(self.first))
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = i64> {
+        self.first..=self.last
+    }
+}
+
+/// Formats as `first-last`, the form it is parsed from.
+impl fmt::Display for FrameRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.first, self.last)
+    }
+}
+
+/// Parses `first-last` or a single frame. Frames may be negative, as in `-5--1`.
+impl FromStr for FrameRange {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid frame range `{text}`, expected `first-last`");
+        // A minus sign in front of the first frame isn't the separator
+        let separator = text
+            .char_indices()
+            .skip(1)
+            .find(|&(_, c)| c == '-')
+            .map(|(idx, _)| idx);
+        let (first, last) = match separator {
+            Some(idx) => (&text[..idx], &text[idx + 1..]),
+            None => (text, text),
+        };
+        let first = first.trim().parse().map_err(|_| invalid())?;
+        let last = last.trim().parse().map_err(|_| invalid())?;
+        if last < first {
+            return Err(invalid());
+        }
+        Ok(FrameRange::new(first, last))
+    }
+}
+
+impl Default for FrameRange {
+    fn default() -> Self {
+        FrameRange::new(1, 100)
+    }
+}
+
+/// What playback does at the out point.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoopMode {
+    /// Continues at the in point.
+    #[default]
+    Loop,
+    /// Plays backwards to the in point, then forwards again.
+    PingPong,
+    /// Stops.
+    Once,
+}
+
+impl LoopMode {
+    pub const ALL: [LoopMode; 3] = [LoopMode::Loop, LoopMode::PingPong, LoopMode::Once];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            LoopMode::Loop => "Loop",
+            LoopMode::PingPong => "Ping-Pong",
+            LoopMode::Once => "Once",
+        }
+    }
+}
+
+/// Playback settings of a project.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Playback {
+    /// First frame that is played, the first of the frame range if unset.
+    pub in_point: Option<i64>,
+    /// Last frame that is played, the last of the frame range if unset.
+    pub out_point: Option<i64>,
+    pub loop_mode: LoopMode,
+    /// Frames per second playback aims for.
+    pub fps: f64,
+}
+
+impl Playback {
+    /// Frames that are played, the in and out points clamped to `frames`.
+    pub fn range(&self, frames: FrameRange) -> FrameRange {
+        let first = frames.clamp(self.in_point.unwrap_or(frames.first));
+        let last = frames.clamp(self.out_point.unwrap_or(frames.last));
+        FrameRange::new(first, last.max(first))
+    }
+
+    /// Frame shown `steps` frames after playback started at `start` within
+    /// `range`, and whether playback stopped there. Playback starting
+    /// outside the range, or at its end when it doesn't loop, starts at the
+    /// in point.
+    pub fn frame_after(&self, start: i64, steps: u64, range: FrameRange) -> (i64, bool) {
+        let len = range.len().max(1);
+        let start = match self.loop_mode {
+            LoopMode::Once if start == range.last => range.first,
+            _ if !range.contains(start) => range.first,
+            _ => start,
+        };
+        let offset = start.abs_diff(range.first) + steps;
+        match self.loop_mode {
+            LoopMode::Loop => (range.first + (offset % len) as i64, false),
+            LoopMode::Once => {
+                let offset = offset.min(len - 1);
+                (range.first + offset as i64, offset == len - 1)
+            }
+            LoopMode::PingPong if len == 1 => (range.first, false),
+            LoopMode::PingPong => {
+                let offset = offset % (2 * (len - 1));
+                if offset < len {
+                    (range.first + offset as i64, false)
+                } else {
+                    (range.last - (offset - (len - 1)) as i64, false)
+                }
+            }
+        }
+    }
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            in_point: None,
+            out_point: None,
+            loop_mode: LoopMode::Loop,
+            fps: 24.0,
+        }
+    }
+}
+
+/// What a Read node does when the file of a frame doesn't exist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingFrames {
+    /// Reads the nearest earlier frame, or the first one after it.
+    Hold,
+    /// Outputs transparent black in the format of the nearest frame.
+    Black,
+    /// Fails the evaluation.
+    Error,
+}
+
+impl MissingFrames {
+    pub const ALL: [MissingFrames; 3] = [
+        MissingFrames::Hold,
+        MissingFrames::Black,
+        MissingFrames::Error,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            MissingFrames::Hold => "Hold",
+            MissingFrames::Black => "Black",
+            MissingFrames::Error => "Error",
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|missing| missing.name()).collect()
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(MissingFrames::Hold)
+    }
+}
+
+/// File a Read node reads for a frame.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameFile {
+    /// The image in the file.
+    Image(String),
+    /// Transparent black in the format of the image in the file.
+    Black(String),
+}
+
+impl FrameFile {
+    pub fn path(&self) -> &str {
+        match self {
+            FrameFile::Image(path) | FrameFile::Black(path) => path,
+        }
+    }
+}
+
+/// Whether `path` contains a frame token.
+pub fn is_sequence(path: &str) -> bool {
+    split(path).is_some()
+}
+
+/// Path of `frame` in the sequence `pattern`. Paths without a token are
+/// returned as they are.
+pub fn frame_path(pattern: &str, frame: i64) -> String {
+    match split(pattern) {
+        Some((prefix, padding, suffix)) => {
+            format!("{prefix}{}{suffix}", format_frame(frame, padding))
+        }
+        None => pattern.to_owned(),
+    }
+}
+
+/// Frames of the sequence `pattern` that exist on disk, in order.
+/// Paths without a token give no frames.
+pub fn frames_on_disk(pattern: &str) -> Vec<i64> {
+    let Some((prefix, padding, suffix)) = split(pattern) else {
+        return Vec::new();
+    };
+    // The token may only appear in the file name, not in a directory
+    let (dir, name_prefix) = match prefix.rfind(['/', '\\']) {
+        Some(idx) => (&prefix[..=idx], &prefix[idx + 1..]),
+        None => ("", prefix),
+    };
+    if suffix.contains(['/', '\\']) {
+        return Vec::new();
+    }
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    let mut frames = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let number = name.strip_prefix(name_prefix)?.strip_suffix(suffix)?;
+            let digits = number.strip_prefix('-').unwrap_or(number);
+            // Unpadded tokens match any number of digits, padded ones at least the padding
+            if digits.is_empty()
+                || !digits.bytes().all(|byte| byte.is_ascii_digit())
+                || digits.len() < padding
+                || (digits.len() > padding.max(1) && digits.starts_with('0'))
+            {
+                return None;
+            }
+            number.parse().ok()
+        })
+        .collect::<Vec<i64>>();
+    frames.sort_unstable();
+    frames.dedup();
+    frames
+}
+
+/// First and last frame of the sequence `pattern` on disk, if any exist.
+pub fn detect_range(pattern: &str) -> Option<FrameRange> {
+    let frames = frames_on_disk(pattern);
+    Some(FrameRange::new(*frames.first()?, *frames.last()?))
+}
+
+/// File to read for `frame` of `pattern`, following `missing` when the
+/// frame doesn't exist. Paths without a token always name their file.
+pub fn frame_file(pattern: &str, frame: i64, missing: MissingFrames) -> Result<FrameFile, String> {
+    let path = frame_path(pattern, frame);
+    if !is_sequence(pattern) || Path::new(&path).exists() {
+        return Ok(FrameFile::Image(path));
+    }
+    if missing == MissingFrames::Error {
+        return Err(format!("{path}: frame {frame} is missing"));
+    }
+
+    let frames = frames_on_disk(pattern);
+    let nearest = frames
+        .iter()
+        .rev()
+        .find(|&&other| other < frame)
+        .or(frames.first())
+        .ok_or_else(|| format!("{pattern}: no frames on disk"))?;
+    let nearest = frame_path(pattern, *nearest);
+    Ok(match missing {
+        MissingFrames::Black => FrameFile::Black(nearest),
+        _ => FrameFile::Image(nearest),
+    })
+}
+
+/// Splits a path at the first frame token of its file name into the text
+/// before it, the padding of the frame number and the text after it.
+fn split(path: &str) -> Option<(&str, usize, &str)> {
+    let bytes = path.as_bytes();
+    let name = path.rfind(['/', '\\']).map_or(0, |idx| idx + 1);
+    for (idx, &byte) in bytes.iter().enumerate().skip(name) {
+        match byte {
+            b'#' => {
+                let len = bytes[idx..].iter().take_while(|&&b| b == b'#').count();
+                return Some((&path[..idx], len, &path[idx + len..]));
+            }
+            b'%' => {
+                // %d or %0Nd
+                let rest = &path[idx + 1..];
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                if rest[digits..].starts_with('d') {
+                    let padding = rest[..digits].parse().unwrap_or(0);
+                    return Some((&path[..idx], padding, &rest[digits + 1..]));
+                }
+            }
+            b'$' if path[idx + 1..].starts_with('F') => {
+                let rest = &path[idx + 2..];
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                // Part of a word like `$Final` rather than a token
+                let word = |byte: &u8| byte.is_ascii_alphabetic() || *byte == b'_';
+                if digits == 0 && rest.as_bytes().first().is_some_and(word) {
+                    continue;
+                }
+                let padding = rest[..digits].parse().unwrap_or(0);
+                return Some((&path[..idx], padding, &rest[digits..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn format_frame(frame: i64, padding: usize) -> String {
+    if frame < 0 {
+        format!("-{:0padding$}", frame.unsigned_abs())
+    } else {
+        format!("{frame:0padding$}")
+    }
+}
@@ -0,0 +1,129 @@
+//! Standard input and output as image files, for running graphs in shell
+//! pipelines.
+//!
+//! A Read node with the file `-` reads the image on standard input and a
+//! Write node with the file `-` writes to standard output. Standard input is
+//! read the first time a node needs it and kept, so it can be evaluated any
+//! number of times. Its format is sniffed from the first bytes unless set.
+//! Standard output takes the format set for it, or else the format of
+//! standard input, or else PNG.
+//!
+//! Only the command line renders through standard input and output, and turns
+//! them on with [`enable`]. Elsewhere `-` is an ordinary file name, so a
+//! graph shown in the app never waits on a standard input that isn't piped.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use image::ImageFormat;
+
+use crate::image::ImageBuffer;
+
+/// File of Read and Write nodes standing for standard input and output.
+pub const PATH: &str = "-";
+
+static INPUT_FORMAT: OnceLock<ImageFormat> = OnceLock::new();
+static OUTPUT_FORMAT: OnceLock<ImageFormat> = OnceLock::new();
+static INPUT: OnceLock<Result<Input, String>> = OnceLock::new();
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+struct Input {
+    image: Arc<ImageBuffer>,
+    format: ImageFormat,
+    /// Hash of the bytes read.
+    hash: u64,
+}
+
+/// Makes the file `-` stand for standard input and output from now on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether a file of a Read or Write node stands for standard input or
+/// output, which is never the case before [`enable`].
+pub fn is_stdio(path: &str) -> bool {
+    path == PATH && ENABLED.load(Ordering::Relaxed)
+}
+
+/// Format named by its usual extension, like `png`, `jpg` or `exr`.
+pub fn parse_format(name: &str) -> Option<ImageFormat> {
+    ImageFormat::from_extension(name.trim().to_ascii_lowercase())
+}
+
+/// Decodes standard input as `format` rather than sniffing it. Only has an
+/// effect before standard input is read, and only once.
+pub fn set_input_format(format: ImageFormat) {
+    let _ = INPUT_FORMAT.set(format);
+}
+
+/// Encodes standard output as `format`. Only the first call has an effect.
+pub fn set_output_format(format: ImageFormat) {
+    let _ = OUTPUT_FORMAT.set(format);
+}
+
+/// Image on standard input.
+pub fn input() -> Result<Arc<ImageBuffer>, String> {
+    match INPUT.get_or_init(read_input) {
+        Ok(input) => Ok(input.image.clone()),
+        Err(message) => Err(message.clone()),
+    }
+}
+
+/// Hash of the bytes on standard input, `None` if it can't be read.
+pub fn input_hash() -> Option<u64> {
+    INPUT
+        .get_or_init(read_input)
+        .as_ref()
+        .ok()
+        .map(|input| input.hash)
+}
+
+/// Writes `image` to standard output.
+pub fn write_output(image: &ImageBuffer) -> Result<(), String> {
+    let input_format = || match INPUT.get() {
+        Some(Ok(input)) => Some(input.format),
+        _ => None,
+    };
+    let format = OUTPUT_FORMAT
+        .get()
+        .copied()
+        .or_else(input_format)
+        .unwrap_or(ImageFormat::Png);
+    let bytes = image
+        .encode(format)
+        .map_err(|err| format!("standard output: {err}"))?;
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(&bytes)
+        .and_then(|()| stdout.flush())
+        .map_err(|err| format!("standard output: {err}"))
+}
+
+fn read_input() -> Result<Input, String> {
+    let mut stdin = io::stdin();
+    // Reading a terminal would wait for input that never comes
+    if stdin.is_terminal() {
+        return Err("standard input is a terminal rather than an image".to_owned());
+    }
+    let mut bytes = Vec::new();
+    stdin
+        .read_to_end(&mut bytes)
+        .map_err(|err| format!("standard input: {err}"))?;
+    let format = match INPUT_FORMAT.get() {
+        Some(&format) => format,
+        None => image::guess_format(&bytes)
+            .map_err(|_| "standard input: the image format isn't recognised".to_owned())?,
+    };
+    let image =
+        ImageBuffer::decode(&bytes, format).map_err(|err| format!("standard input: {err}"))?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    Ok(Input {
+        image: Arc::new(image),
+        format,
+        hash: hasher.finish(),
+    })
+}
@@ -0,0 +1,66 @@
+//! Cancellation and progress of a background evaluation.
+//!
+//! A task is made current on the thread that evaluates the graph. The
+//! evaluator records which node it is working on, and tiled kernels report
+//! how many of their tiles are done and stop early once the task is cancelled.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use egui_snarl::NodeId;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Task>>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Default)]
+pub struct Task {
+    cancelled: AtomicBool,
+    node: Mutex<Option<NodeId>>,
+    /// Fraction of the current node that is done, as `f32` bits.
+    progress: AtomicU32,
+}
+
+impl Task {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Asks the evaluation to stop. Its result will be discarded.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Node being evaluated and the fraction of it that is done.
+    pub fn progress(&self) -> Option<(NodeId, f32)> {
+        let node = (*self.node.lock().unwrap())?;
+        Some((node, f32::from_bits(self.progress.load(Ordering::Relaxed))))
+    }
+
+    pub fn start_node(&self, node: NodeId) {
+        *self.node.lock().unwrap() = Some(node);
+        self.set_fraction(0.0);
+    }
+
+    pub fn set_fraction(&self, fraction: f32) {
+        self.progress.store(fraction.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Runs `f` with this task as the current task of the calling thread.
+    pub fn run<R>(self: &Arc<Self>, f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let result = f();
+        CURRENT.with(|current| current.replace(previous));
+        result
+    }
+}
+
+/// Task of the calling thread, if it is evaluating in the background.
+pub fn current() -> Option<Arc<Task>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
@@ -0,0 +1,116 @@
+//! Tiled execution of per-pixel kernels.
+//!
+//! The data window of an output image is cut into square tiles, which are
+//! spread over a pool of worker threads, one per core. The pool is started
+//! once and kept, so evaluating a graph doesn't start threads for every
+//! node, and kernels called from the workers share it. Each tile is
+//! rendered into its own buffer and the buffers are composed into the image
+//! once every tile is done, so callers only ever see finished images.
+//! Finished tiles are reported to the current task, and a cancelled task
+//! leaves the remaining tiles black.
+//!
+//! Kernels read their inputs through `ImageBuffer::get` and friends, and
+//! inputs are complete before a kernel runs. A kernel that needs neighbouring
+//! pixels, like a blur or a convolution, reads the halo around its tile
+//! straight from the input. Tiles therefore need no overlap and there are
+//! no seams between them.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::image::{ImageBuffer, Window};
+use crate::task;
+
+/// Width and height of a tile in pixels.
+pub const TILE_SIZE: usize = 128;
+
+/// Images with fewer pixels are rendered on the calling thread,
+/// where starting workers would cost more than it saves.
+const MIN_PARALLEL_PIXELS: usize = 2 * TILE_SIZE * TILE_SIZE;
+
+/// Tiles covering `window` row by row. Tiles on the right and bottom edges
+/// are cut to the window.
+pub fn tiles(window: Window) -> Vec<Window> {
+    let mut tiles = Vec::new();
+    for y in (0..window.height).step_by(TILE_SIZE) {
+        for x in (0..window.width).step_by(TILE_SIZE) {
+            tiles.push(Window::new(
+                window.x + x as isize,
+                window.y + y as isize,
+                TILE_SIZE.min(window.width - x),
+                TILE_SIZE.min(window.height - y),
+            ));
+        }
+    }
+    tiles
+}
+
+/// Calls `f` with the absolute coordinates and channels of every pixel of
+/// the data window of `image`, spreading tiles over the worker threads.
+pub fn fill(image: &mut ImageBuffer, f: impl Fn(isize, isize, &mut [f32]) + Sync) {
+    fill_with(image, || (), |_, x, y, out| f(x, y, out));
+}
+
+/// Like `fill`, but every tile first creates scratch state with `init`
+/// and passes it to each call of `f`, so kernels can reuse buffers.
+pub fn fill_with<S>(
+    image: &mut ImageBuffer,
+    init: impl Fn() -> S + Sync,
+    f: impl Fn(&mut S, isize, isize, &mut [f32]) + Sync,
+) {
+    let channels = image.channels();
+    fill_tiles(image, |tile, data| {
+        let mut scratch = init();
+        for (idx, pixel) in data.chunks_exact_mut(channels).enumerate() {
+            let x = tile.x + (idx % tile.width) as isize;
+            let y = tile.y + (idx / tile.width) as isize;
+            f(&mut scratch, x, y, pixel);
+        }
+    });
+}
+
+/// Calls `f` once per tile of the data window of `image` with the tile and
+/// its pixels, stored row by row. For kernels that share work between the
+/// pixels of a tile, like running sums over the tile and its halo.
+pub fn fill_tiles(image: &mut ImageBuffer, f: impl Fn(Window, &mut [f32]) + Sync) {
+    let window = image.data_window();
+    let channels = image.channels();
+    if window.width * window.height < MIN_PARALLEL_PIXELS {
+        f(window, image.data_mut());
+        return;
+    }
+
+    // The task is current on the calling thread only
+    let task = task::current();
+    let tiles = tiles(window);
+    let finished = AtomicUsize::new(0);
+    // Tiles taken once the task is cancelled are skipped
+    let rendered = tiles
+        .par_iter()
+        .filter_map(|&tile| {
+            if task.as_ref().is_some_and(|task| task.is_cancelled()) {
+                return None;
+            }
+            let mut data = vec![0.0; tile.width * tile.height * channels];
+            f(tile, &mut data);
+
+            let count = finished.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(task) = &task {
+                task.set_fraction(count as f32 / tiles.len() as f32);
+            }
+            Some((tile, data))
+        })
+        .collect::<Vec<_>>();
+
+    let stride = window.width * channels;
+    let data = image.data_mut();
+    for (tile, pixels) in rendered {
+        let row_len = tile.width * channels;
+        let x = (tile.x - window.x) as usize * channels;
+        for (row, src) in pixels.chunks_exact(row_len).enumerate() {
+            let start = (tile.y - window.y) as usize * stride + row * stride + x;
+            data[start..start + row_len].copy_from_slice(src);
+        }
+    }
+}
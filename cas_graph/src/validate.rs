@@ -0,0 +1,175 @@
+//! Checks of a project that find problems without evaluating the graph:
+//! wires between pins of different types, cycles, files that don't exist
+//! and expressions that don't parse.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use egui_snarl::{InPinId, NodeId, OutPinId};
+
+use crate::node::{DemoNode, Expr, PixelExprNode};
+use crate::node_graph::{pin_in_compat, pin_out_compat};
+use crate::ops::{OpNode, OpType};
+use crate::project::Project;
+use crate::sequence::{self, MissingFrames};
+use crate::stdio;
+
+/// Problem found in a node.
+pub struct Issue {
+    pub node: NodeId,
+    pub message: String,
+}
+
+/// Every problem found in the project, ordered by node. Files of sequences
+/// are checked for every frame of the project frame range.
+pub fn validate(project: &Project) -> Vec<Issue> {
+    let name = |node| project.node_name(node);
+    let snarl = &project.snarl;
+    let mut issues = Vec::new();
+
+    let mut upstream = HashMap::<NodeId, Vec<OutPinId>>::new();
+    for (out_pin, in_pin) in snarl.wires() {
+        upstream.entry(in_pin.node).or_default().push(out_pin);
+        let (from, to) = (&snarl[out_pin.node], &snarl[in_pin.node]);
+        let source = format!(
+            "{}.{}",
+            name(out_pin.node),
+            from.output_name(out_pin.output)
+        );
+        let message = if out_pin.output >= from.outputs() {
+            format!(
+                "input {} is connected to a missing output",
+                to.input_name(in_pin.input)
+            )
+        } else if in_pin.input >= to.inputs() {
+            format!("{source} is connected to a missing input")
+        } else if pin_out_compat(from, out_pin.output) & pin_in_compat(to, in_pin.input) == 0 {
+            format!(
+                "input {} can't take the value of {source}",
+                to.input_name(in_pin.input)
+            )
+        } else {
+            continue;
+        };
+        issues.push(Issue {
+            node: in_pin.node,
+            message,
+        });
+    }
+
+    for (node, value) in snarl.node_ids() {
+        if in_cycle(&upstream, node) {
+            issues.push(Issue {
+                node,
+                message: "is part of a cycle".to_owned(),
+            });
+        }
+        let connected = |input| !snarl.in_pin(InPinId { node, input }).remotes.is_empty();
+        let messages = match value {
+            DemoNode::ExprNode(expr_node) if !connected(0) => {
+                unparsed(&expr_node.text, "expression")
+                    .into_iter()
+                    .collect()
+            }
+            DemoNode::PixelExpr(pixel_expr) => (0..4)
+                .filter_map(|channel| {
+                    let pin = PixelExprNode::FIRST_CHANNEL + channel;
+                    let what = format!("{} expression", value.input_name(pin));
+                    unparsed(&pixel_expr.channels[channel], &what)
+                })
+                .collect(),
+            DemoNode::Op(op) => {
+                // Paths arriving on a wire are only known when evaluating
+                let file = op
+                    .properties
+                    .iter()
+                    .position(|property| property.name() == "File")
+                    .map(|idx| op.op_type.inputs().len() + idx);
+                match op.op_type {
+                    _ if file.is_some_and(connected) => None,
+                    OpType::Read => missing_input(op, project),
+                    OpType::Write => missing_output(op),
+                    _ => None,
+                }
+                .into_iter()
+                .collect()
+            }
+            _ => Vec::new(),
+        };
+        issues.extend(messages.into_iter().map(|message| Issue { node, message }));
+    }
+
+    issues.sort_by_key(|issue| issue.node);
+    issues
+}
+
+/// Whether `node` is upstream of itself.
+fn in_cycle(upstream: &HashMap<NodeId, Vec<OutPinId>>, node: NodeId) -> bool {
+    let mut visited = Vec::new();
+    let mut pending = vec![node];
+    while let Some(next) = pending.pop() {
+        for remote in upstream.get(&next).into_iter().flatten() {
+            if remote.node == node {
+                return true;
+            }
+            if !visited.contains(&remote.node) {
+                visited.push(remote.node);
+                pending.push(remote.node);
+            }
+        }
+    }
+    false
+}
+
+/// Problem with an expression that doesn't parse, if any.
+fn unparsed(text: &str, what: &str) -> Option<String> {
+    let err = syn::parse_str::<Expr>(text).err()?;
+    Some(format!("{what} `{text}` doesn't parse: {err}"))
+}
+
+/// Problem with the file of a Read node, if any.
+fn missing_input(op: &OpNode, project: &Project) -> Option<String> {
+    let pattern = op.path("File");
+    if pattern.is_empty() {
+        return Some("has no file to read".to_owned());
+    }
+    // Standard input once rendered in a pipeline, see `stdio::enable`
+    if pattern == stdio::PATH {
+        return None;
+    }
+    if !sequence::is_sequence(pattern) {
+        return (!Path::new(pattern).exists()).then(|| format!("{pattern} doesn't exist"));
+    }
+
+    let missing = MissingFrames::from_index(op.choice("Missing Frames"));
+    let frames = project.frame_range;
+    let absent = frames
+        .frames()
+        .filter(|&frame| {
+            sequence::frame_file(pattern, frame, missing)
+                .map_or(true, |file| !Path::new(file.path()).exists())
+        })
+        .collect::<Vec<_>>();
+    match absent.as_slice() {
+        [] => None,
+        [frame] => Some(format!("frame {frame} of {pattern} is missing")),
+        _ if absent.len() as u64 == frames.len() => {
+            Some(format!("none of frames {frames} of {pattern} exist"))
+        }
+        [first, ..] => Some(format!(
+            "{} frames of {pattern} are missing, the first is frame {first}",
+            absent.len()
+        )),
+    }
+}
+
+/// Problem with the file of a Write node, if any.
+fn missing_output(op: &OpNode) -> Option<String> {
+    let path = op.path("File");
+    if path.is_empty() {
+        return Some("has no file to write".to_owned());
+    }
+    let dir = Path::new(path).parent()?;
+    (!dir.as_os_str().is_empty() && !dir.is_dir())
+        .then(|| format!("folder {} of {path} doesn't exist", dir.display()))
+}
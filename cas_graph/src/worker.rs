@@ -0,0 +1,393 @@
+//! Evaluation of the graph on a background thread.
+//!
+//! The UI submits a snapshot of the graph every frame. A snapshot that
+//! differs from the one being rendered cancels it and starts a new job, and
+//! the result of the last finished job stays available until the next one
+//! is done. Jobs may be limited to a region of the output, like the part
+//! of the image a viewer shows, and a job for a region inside the one of
+//! the last job isn't needed. Renders of Write nodes run on threads of their own, so they are
+//! never cancelled by edits to the graph, and so do the evaluations that freeze nodes.
+//!
+//! Finished results are also kept per frame, as long as the graph stays the
+//! same, so frames that were shown before are shown again without a job.
+
+use std::collections::HashSet;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use egui_snarl::{InPinId, NodeId, OutPinId, Snarl};
+
+use crate::disk_cache::DiskCache;
+use crate::eval::{self, EvalError, Evaluator, Proxy, Value};
+use crate::frame_cache::FrameCache;
+use crate::image::Window;
+use crate::node::DemoNode;
+use crate::sequence::FrameRange;
+use crate::task::Task;
+
+struct Job {
+    snarl: Snarl<DemoNode>,
+    pin: OutPinId,
+    region: Option<Window>,
+    frame: i64,
+    /// Frame cache generation the job was submitted in.
+    generation: u64,
+    task: Arc<Task>,
+}
+
+enum Message {
+    Evaluate(Job),
+    SetDiskCache(Option<DiskCache>),
+    SetProxy(Proxy),
+}
+
+/// Outcome of rendering a Write node: the path written or the error.
+pub type RenderResult = (NodeId, Result<String, EvalError>);
+
+/// Outcome of freezing a node: the content hash to freeze it with or the error.
+pub type FreezeResult = (NodeId, Result<u64, EvalError>);
+
+struct Finished {
+    task: Arc<Task>,
+    region: Option<Window>,
+    frame: i64,
+    generation: u64,
+    result: Result<Value, EvalError>,
+    fused: Vec<Vec<NodeId>>,
+}
+
+pub struct Worker {
+    messages: mpsc::Sender<Message>,
+    results: mpsc::Receiver<Finished>,
+    /// Graph, pin, region and frame of the last submitted job or cached frame.
+    submitted: Option<Submitted>,
+    running: Option<Arc<Task>>,
+    result: Option<Result<Value, EvalError>>,
+    fused: Vec<Vec<NodeId>>,
+    proxy: Proxy,
+    frame: i64,
+    frames: FrameCache,
+    /// Incremented whenever the frame cache is cleared, so results of
+    /// older graphs aren't stored in it.
+    generation: u64,
+    disk_cache: Option<DiskCache>,
+    render_sender: mpsc::Sender<RenderResult>,
+    renders: mpsc::Receiver<RenderResult>,
+    freeze_sender: mpsc::Sender<FreezeResult>,
+    freezes: mpsc::Receiver<FreezeResult>,
+    /// Number of renders and freezes still running.
+    rendering: usize,
+}
+
+impl Worker {
+    /// Starts the evaluation thread. It stops when the worker is dropped.
+    pub fn new() -> Self {
+        let (messages, receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("evaluator".to_owned())
+            .spawn(move || {
+                let mut evaluator = Evaluator::new();
+                while let Ok(message) = receiver.recv() {
+                    // Only the newest job matters, older ones are stale
+                    let mut job = None;
+                    for message in std::iter::once(message).chain(receiver.try_iter()) {
+                        match message {
+                            Message::Evaluate(newer) => job = Some(newer),
+                            Message::SetDiskCache(disk_cache) => {
+                                evaluator.set_disk_cache(disk_cache);
+                            }
+                            Message::SetProxy(proxy) => evaluator.set_proxy(proxy),
+                        }
+                    }
+                    let Some(job) = job.filter(|job| !job.task.is_cancelled()) else {
+                        continue;
+                    };
+
+                    evaluator.set_frame(job.frame);
+                    let result = job
+                        .task
+                        .run(|| evaluator.evaluate(&job.snarl, job.pin, job.region));
+                    if job.task.is_cancelled() {
+                        continue;
+                    }
+                    let finished = Finished {
+                        task: job.task,
+                        region: job.region,
+                        frame: job.frame,
+                        generation: job.generation,
+                        result,
+                        fused: evaluator.fused().to_vec(),
+                    };
+                    if result_sender.send(finished).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to start the evaluator thread");
+
+        let (render_sender, renders) = mpsc::channel();
+        let (freeze_sender, freezes) = mpsc::channel();
+        Worker {
+            messages,
+            results,
+            submitted: None,
+            running: None,
+            result: None,
+            fused: Vec::new(),
+            proxy: Proxy::Full,
+            frame: 0,
+            frames: FrameCache::default(),
+            generation: 0,
+            disk_cache: None,
+            render_sender,
+            renders,
+            freeze_sender,
+            freezes,
+            rendering: 0,
+        }
+    }
+
+    /// Evaluates the pixels of `pin` inside `region`, or all of them without
+    /// a region, at the current frame, unless the same graph was submitted
+    /// before for a region that includes it or the frame is cached. `None`
+    /// for the pin clears the result.
+    pub fn submit(
+        &mut self,
+        snarl: &Snarl<DemoNode>,
+        pin: Option<OutPinId>,
+        region: Option<Window>,
+    ) {
+        let Some(pin) = pin else {
+            self.cancel();
+            self.submitted = None;
+            self.result = None;
+            self.clear_frames();
+            return;
+        };
+        let same = self
+            .submitted
+            .as_ref()
+            .is_some_and(|submitted| submitted.pin == pin && same_graph(&submitted.snarl, snarl));
+        if !same {
+            self.clear_frames();
+        } else if self.submitted.as_ref().is_some_and(|submitted| {
+            submitted.frame == self.frame && eval::covers(submitted.region, region)
+        }) {
+            return;
+        }
+
+        self.cancel();
+        self.submitted = Some(Submitted {
+            snarl: snarl.clone(),
+            pin,
+            region,
+            frame: self.frame,
+        });
+        if let Some(value) = self.frames.get(self.frame, region) {
+            self.result = Some(Ok(value.clone()));
+            return;
+        }
+        let task = Task::new();
+        self.running = Some(task.clone());
+        let job = Job {
+            snarl: snarl.clone(),
+            pin,
+            region,
+            frame: self.frame,
+            generation: self.generation,
+            task,
+        };
+        self.send(Message::Evaluate(job));
+    }
+
+    pub fn disk_cache(&self) -> Option<&DiskCache> {
+        self.disk_cache.as_ref()
+    }
+
+    /// Enables or disables the disk cache of the evaluator and of renders.
+    pub fn set_disk_cache(&mut self, disk_cache: Option<DiskCache>) {
+        self.disk_cache.clone_from(&disk_cache);
+        self.send(Message::SetDiskCache(disk_cache));
+    }
+
+    pub fn proxy(&self) -> Proxy {
+        self.proxy
+    }
+
+    /// Changes the proxy resolution. The next submitted graph is evaluated
+    /// again even if it didn't change, and cached frames are dropped.
+    pub fn set_proxy(&mut self, proxy: Proxy) {
+        if proxy != self.proxy {
+            self.proxy = proxy;
+            self.submitted = None;
+            self.clear_frames();
+            self.send(Message::SetProxy(proxy));
+        }
+    }
+
+    pub fn frame(&self) -> i64 {
+        self.frame
+    }
+
+    /// Changes the frame of image sequences the next submitted graph is
+    /// evaluated at.
+    pub fn set_frame(&mut self, frame: i64) {
+        self.frame = frame;
+    }
+
+    /// Results of the submitted graph kept per frame.
+    pub fn frames(&self) -> &FrameCache {
+        &self.frames
+    }
+
+    pub fn set_frame_budget(&mut self, budget: usize) {
+        self.frames.set_budget(budget);
+    }
+
+    /// Drops the cached frames, so they are evaluated again when shown.
+    pub fn clear_frames(&mut self) {
+        self.frames.clear();
+        self.generation += 1;
+    }
+
+    /// Renders a Write node of `snarl` at full resolution on a new thread,
+    /// for every frame of `frames` if it writes a sequence and for the
+    /// current frame otherwise.
+    pub fn render(&mut self, snarl: &Snarl<DemoNode>, node: NodeId, frames: FrameRange) {
+        let snarl = snarl.clone();
+        let frame = self.frame;
+        let disk_cache = self.disk_cache.clone();
+        let sender = self.render_sender.clone();
+        thread::Builder::new()
+            .name("render".to_owned())
+            .spawn(move || {
+                let mut evaluator = Evaluator::new();
+                evaluator.set_disk_cache(disk_cache);
+                evaluator.set_frame(frame);
+                let _ = sender.send((node, evaluator.render(&snarl, node, frames, |_, _| {})));
+            })
+            .expect("Failed to start a render thread");
+        self.rendering += 1;
+    }
+
+    /// Renders that finished since the last call.
+    pub fn finished_renders(&mut self) -> Vec<RenderResult> {
+        let finished = self.renders.try_iter().collect::<Vec<_>>();
+        self.rendering -= finished.len();
+        finished
+    }
+
+    /// Evaluates a node of `snarl` at the current frame on a new thread and
+    /// stores its outputs for the node to be frozen with, see
+    /// `Evaluator::freeze`. The node is frozen by the caller once the hash
+    /// arrives in `finished_freezes`.
+    pub fn freeze(&mut self, snarl: &Snarl<DemoNode>, node: NodeId) {
+        let snarl = snarl.clone();
+        let frame = self.frame;
+        let disk_cache = self.disk_cache.clone();
+        let sender = self.freeze_sender.clone();
+        thread::Builder::new()
+            .name("freeze".to_owned())
+            .spawn(move || {
+                let mut evaluator = Evaluator::new();
+                evaluator.set_disk_cache(disk_cache);
+                evaluator.set_frame(frame);
+                let _ = sender.send((node, evaluator.freeze(&snarl, node)));
+            })
+            .expect("Failed to start a freeze thread");
+        self.rendering += 1;
+    }
+
+    /// Freezes that finished since the last call.
+    pub fn finished_freezes(&mut self) -> Vec<FreezeResult> {
+        let finished = self.freezes.try_iter().collect::<Vec<_>>();
+        self.rendering -= finished.len();
+        finished
+    }
+
+    pub fn is_rendering(&self) -> bool {
+        self.rendering > 0
+    }
+
+    /// Takes the result of a finished job, if any.
+    pub fn poll(&mut self) {
+        while let Ok(finished) = self.results.try_recv() {
+            if finished.task.is_cancelled() {
+                continue;
+            }
+            if self
+                .running
+                .as_ref()
+                .is_some_and(|task| Arc::ptr_eq(task, &finished.task))
+            {
+                self.running = None;
+            }
+            if let (Ok(value), true) = (&finished.result, finished.generation == self.generation) {
+                self.frames
+                    .insert(finished.frame, finished.region, value.clone());
+            }
+            self.result = Some(finished.result);
+            self.fused = finished.fused;
+        }
+    }
+
+    /// Result of the last finished job.
+    pub fn result(&self) -> Option<&Result<Value, EvalError>> {
+        self.result.as_ref()
+    }
+
+    /// Chains of nodes the last finished job ran as a single pass.
+    pub fn fused(&self) -> &[Vec<NodeId>] {
+        &self.fused
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Node the running job is evaluating and the fraction of it that is done.
+    pub fn progress(&self) -> Option<(NodeId, f32)> {
+        self.running.as_ref().and_then(|task| task.progress())
+    }
+
+    fn cancel(&mut self) {
+        if let Some(task) = self.running.take() {
+            task.cancel();
+        }
+    }
+
+    fn send(&self, message: Message) {
+        self.messages
+            .send(message)
+            .expect("The evaluator thread stopped");
+    }
+}
+
+/// Last submission, which later ones are compared against.
+struct Submitted {
+    snarl: Snarl<DemoNode>,
+    pin: OutPinId,
+    region: Option<Window>,
+    frame: i64,
+}
+
+impl Default for Worker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Whether two graphs have the same nodes and wires. Node positions and
+/// other UI state are ignored.
+fn same_graph(a: &Snarl<DemoNode>, b: &Snarl<DemoNode>) -> bool {
+    let wires = |snarl: &Snarl<DemoNode>| snarl.wires().collect::<HashSet<(OutPinId, InPinId)>>();
+    a.node_ids().eq(b.node_ids()) && wires(a) == wires(b)
+}
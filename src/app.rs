@@ -1,10 +1,20 @@
+use crate::batch_dialog::BatchDialog;
+use crate::curve_editor::CurveEditor;
 use crate::egui_tools::EguiRenderer;
+use crate::scopes::Scopes;
+use crate::timeline::Timeline;
+use crate::viewer::ImageViewer;
+use cas_graph::disk_cache::DiskCache;
+use cas_graph::eval::Proxy;
 use cas_graph::graph_style;
 use cas_graph::node::DemoNode;
 use cas_graph::node_graph::DemoViewer;
+use cas_graph::project::{self, Project};
+use cas_graph::sequence::{FrameRange, Playback};
+use cas_graph::worker::Worker;
 use egui::Id;
 use egui_snarl::ui::{NodeLayout, PinPlacement, SnarlStyle, SnarlWidget};
-use egui_snarl::Snarl;
+use egui_snarl::{NodeId, OutPinId, Snarl};
 use egui_wgpu::wgpu::SurfaceError;
 use egui_wgpu::{wgpu, ScreenDescriptor};
 use std::sync::Arc;
@@ -106,6 +116,33 @@ pub struct App {
     state: Option<AppState>,
     window: Option<Arc<Window>>,
     snarl: Snarl<DemoNode>,
+    graph_viewer: DemoViewer,
+    worker: Worker,
+    image_viewer: ImageViewer,
+    scopes: Scopes,
+    show_scopes: bool,
+    timeline: Timeline,
+    curve_editor: CurveEditor,
+    show_curve_editor: bool,
+    /// Node and parameter index of the curve in the curve editor.
+    curve_target: Option<(NodeId, usize)>,
+    batch_dialog: BatchDialog,
+    /// Path of the project file, typed in the File menu.
+    project_path: String,
+    disk_cache: bool,
+    /// Frames of image sequences that are rendered.
+    frame_range: FrameRange,
+    /// Frame of image sequences shown in the viewer.
+    frame: i64,
+    playback: Playback,
+    /// Outcome of the last project action, shown in the menu bar.
+    status: Option<String>,
+}
+
+/// Project action picked in the File menu, run once the frame is laid out.
+enum FileAction {
+    Open,
+    Save,
 }
 
 impl App {
@@ -116,6 +153,57 @@ impl App {
             state: None,
             window: None,
             snarl: Snarl::new(),
+            graph_viewer: DemoViewer::default(),
+            worker: Worker::new(),
+            image_viewer: ImageViewer::new(),
+            scopes: Scopes::new(),
+            show_scopes: false,
+            timeline: Timeline::new(),
+            curve_editor: CurveEditor::new(),
+            show_curve_editor: false,
+            curve_target: None,
+            batch_dialog: BatchDialog::new(),
+            project_path: String::new(),
+            disk_cache: false,
+            frame_range: FrameRange::default(),
+            frame: FrameRange::default().first,
+            playback: Playback::default(),
+            status: None,
+        }
+    }
+
+    fn open_project(&mut self) {
+        match Project::load(&self.project_path) {
+            Ok(project) => {
+                self.worker
+                    .set_disk_cache(project.disk_cache(&self.project_path));
+                self.snarl = project.snarl;
+                self.disk_cache = project.disk_cache;
+                self.frame_range = project.frame_range;
+                self.frame = project.frame_range.clamp(project.frame);
+                self.playback = project.playback;
+                self.graph_viewer.viewed = None;
+                self.status = None;
+            }
+            Err(err) => self.status = Some(err.to_string()),
+        }
+    }
+
+    fn save_project(&mut self) {
+        let project = Project {
+            snarl: self.snarl.clone(),
+            disk_cache: self.disk_cache,
+            frame_range: self.frame_range,
+            frame: self.frame,
+            playback: self.playback,
+        };
+        match project.save(&self.project_path) {
+            Ok(()) => {
+                self.worker
+                    .set_disk_cache(project.disk_cache(&self.project_path));
+                self.status = Some(format!("Saved {}", self.project_path));
+            }
+            Err(err) => self.status = Some(err.to_string()),
         }
     }
 
@@ -195,6 +283,7 @@ impl App {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
         let window = self.window.as_ref().unwrap();
+        let mut file_action = None;
 
         {
             state.egui_renderer.begin_frame(window);
@@ -203,11 +292,77 @@ impl App {
 
             egui_extras::install_image_loaders(&state.egui_renderer.context());
 
+            self.timeline
+                .update(&mut self.frame, self.frame_range, &self.playback);
+            self.worker.set_frame(self.frame);
+            self.graph_viewer.frame = self.frame;
+            self.graph_viewer.disk_cache = self.worker.disk_cache().is_some();
+            let viewed = self
+                .graph_viewer
+                .viewed
+                .filter(|&node| self.snarl.get_node(node).is_some());
+            // The last finished result stays on screen while the worker renders
+            // Only the pixels the viewer showed on the last frame are computed
+            let region = self.image_viewer.visible_region();
+            // While playing, a frame being evaluated is finished rather than
+            // cancelled by the next one, and the frames due meanwhile are dropped
+            let cached = self.worker.frames().get(self.frame, region).is_some();
+            if !self.timeline.is_playing() || cached || !self.worker.is_busy() {
+                self.worker.submit(
+                    &self.snarl,
+                    viewed.map(|node| OutPinId { node, output: 0 }),
+                    region,
+                );
+            }
+            self.worker.poll();
+            self.graph_viewer.progress = self.worker.progress();
+            self.graph_viewer.fused = self.worker.fused().to_vec();
+            // Cloned so the worker can be changed from the menus
+            let result = self.worker.result().cloned();
+
             egui::TopBottomPanel::top("top_panel").show(state.egui_renderer.context(), |ui| {
                 // The top panel is often a good place for a menu bar:
 
                 egui::menu::bar(ui, |ui| {
                     ui.menu_button("File", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Project");
+                            ui.text_edit_singleline(&mut self.project_path)
+                                .on_hover_text(format!("Path of a .{} file", project::EXTENSION));
+                        });
+                        let has_path = !self.project_path.is_empty();
+                        if ui.add_enabled(has_path, egui::Button::new("Open")).clicked() {
+                            file_action = Some(FileAction::Open);
+                            ui.close_menu();
+                        }
+                        if ui.add_enabled(has_path, egui::Button::new("Save")).clicked() {
+                            file_action = Some(FileAction::Save);
+                            ui.close_menu();
+                        }
+                        let toggled = ui
+                            .add_enabled(
+                                has_path,
+                                egui::Checkbox::new(&mut self.disk_cache, "Disk Cache"),
+                            )
+                            .on_hover_text(
+                                "Keep slow node outputs next to the project file for later sessions",
+                            )
+                            .changed();
+                        if toggled {
+                            let disk_cache = self.disk_cache.then(|| {
+                                DiskCache::new(
+                                    Project::cache_dir(&self.project_path),
+                                    DiskCache::DEFAULT_LIMIT,
+                                )
+                            });
+                            self.worker.set_disk_cache(disk_cache);
+                        }
+                        ui.separator();
+                        if ui.button("Batch…").clicked() {
+                            self.batch_dialog.open = true;
+                            ui.close_menu();
+                        }
+                        ui.separator();
                         if ui.button("Quit").clicked() {
                             state
                                 .egui_renderer
@@ -215,23 +370,98 @@ impl App {
                                 .send_viewport_cmd(egui::ViewportCommand::Close);
                         }
                     });
+                    ui.menu_button("View", |ui| {
+                        ui.checkbox(&mut self.show_scopes, "Scopes");
+                        ui.checkbox(&mut self.show_curve_editor, "Curve Editor");
+                    });
                     ui.add_space(16.0);
 
                     egui::widgets::global_theme_preference_switch(ui);
 
+                    let mut proxy = self.worker.proxy();
+                    egui::ComboBox::from_id_salt("proxy")
+                        .selected_text(format!("Proxy {}", proxy.name()))
+                        .show_ui(ui, |ui| {
+                            for option in Proxy::ALL {
+                                ui.selectable_value(&mut proxy, option, option.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Resolution for interactive work. Renders of Write nodes are always full resolution");
+                    self.worker.set_proxy(proxy);
+
+                    if self.worker.is_busy()
+                        || self.worker.is_rendering()
+                        || self.batch_dialog.is_running()
+                    {
+                        ui.spinner();
+                        ui.label("Rendering…");
+                    }
+                    if let Some(status) = &self.status {
+                        ui.label(status);
+                    }
+
                     if ui.button("Clear All").clicked() {
                         self.snarl = Snarl::default();
+                        self.graph_viewer.viewed = None;
                     }
                 });
             });
 
+            egui::SidePanel::right("viewer_panel")
+                .resizable(true)
+                .default_width(480.0)
+                .show(state.egui_renderer.context(), |ui| {
+                    egui::TopBottomPanel::bottom("timeline_panel")
+                        .resizable(false)
+                        .show_inside(ui, |ui| {
+                            self.timeline.show(
+                                ui,
+                                &mut self.frame,
+                                &mut self.frame_range,
+                                &mut self.playback,
+                                &mut self.worker,
+                            );
+                        });
+                    if self.show_scopes {
+                        egui::TopBottomPanel::bottom("scopes_panel")
+                            .resizable(true)
+                            .default_height(240.0)
+                            .show_inside(ui, |ui| self.scopes.show(ui, result.as_ref()));
+                    }
+                    self.image_viewer
+                        .show(ui, result.as_ref(), &mut self.snarl, viewed);
+                });
+
+            if self.show_curve_editor {
+                egui::TopBottomPanel::bottom("curve_panel")
+                    .resizable(true)
+                    .default_height(220.0)
+                    .show(state.egui_renderer.context(), |ui| {
+                        self.curve_editor.show(
+                            ui,
+                            &mut self.snarl,
+                            &mut self.curve_target,
+                            &mut self.frame,
+                            self.frame_range,
+                        );
+                    });
+            }
+
             egui::CentralPanel::default().show(state.egui_renderer.context(), |ui| {
                 SnarlWidget::new()
                     .id(Id::new("snarl-graph"))
                     .style(state.snarl_style)
-                    .show(&mut self.snarl, &mut DemoViewer, ui);
+                    .show(&mut self.snarl, &mut self.graph_viewer, ui);
             });
 
+            self.batch_dialog.show(
+                state.egui_renderer.context(),
+                &self.snarl,
+                self.frame,
+                self.worker.disk_cache(),
+            );
+
             // ---------------------------------------------------------
 
             state.egui_renderer.end_frame_and_draw(
@@ -246,6 +476,41 @@ impl App {
 
         state.queue.submit(Some(encoder.finish()));
         surface_texture.present();
+
+        match file_action {
+            Some(FileAction::Open) => self.open_project(),
+            Some(FileAction::Save) => self.save_project(),
+            None => {}
+        }
+
+        if let Some(target) = self.graph_viewer.curve_request.take() {
+            self.curve_target = Some(target);
+            self.show_curve_editor = true;
+        }
+        if let Some(range) = self.graph_viewer.frame_range_request.take() {
+            self.frame_range = range;
+            self.frame = range.clamp(self.frame);
+        }
+        for node in std::mem::take(&mut self.graph_viewer.render_requests) {
+            self.worker.render(&self.snarl, node, self.frame_range);
+        }
+        for (_, result) in self.worker.finished_renders() {
+            self.status = Some(match result {
+                Ok(path) => format!("Wrote {path}"),
+                Err(err) => err.to_string(),
+            });
+        }
+        for node in std::mem::take(&mut self.graph_viewer.freeze_requests) {
+            self.worker.freeze(&self.snarl, node);
+        }
+        for (node, result) in self.worker.finished_freezes() {
+            match (result, self.snarl.get_node_mut(node)) {
+                (Ok(hash), Some(DemoNode::Op(op))) => op.frozen = Some(hash),
+                (Err(err), _) => self.status = Some(err.to_string()),
+                // The node was removed meanwhile
+                (Ok(_), _) => {}
+            }
+        }
     }
 }
 
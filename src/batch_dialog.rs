@@ -0,0 +1,284 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use cas_graph::batch::{self, Batch};
+use cas_graph::disk_cache::DiskCache;
+use cas_graph::node::DemoNode;
+use cas_graph::ops::OpType;
+use cas_graph::project;
+use egui::{Color32, Context, Ui};
+use egui_snarl::{NodeId, Snarl};
+
+/// Summary of a finished batch with a line for every file that failed, or
+/// the error that kept it from running.
+type Finished = Result<(String, Vec<String>), String>;
+
+/// Window that runs every image of a folder through the graph.
+///
+/// The batch runs on a thread of its own with a copy of the graph, so the
+/// graph can be edited meanwhile without changing the batch.
+pub struct BatchDialog {
+    pub open: bool,
+    input: String,
+    filter: String,
+    read: Option<NodeId>,
+    write: Option<NodeId>,
+    /// Output pattern, the file of the Write node if empty.
+    output: String,
+    /// Path of the report, next to the outputs if empty.
+    report: String,
+    running: Option<Running>,
+    finished: Option<Finished>,
+}
+
+struct Running {
+    /// Number of files matching when the batch started.
+    total: usize,
+    /// Whether every file succeeded, as it finishes.
+    progress: Receiver<bool>,
+    succeeded: usize,
+    failed: usize,
+    thread: JoinHandle<Finished>,
+}
+
+impl BatchDialog {
+    pub fn new() -> Self {
+        BatchDialog {
+            open: false,
+            input: String::new(),
+            filter: "*.png".to_owned(),
+            read: None,
+            write: None,
+            output: String::new(),
+            report: String::new(),
+            running: None,
+            finished: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &Context,
+        snarl: &Snarl<DemoNode>,
+        frame: i64,
+        disk_cache: Option<&DiskCache>,
+    ) {
+        self.poll();
+        let mut open = self.open;
+        egui::Window::new("Batch")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| self.contents(ui, snarl, frame, disk_cache));
+        self.open = open;
+    }
+
+    fn contents(
+        &mut self,
+        ui: &mut Ui,
+        snarl: &Snarl<DemoNode>,
+        frame: i64,
+        disk_cache: Option<&DiskCache>,
+    ) {
+        let names = project::node_names(snarl);
+        let nodes = |op_type| {
+            names
+                .iter()
+                .filter(
+                    |&&(node, _)| matches!(&snarl[node], DemoNode::Op(op) if op.op_type == op_type),
+                )
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let (reads, writes) = (nodes(OpType::Read), nodes(OpType::Write));
+        self.read = pick(self.read, &reads);
+        self.write = pick(self.write, &writes);
+
+        egui::Grid::new("batch_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Input Folder");
+                ui.text_edit_singleline(&mut self.input);
+                ui.end_row();
+
+                ui.label("Filter");
+                ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("All files"))
+                    .on_hover_text("Names of the images, like *.png or *.png;*.jpg");
+                ui.end_row();
+
+                ui.label("Read Node");
+                node_combo(ui, "batch_read", &mut self.read, &reads);
+                ui.end_row();
+
+                ui.label("Write Node");
+                node_combo(ui, "batch_write", &mut self.write, &writes);
+                ui.end_row();
+
+                ui.label("Output");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.output)
+                        .hint_text("File of the Write node"),
+                )
+                .on_hover_text(
+                    "Path of the outputs, where {stem}, {ext} and {index} are the name, \
+                     extension and number of the image",
+                );
+                ui.end_row();
+
+                ui.label("Report");
+                ui.add(egui::TextEdit::singleline(&mut self.report).hint_text(batch::REPORT_NAME))
+                    .on_hover_text(
+                        "File the summary is written to, next to the outputs by default",
+                    );
+                ui.end_row();
+            });
+
+        ui.separator();
+        if let Some(running) = &self.running {
+            let done = running.succeeded + running.failed;
+            ui.add(
+                egui::ProgressBar::new(done as f32 / running.total.max(1) as f32).text(format!(
+                    "{done} / {} files, {} failed",
+                    running.total, running.failed
+                )),
+            );
+            return;
+        }
+
+        let ready = !self.input.is_empty() && self.read.is_some() && self.write.is_some();
+        if ui.add_enabled(ready, egui::Button::new("Run")).clicked() {
+            self.start(snarl, frame, disk_cache);
+        }
+        match &self.finished {
+            Some(Ok((summary, failures))) => {
+                ui.label(summary);
+                egui::ScrollArea::vertical()
+                    .max_height(120.0)
+                    .show(ui, |ui| {
+                        for failure in failures {
+                            ui.colored_label(Color32::LIGHT_RED, failure);
+                        }
+                    });
+            }
+            Some(Err(err)) => {
+                ui.colored_label(Color32::LIGHT_RED, err);
+            }
+            None => {}
+        }
+    }
+
+    fn start(&mut self, snarl: &Snarl<DemoNode>, frame: i64, disk_cache: Option<&DiskCache>) {
+        let (Some(read), Some(write)) = (self.read, self.write) else {
+            return;
+        };
+        let output = if self.output.is_empty() {
+            match &snarl[write] {
+                DemoNode::Op(op) => op.path("File").to_owned(),
+                _ => String::new(),
+            }
+        } else {
+            self.output.clone()
+        };
+        let mut batch = Batch::new(&self.input, read, write, &output);
+        batch.filter.clone_from(&self.filter);
+        batch.frame = frame;
+        let report_path = if self.report.is_empty() {
+            batch.report_path()
+        } else {
+            self.report.clone().into()
+        };
+
+        let total = batch.files().map_or(0, |files| files.len());
+        let (sender, progress) = mpsc::channel();
+        let snarl = snarl.clone();
+        let disk_cache = disk_cache.cloned();
+        let thread = thread::Builder::new()
+            .name("batch".to_owned())
+            .spawn(move || {
+                let started = Instant::now();
+                let outcomes = batch
+                    .run(&snarl, disk_cache, |outcome| {
+                        let _ = sender.send(outcome.result.is_ok());
+                    })
+                    .map_err(|err| err.to_string())?;
+                let report = batch::report(&outcomes, started.elapsed());
+                std::fs::write(&report_path, &report)
+                    .map_err(|err| format!("{}: {err}", report_path.display()))?;
+
+                let failures = outcomes
+                    .iter()
+                    .filter_map(|outcome| {
+                        let err = outcome.result.as_ref().err()?;
+                        Some(format!("{}: {err}", outcome.input.display()))
+                    })
+                    .collect();
+                let summary = format!(
+                    "{} Report written to {}",
+                    report.lines().last().unwrap_or_default(),
+                    report_path.display()
+                );
+                Ok((summary, failures))
+            })
+            .expect("Failed to start the batch thread");
+
+        self.finished = None;
+        self.running = Some(Running {
+            total,
+            progress,
+            succeeded: 0,
+            failed: 0,
+            thread,
+        });
+    }
+
+    /// Counts the files finished since the last call and takes the result
+    /// of the batch once it is done.
+    fn poll(&mut self) {
+        let Some(running) = &mut self.running else {
+            return;
+        };
+        for succeeded in running.progress.try_iter() {
+            if succeeded {
+                running.succeeded += 1;
+            } else {
+                running.failed += 1;
+            }
+        }
+        if running.thread.is_finished() {
+            let running = self.running.take().unwrap();
+            self.finished = Some(
+                running
+                    .thread
+                    .join()
+                    .unwrap_or_else(|_| Err("The batch stopped unexpectedly".to_owned())),
+            );
+        }
+    }
+}
+
+/// `node` if it is still one of `nodes`, or the only node of `nodes`.
+fn pick(node: Option<NodeId>, nodes: &[(NodeId, String)]) -> Option<NodeId> {
+    match node.filter(|node| nodes.iter().any(|(other, _)| other == node)) {
+        Some(node) => Some(node),
+        None if nodes.len() == 1 => Some(nodes[0].0),
+        None => None,
+    }
+}
+
+fn node_combo(ui: &mut Ui, id: &str, node: &mut Option<NodeId>, nodes: &[(NodeId, String)]) {
+    let selected = nodes
+        .iter()
+        .find(|&&(other, _)| Some(other) == *node)
+        .map_or("None", |(_, name)| name.as_str());
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            for (other, name) in nodes {
+                ui.selectable_value(node, Some(*other), name);
+            }
+        });
+}
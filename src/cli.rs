@@ -0,0 +1,662 @@
+//! Command line mode, which runs without a window so it works on machines
+//! without a display.
+//!
+//! Exit codes:
+//!
+//! - 0: everything succeeded.
+//! - 1: a render, a value or a file of a batch failed, or validation found
+//!   problems.
+//! - 2: the arguments are invalid.
+//! - 3: the project can't be loaded, or a node or parameter given doesn't exist.
+
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use cas_graph::batch::{self, Batch};
+use cas_graph::eval::{Evaluator, Value};
+use cas_graph::node::{DemoNode, PixelExprNode};
+use cas_graph::ops::{OpType, PinType};
+use cas_graph::project::Project;
+use cas_graph::sequence::{self, FrameRange};
+use cas_graph::stdio;
+use egui_snarl::{NodeId, OutPinId};
+use image::ImageFormat;
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_PROJECT: u8 = 3;
+
+const USAGE: &str = "\
+Usage:
+  cascade                      Opens the editor
+  cascade render PROJECT [-] [OPTIONS]
+                               Renders Write nodes of a project without a window
+  cascade inspect PROJECT [OPTIONS]
+                               Prints the nodes, wires and number and string
+                               outputs of a project
+  cascade validate PROJECT...  Checks projects for wires of the wrong type,
+                               cycles, missing files and invalid expressions
+  cascade batch PROJECT --input FOLDER [OPTIONS]
+                               Runs every image of a folder through a Read and
+                               a Write node
+
+Render options:
+  -                    Reads the image of the Read node from standard input and writes
+                       the Write node to standard output, as in
+                       cat in.png | cascade render grade.csc - > out.png
+                       Read and Write nodes with the file - do so without it
+  --input-format FMT   Format of standard input, like png or exr, detected by default
+  --output-format FMT  Format of standard output, that of the input or PNG by default
+  --frames FIRST-LAST  Frames of sequences to render, the project frame range by default
+  --node NAME          Write node to render, like Write1. Repeat for several, all by default
+  --set NAME=VALUE     Overrides a parameter, like Grade1.Gain=1.5 or Gain=1.5
+                       if only one node has it. Repeat for several
+  --quiet              Prints errors only
+  --help               Prints this help
+
+Inspect options:
+  --frame FRAME        Frame to print values at, the project frame by default
+  --set NAME=VALUE     Overrides a parameter, as for render
+
+Batch options:
+  --input FOLDER       Folder of the images to process
+  --filter PATTERNS    Names of the images, like *.png or *.png;*.jpg, all files by default
+  --read NAME          Read node the images are set on, the only one by default
+  --write NAME         Write node to render, the only one by default
+  --output PATTERN     Path of the outputs, where {stem}, {ext} and {index} are the name,
+                       extension and number of the image. The file of the Write node by default
+  --jobs N             Images processed at the same time, 2 by default
+  --report FILE        File the summary is written to, batch_report.txt next to the outputs
+                       by default
+  --set NAME=VALUE     Overrides a parameter, as for render
+  --quiet              Prints errors only
+";
+
+/// Runs the command in `args`, the arguments after the program name.
+pub fn run(args: &[String]) -> ExitCode {
+    match args.first().map(String::as_str) {
+        Some("render") => render(&args[1..]),
+        Some("inspect") => inspect(&args[1..]),
+        Some("validate") => validate(&args[1..]),
+        Some("batch") => batch(&args[1..]),
+        Some("help" | "--help" | "-h") => {
+            print!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        Some(command) => usage_error(&format!("unknown command `{command}`")),
+        None => usage_error("no command given"),
+    }
+}
+
+struct RenderArgs {
+    project: String,
+    frames: Option<FrameRange>,
+    nodes: Vec<String>,
+    assignments: Vec<String>,
+    quiet: bool,
+    /// Whether the image is piped through standard input and output.
+    pipe: bool,
+    input_format: Option<ImageFormat>,
+    output_format: Option<ImageFormat>,
+}
+
+impl RenderArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = RenderArgs {
+            project: String::new(),
+            frames: None,
+            nodes: Vec::new(),
+            assignments: Vec::new(),
+            quiet: false,
+            pipe: false,
+            input_format: None,
+            output_format: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+            match arg.as_str() {
+                "--frames" => parsed.frames = Some(value()?.parse()?),
+                "--node" => parsed.nodes.push(value()?),
+                "--set" => parsed.assignments.push(value()?),
+                "--quiet" => parsed.quiet = true,
+                "--input-format" => parsed.input_format = Some(image_format(value()?)?),
+                "--output-format" => parsed.output_format = Some(image_format(value()?)?),
+                stdio::PATH if !parsed.project.is_empty() && !parsed.pipe => parsed.pipe = true,
+                option if option.starts_with("--") => {
+                    return Err(format!("unknown option `{option}`"))
+                }
+                _ if parsed.project.is_empty() => parsed.project.clone_from(arg),
+                _ => return Err(format!("unexpected argument `{arg}`")),
+            }
+        }
+        if parsed.project.is_empty() {
+            return Err("no project given".to_owned());
+        }
+        Ok(parsed)
+    }
+}
+
+/// Image format named `name`.
+fn image_format(name: String) -> Result<ImageFormat, String> {
+    stdio::parse_format(&name).ok_or_else(|| format!("unknown image format `{name}`"))
+}
+
+fn render(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "--help") {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let args = match RenderArgs::parse(args) {
+        Ok(args) => args,
+        Err(message) => return usage_error(&message),
+    };
+
+    stdio::enable();
+    if let Some(format) = args.input_format {
+        stdio::set_input_format(format);
+    }
+    if let Some(format) = args.output_format {
+        stdio::set_output_format(format);
+    }
+
+    let mut project = match load(&args.project, &args.assignments) {
+        Ok(project) => project,
+        Err(code) => return code,
+    };
+    if args.pipe {
+        match chosen_node(&project, OpType::Read, None, "--set NAME.File=-") {
+            Ok(read) => {
+                if let DemoNode::Op(op) = &mut project.snarl[read] {
+                    op.set_path("File", stdio::PATH);
+                }
+            }
+            Err(message) => return project_error(&message),
+        }
+    }
+    let nodes = if args.nodes.is_empty() && args.pipe {
+        match chosen_node(&project, OpType::Write, None, "--node") {
+            Ok(write) => vec![write],
+            Err(message) => return project_error(&message),
+        }
+    } else if args.nodes.is_empty() {
+        project.write_nodes()
+    } else {
+        match args
+            .nodes
+            .iter()
+            .map(|name| project.find_node(name))
+            .collect()
+        {
+            Ok(nodes) => nodes,
+            Err(err) => return project_error(&err.to_string()),
+        }
+    };
+    let writes = project.write_nodes();
+    if let Some(idx) = nodes.iter().position(|node| !writes.contains(node)) {
+        return project_error(&format!("{} isn't a Write node", args.nodes[idx]));
+    }
+    if nodes.is_empty() {
+        return project_error("the project has no Write nodes");
+    }
+    if args.pipe {
+        for &node in &nodes {
+            if let DemoNode::Op(op) = &mut project.snarl[node] {
+                op.set_path("File", stdio::PATH);
+            }
+        }
+    }
+    // Progress goes to standard error while images go to standard output
+    let to_stdout = nodes.iter().any(
+        |&node| matches!(&project.snarl[node], DemoNode::Op(op) if stdio::is_stdio(op.path("File"))),
+    );
+    let say = |line: &str| match (args.quiet, to_stdout) {
+        (true, _) => {}
+        (false, true) => eprintln!("{line}"),
+        (false, false) => println!("{line}"),
+    };
+
+    let frames = args.frames.unwrap_or(project.frame_range);
+    let name = |node| project.node_name(node);
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_disk_cache(project.disk_cache(&args.project));
+    // Files that aren't sequences are written for the first frame given
+    let frame = match args.frames {
+        Some(frames) => frames.first,
+        None => project.frame_range.clamp(project.frame),
+    };
+    evaluator.set_frame(frame);
+    let started = Instant::now();
+    let mut failed = 0;
+    let mut files = 0;
+    for &node in &nodes {
+        let DemoNode::Op(write) = &project.snarl[node] else {
+            unreachable!("Only Write nodes are rendered")
+        };
+        let total = if sequence::is_sequence(write.path("File")) {
+            frames.len()
+        } else {
+            1
+        };
+        say(&format!("Rendering {}", name(node)));
+        let mut done = 0;
+        let result = evaluator.render(&project.snarl, node, frames, |frame, path| {
+            done += 1;
+            say(&format!("  frame {frame} ({done}/{total}): {path}"));
+        });
+        files += done;
+        if let Err(err) = result {
+            eprintln!("error: {}: {err}", name(node));
+            failed += 1;
+        }
+    }
+
+    say(&format!(
+        "Wrote {files} files from {} nodes in {:.1}s",
+        nodes.len() - failed,
+        started.elapsed().as_secs_f64()
+    ));
+    if failed > 0 {
+        ExitCode::from(EXIT_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+struct InspectArgs {
+    project: String,
+    frame: Option<i64>,
+    assignments: Vec<String>,
+}
+
+impl InspectArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = InspectArgs {
+            project: String::new(),
+            frame: None,
+            assignments: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+            match arg.as_str() {
+                "--frame" => {
+                    let frame = value()?;
+                    let frame = frame
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("invalid frame `{frame}`"))?;
+                    parsed.frame = Some(frame);
+                }
+                "--set" => parsed.assignments.push(value()?),
+                option if option.starts_with("--") => {
+                    return Err(format!("unknown option `{option}`"))
+                }
+                _ if parsed.project.is_empty() => parsed.project.clone_from(arg),
+                _ => return Err(format!("unexpected argument `{arg}`")),
+            }
+        }
+        if parsed.project.is_empty() {
+            return Err("no project given".to_owned());
+        }
+        Ok(parsed)
+    }
+}
+
+fn inspect(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "--help") {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let args = match InspectArgs::parse(args) {
+        Ok(args) => args,
+        Err(message) => return usage_error(&message),
+    };
+    let project = match load(&args.project, &args.assignments) {
+        Ok(project) => project,
+        Err(code) => return code,
+    };
+    let frame = args.frame.unwrap_or(project.frame);
+    let name = |node| project.node_name(node);
+
+    println!("Nodes:");
+    for (node, value) in project.snarl.node_ids() {
+        println!("  {} ({})", name(node), value.name());
+        for (parameter, text) in parameters(value, frame) {
+            println!("    {parameter} = {text}");
+        }
+    }
+
+    println!("Wires:");
+    let mut wires = project.snarl.wires().collect::<Vec<_>>();
+    wires.sort_by_key(|&(out_pin, in_pin)| (in_pin.node, in_pin.input, out_pin.node));
+    for (out_pin, in_pin) in wires {
+        println!(
+            "  {}.{} -> {}.{}",
+            name(out_pin.node),
+            project.snarl[out_pin.node].output_name(out_pin.output),
+            name(in_pin.node),
+            project.snarl[in_pin.node].input_name(in_pin.input),
+        );
+    }
+
+    println!("Values at frame {frame}:");
+    let mut evaluator = Evaluator::new();
+    evaluator.set_disk_cache(project.disk_cache(&args.project));
+    evaluator.set_frame(frame);
+    let mut failed = false;
+    for (node, value) in project.snarl.node_ids() {
+        for output in 0..value.outputs() {
+            let shown = match value {
+                DemoNode::Number(..) | DemoNode::String(_) | DemoNode::ExprNode(_) => true,
+                DemoNode::Op(op) => op.op_type.outputs()[output].1 != PinType::Image,
+                _ => false,
+            };
+            if !shown {
+                continue;
+            }
+            let pin = format!("{}.{}", name(node), value.output_name(output));
+            match evaluator.evaluate(&project.snarl, OutPinId { node, output }, None) {
+                Ok(Value::Number(number)) => println!("  {pin} = {number}"),
+                Ok(Value::String(text)) => println!("  {pin} = {text:?}"),
+                Ok(Value::Image(_)) => unreachable!("Image outputs aren't evaluated"),
+                Err(err) => {
+                    println!("  {pin}: error: {err}");
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    if failed {
+        ExitCode::from(EXIT_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Names and values of the settings of a node at `frame`.
+fn parameters(node: &DemoNode, frame: i64) -> Vec<(&str, String)> {
+    let animated = |curve: Option<_>| if curve.is_some() { " (animated)" } else { "" };
+    match node {
+        DemoNode::Sink => Vec::new(),
+        DemoNode::Number(_, curve) => {
+            let value = node.number_out(frame).unwrap_or_default();
+            vec![("Value", format!("{value}{}", animated(curve.as_ref())))]
+        }
+        DemoNode::String(text) => vec![("Value", format!("{text:?}"))],
+        DemoNode::ShowImage(uri) => vec![("URL", uri.clone())],
+        DemoNode::ExprNode(expr_node) => vec![("Expression", expr_node.text.clone())],
+        DemoNode::PixelExpr(pixel_expr) => (0..4)
+            .map(|channel| {
+                let pin = PixelExprNode::FIRST_CHANNEL + channel;
+                (node.input_name(pin), pixel_expr.channels[channel].clone())
+            })
+            .collect(),
+        DemoNode::Op(op) => op
+            .properties
+            .iter()
+            .map(|property| {
+                let value = property.format_value(frame);
+                (
+                    property.name(),
+                    format!("{value}{}", animated(property.curve())),
+                )
+            })
+            .collect(),
+    }
+}
+
+fn validate(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "--help") {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    if let Some(option) = args.iter().find(|arg| arg.starts_with("--")) {
+        return usage_error(&format!("unknown option `{option}`"));
+    }
+    if args.is_empty() {
+        return usage_error("no project given");
+    }
+
+    let mut unreadable = false;
+    let mut invalid = 0;
+    for path in args {
+        let project = match Project::load(path) {
+            Ok(project) => project,
+            Err(err) => {
+                eprintln!("error: {path}: {err}");
+                unreadable = true;
+                continue;
+            }
+        };
+        let issues = cas_graph::validate::validate(&project);
+        for issue in &issues {
+            let name = project.node_name(issue.node);
+            println!("{path}: {name}: {}", issue.message);
+        }
+        if !issues.is_empty() {
+            invalid += 1;
+        }
+    }
+
+    if unreadable {
+        ExitCode::from(EXIT_PROJECT)
+    } else if invalid > 0 {
+        eprintln!("{invalid} of {} projects have problems", args.len());
+        ExitCode::from(EXIT_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+struct BatchArgs {
+    project: String,
+    input: String,
+    filter: String,
+    read: Option<String>,
+    write: Option<String>,
+    output: Option<String>,
+    jobs: Option<usize>,
+    report: Option<String>,
+    assignments: Vec<String>,
+    quiet: bool,
+}
+
+impl BatchArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = BatchArgs {
+            project: String::new(),
+            input: String::new(),
+            filter: String::new(),
+            read: None,
+            write: None,
+            output: None,
+            jobs: None,
+            report: None,
+            assignments: Vec::new(),
+            quiet: false,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{arg} needs a value"))
+            };
+            match arg.as_str() {
+                "--input" => parsed.input = value()?,
+                "--filter" => parsed.filter = value()?,
+                "--read" => parsed.read = Some(value()?),
+                "--write" => parsed.write = Some(value()?),
+                "--output" => parsed.output = Some(value()?),
+                "--jobs" => {
+                    let jobs = value()?;
+                    let jobs = jobs
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|&jobs| jobs > 0)
+                        .ok_or_else(|| format!("invalid number of jobs `{jobs}`"))?;
+                    parsed.jobs = Some(jobs);
+                }
+                "--report" => parsed.report = Some(value()?),
+                "--set" => parsed.assignments.push(value()?),
+                "--quiet" => parsed.quiet = true,
+                option if option.starts_with("--") => {
+                    return Err(format!("unknown option `{option}`"))
+                }
+                _ if parsed.project.is_empty() => parsed.project.clone_from(arg),
+                _ => return Err(format!("unexpected argument `{arg}`")),
+            }
+        }
+        if parsed.project.is_empty() {
+            return Err("no project given".to_owned());
+        }
+        if parsed.input.is_empty() {
+            return Err("no input folder given".to_owned());
+        }
+        Ok(parsed)
+    }
+}
+
+fn batch(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "--help") {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let args = match BatchArgs::parse(args) {
+        Ok(args) => args,
+        Err(message) => return usage_error(&message),
+    };
+    let project = match load(&args.project, &args.assignments) {
+        Ok(project) => project,
+        Err(code) => return code,
+    };
+    let read = match chosen_node(&project, OpType::Read, args.read.as_deref(), "--read") {
+        Ok(node) => node,
+        Err(message) => return project_error(&message),
+    };
+    let write = match chosen_node(&project, OpType::Write, args.write.as_deref(), "--write") {
+        Ok(node) => node,
+        Err(message) => return project_error(&message),
+    };
+
+    let DemoNode::Op(write_op) = &project.snarl[write] else {
+        unreachable!("Write nodes are image nodes")
+    };
+    let output = args
+        .output
+        .unwrap_or_else(|| write_op.path("File").to_owned());
+    let mut batch = Batch::new(&args.input, read, write, &output);
+    batch.filter = args.filter;
+    batch.frame = project.frame_range.clamp(project.frame);
+    if let Some(jobs) = args.jobs {
+        batch.jobs = jobs;
+    }
+
+    let total = batch.files().map_or(0, |files| files.len());
+    let finished = AtomicUsize::new(0);
+    let started = Instant::now();
+    let result = batch.run(
+        &project.snarl,
+        project.disk_cache(&args.project),
+        |outcome| {
+            let count = finished.fetch_add(1, Ordering::Relaxed) + 1;
+            let input = outcome.input.display();
+            match &outcome.result {
+                Ok(()) if !args.quiet => {
+                    println!("  [{count}/{total}] {input} -> {}", outcome.output);
+                }
+                Ok(()) => {}
+                Err(err) => eprintln!("error: [{count}/{total}] {input}: {err}"),
+            }
+        },
+    );
+    let outcomes = match result {
+        Ok(outcomes) => outcomes,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(EXIT_FAILED);
+        }
+    };
+
+    let report = batch::report(&outcomes, started.elapsed());
+    let report_path = args.report.map_or_else(|| batch.report_path(), Into::into);
+    if let Err(err) = std::fs::write(&report_path, &report) {
+        eprintln!("error: {}: {err}", report_path.display());
+        return ExitCode::from(EXIT_FAILED);
+    }
+    if !args.quiet {
+        if let Some(summary) = report.lines().last() {
+            println!("{summary}");
+        }
+        println!("Report written to {}", report_path.display());
+    }
+    if outcomes.iter().any(|outcome| outcome.result.is_err()) {
+        ExitCode::from(EXIT_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Node named by `name`, which has to be of type `op_type`, or the only
+/// node of that type without a name. `option` is the option naming it.
+fn chosen_node(
+    project: &Project,
+    op_type: OpType,
+    name: Option<&str>,
+    option: &str,
+) -> Result<NodeId, String> {
+    let nodes = project.op_nodes(op_type);
+    let kind = op_type.name();
+    match name {
+        Some(name) => {
+            let node = project.find_node(name).map_err(|err| err.to_string())?;
+            if nodes.contains(&node) {
+                Ok(node)
+            } else {
+                Err(format!("{name} isn't a {kind} node"))
+            }
+        }
+        None => match nodes.as_slice() {
+            [node] => Ok(*node),
+            [] => Err(format!("the project has no {kind} nodes")),
+            _ => Err(format!(
+                "the project has several {kind} nodes; choose one with {option}"
+            )),
+        },
+    }
+}
+
+/// Loads a project and applies the parameter overrides to it.
+fn load(path: &str, assignments: &[String]) -> Result<Project, ExitCode> {
+    let mut project =
+        Project::load(path).map_err(|err| project_error(&format!("{path}: {err}")))?;
+    for assignment in assignments {
+        project
+            .set_parameter(assignment)
+            .map_err(|err| project_error(&err.to_string()))?;
+    }
+    Ok(project)
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {message}\n\n{USAGE}");
+    ExitCode::from(EXIT_USAGE)
+}
+
+fn project_error(message: &str) -> ExitCode {
+    eprintln!("error: {message}");
+    ExitCode::from(EXIT_PROJECT)
+}
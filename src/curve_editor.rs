@@ -0,0 +1,367 @@
+use cas_graph::curve::{Curve, Interpolation};
+use cas_graph::node::DemoNode;
+use cas_graph::sequence::FrameRange;
+use egui::{pos2, vec2, Align2, Color32, FontId, Pos2, Rect, Sense, Shape, Stroke, Ui, Vec2};
+use egui_snarl::{NodeId, Snarl};
+
+const KEY_RADIUS: f32 = 4.5;
+/// Pointer distance in screen points at which keys and handles are picked.
+const PICK_DISTANCE: f32 = 8.0;
+/// Length of the tangent handles in screen points.
+const HANDLE_LENGTH: f32 = 40.0;
+const CURVE_COLOR: Color32 = Color32::from_rgb(0x60, 0x90, 0xd0);
+const KEY_COLOR: Color32 = Color32::from_gray(220);
+const SELECTED_COLOR: Color32 = Color32::from_rgb(0xe0, 0xc0, 0x40);
+const RANGE_COLOR: Color32 = Color32::from_gray(30);
+const AXIS_COLOR: Color32 = Color32::from_gray(60);
+const PLAYHEAD_COLOR: Color32 = Color32::from_rgb(0xe0, 0xc0, 0x40);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Drag {
+    Key,
+    InTangent,
+    OutTangent,
+    /// Dragging over the plot away from keys moves the current frame.
+    Scrub,
+}
+
+/// Edits the animation curve of a number parameter of a node.
+///
+/// Keys are dragged along whole frames, double-clicking adds a key and the
+/// handles of the selected key set its tangents, both at once unless Shift
+/// is held. The view fits the frame range and the values of the curve, and
+/// stays put during a drag so the curve doesn't move under the pointer.
+pub struct CurveEditor {
+    /// Index of the selected key.
+    selected: Option<usize>,
+    drag: Option<Drag>,
+    /// Frames along x and values along y that the plot shows.
+    view: Rect,
+}
+
+impl CurveEditor {
+    pub fn new() -> Self {
+        CurveEditor {
+            selected: None,
+            drag: None,
+            view: Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        snarl: &mut Snarl<DemoNode>,
+        target: &mut Option<(NodeId, usize)>,
+        frame: &mut i64,
+        range: FrameRange,
+    ) {
+        let Some((node_id, mut parameter)) =
+            target.filter(|&(node, _)| snarl.get_node(node).is_some())
+        else {
+            *target = None;
+            ui.label("Pick Edit Curve in the menu of the key button of a number to edit its curve");
+            return;
+        };
+        let node = &mut snarl[node_id];
+
+        ui.horizontal(|ui| {
+            ui.label(node.name());
+            let parameters = node.parameters();
+            let name = parameters
+                .iter()
+                .find(|&&(idx, _)| idx == parameter)
+                .map_or("", |&(_, name)| name);
+            egui::ComboBox::from_id_salt("curve_parameter")
+                .selected_text(name)
+                .show_ui(ui, |ui| {
+                    for &(idx, name) in &parameters {
+                        let animated = node.curve(idx).is_some();
+                        let label = if animated {
+                            format!("{name} ◆")
+                        } else {
+                            name.to_owned()
+                        };
+                        ui.selectable_value(&mut parameter, idx, label);
+                    }
+                });
+        });
+        if target.is_some_and(|(_, old)| old != parameter) {
+            self.selected = None;
+        }
+        *target = Some((node_id, parameter));
+
+        let value = node.parameter_value(parameter, *frame).unwrap_or_default();
+        let Some(curve) = node.curve_mut(parameter) else {
+            *target = None;
+            return;
+        };
+        let Some(curve) = curve else {
+            ui.horizontal(|ui| {
+                ui.label("Not animated");
+                if ui.button("Animate").clicked() {
+                    *curve = Some(Curve::new(*frame, value));
+                }
+            });
+            return;
+        };
+        self.selected = self.selected.filter(|&idx| idx < curve.keys().len());
+
+        self.key_controls(ui, curve);
+        self.plot(ui, curve, frame, range);
+    }
+
+    /// Frame, value, interpolation and tangents of the selected key.
+    fn key_controls(&mut self, ui: &mut Ui, curve: &mut Curve) {
+        ui.horizontal(|ui| {
+            let Some(idx) = self.selected else {
+                ui.label("Click a key to select it, double-click to add one");
+                return;
+            };
+            let key = curve.keys()[idx];
+
+            let (mut key_frame, mut key_value) = (key.frame, key.value);
+            ui.label("Frame");
+            let moved = ui.add(egui::DragValue::new(&mut key_frame)).changed();
+            ui.label("Value");
+            let changed = ui
+                .add(egui::DragValue::new(&mut key_value).speed(0.01))
+                .changed();
+            if moved || changed {
+                self.selected = Some(curve.move_key(idx, key_frame, key_value));
+            }
+            let idx = self.selected.unwrap();
+
+            let mut interpolation = key.interpolation;
+            egui::ComboBox::from_id_salt("key_interpolation")
+                .selected_text(interpolation.name())
+                .show_ui(ui, |ui| {
+                    for option in Interpolation::ALL {
+                        ui.selectable_value(&mut interpolation, option, option.name());
+                    }
+                })
+                .response
+                .on_hover_text("Interpolation to the next key");
+            curve.set_interpolation(idx, interpolation);
+
+            if ui
+                .button("Smooth")
+                .on_hover_text("Set the tangents to the slope between the neighbouring keys")
+                .clicked()
+            {
+                curve.smooth_tangents(idx);
+            }
+            if ui
+                .add_enabled(curve.keys().len() > 1, egui::Button::new("Delete Key"))
+                .clicked()
+            {
+                curve.remove_key(idx);
+                self.selected = None;
+            }
+        });
+    }
+
+    fn plot(&mut self, ui: &mut Ui, curve: &mut Curve, frame: &mut i64, range: FrameRange) {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+        let rect = response.rect.shrink(KEY_RADIUS * 2.0);
+        painter.rect_filled(response.rect, 0.0, Color32::from_gray(20));
+        if self.drag.is_none() {
+            self.view = fit(curve, range);
+        }
+
+        let view = self.view;
+        let scale = vec2(rect.width() / view.width(), rect.height() / view.height());
+        // Values grow upwards on screen
+        let to_screen = |frame: f64, value: f64| {
+            pos2(
+                rect.left() + (frame as f32 - view.min.x) * scale.x,
+                rect.bottom() - (value as f32 - view.min.y) * scale.y,
+            )
+        };
+        let from_screen = |pos: Pos2| {
+            (
+                f64::from(view.min.x + (pos.x - rect.left()) / scale.x),
+                f64::from(view.min.y + (rect.bottom() - pos.y) / scale.y),
+            )
+        };
+
+        // Frame range, zero line and the current frame
+        let first = to_screen(range.first as f64, 0.0).x;
+        let last = to_screen(range.last as f64, 0.0).x;
+        painter.rect_filled(
+            Rect::from_x_y_ranges(first..=last, response.rect.y_range()),
+            0.0,
+            RANGE_COLOR,
+        );
+        let zero = to_screen(0.0, 0.0).y;
+        if rect.y_range().contains(zero) {
+            painter.hline(rect.x_range(), zero, Stroke::new(1.0, AXIS_COLOR));
+        }
+        let playhead = to_screen(*frame as f64, 0.0).x;
+        painter.vline(
+            playhead,
+            response.rect.y_range(),
+            Stroke::new(1.0, PLAYHEAD_COLOR),
+        );
+        let label = |pos: Pos2, align: Align2, text: String| {
+            painter.text(
+                pos,
+                align,
+                text,
+                FontId::monospace(10.0),
+                Color32::from_gray(140),
+            );
+        };
+        label(
+            response.rect.left_top() + vec2(4.0, 2.0),
+            Align2::LEFT_TOP,
+            format_value(f64::from(view.max.y)),
+        );
+        label(
+            response.rect.left_bottom() + vec2(4.0, -2.0),
+            Align2::LEFT_BOTTOM,
+            format_value(f64::from(view.min.y)),
+        );
+
+        // The curve is sampled every few screen points
+        let points = (0..=(rect.width() / 2.0) as usize)
+            .map(|step| {
+                let x = rect.left() + step as f32 * 2.0;
+                let (frame, _) = from_screen(pos2(x, 0.0));
+                to_screen(frame, curve.value(frame))
+            })
+            .collect();
+        painter.add(Shape::line(points, Stroke::new(1.5, CURVE_COLOR)));
+
+        let handles = |curve: &Curve, idx: usize| {
+            let key = curve.keys()[idx];
+            let centre = to_screen(key.frame as f64, key.value);
+            let direction = |slope: f64| {
+                let dir = vec2(scale.x, -(slope as f32) * scale.y);
+                dir.normalized() * HANDLE_LENGTH
+            };
+            (
+                centre,
+                centre - direction(key.in_tangent),
+                centre + direction(key.out_tangent),
+            )
+        };
+        if let Some(idx) = self.selected {
+            let (centre, in_handle, out_handle) = handles(curve, idx);
+            for handle in [in_handle, out_handle] {
+                painter.line_segment([centre, handle], Stroke::new(1.0, SELECTED_COLOR));
+                painter.circle_filled(handle, KEY_RADIUS - 1.5, SELECTED_COLOR);
+            }
+        }
+        for (idx, key) in curve.keys().iter().enumerate() {
+            let color = if self.selected == Some(idx) {
+                SELECTED_COLOR
+            } else {
+                KEY_COLOR
+            };
+            painter.circle_filled(to_screen(key.frame as f64, key.value), KEY_RADIUS, color);
+        }
+
+        let Some(pointer) = response.interact_pointer_pos() else {
+            self.drag = None;
+            return;
+        };
+        let picked_key = curve.keys().iter().position(|key| {
+            to_screen(key.frame as f64, key.value).distance(pointer) < PICK_DISTANCE
+        });
+
+        if response.double_clicked() && picked_key.is_none() {
+            let (key_frame, value) = from_screen(pointer);
+            self.selected = Some(curve.set_key(key_frame.round() as i64, value));
+            return;
+        }
+        if response.drag_started() || response.clicked() {
+            let picked_handle = self.selected.and_then(|idx| {
+                let (_, in_handle, out_handle) = handles(curve, idx);
+                if in_handle.distance(pointer) < PICK_DISTANCE {
+                    Some(Drag::InTangent)
+                } else if out_handle.distance(pointer) < PICK_DISTANCE {
+                    Some(Drag::OutTangent)
+                } else {
+                    None
+                }
+            });
+            self.drag = Some(match (picked_handle, picked_key) {
+                (Some(handle), _) => handle,
+                (None, Some(idx)) => {
+                    self.selected = Some(idx);
+                    Drag::Key
+                }
+                (None, None) => {
+                    self.selected = None;
+                    Drag::Scrub
+                }
+            });
+        }
+
+        let (pointer_frame, pointer_value) = from_screen(pointer);
+        match (self.drag, self.selected) {
+            (Some(Drag::Key), Some(idx)) if response.dragged() => {
+                let moved = curve.move_key(idx, pointer_frame.round() as i64, pointer_value);
+                self.selected = Some(moved);
+            }
+            (Some(drag @ (Drag::InTangent | Drag::OutTangent)), Some(idx)) => {
+                let (centre, _, _) = handles(curve, idx);
+                let offset: Vec2 = pointer - centre;
+                // Handles can't cross to the other side of their key
+                let dx = match drag {
+                    Drag::InTangent => offset.x.min(-1.0),
+                    _ => offset.x.max(1.0),
+                };
+                let slope = f64::from((-offset.y / scale.y) / (dx / scale.x));
+                let key = curve.keys()[idx];
+                let (in_tangent, out_tangent) = match (drag, ui.input(|i| i.modifiers.shift)) {
+                    (Drag::InTangent, true) => (slope, key.out_tangent),
+                    (Drag::OutTangent, true) => (key.in_tangent, slope),
+                    _ => (slope, slope),
+                };
+                curve.set_tangents(idx, in_tangent, out_tangent);
+            }
+            (Some(Drag::Scrub), _) => {
+                *frame = range.clamp(pointer_frame.round() as i64);
+            }
+            _ => {}
+        }
+        if response.drag_stopped() || response.clicked() {
+            self.drag = None;
+        }
+    }
+}
+
+/// Frames of the frame range and the keys, and the values the curve takes
+/// over them, with some room around the values.
+fn fit(curve: &Curve, range: FrameRange) -> Rect {
+    let keys = curve.keys();
+    let first = keys
+        .first()
+        .map_or(range.first, |key| key.frame.min(range.first));
+    let last = keys
+        .last()
+        .map_or(range.last, |key| key.frame.max(range.last));
+    let last = last.max(first + 1);
+
+    // Long ranges are sampled rather than read at every frame
+    let step = ((last - first) / 1000).max(1);
+    let (low, high) = (first..=last)
+        .step_by(step as usize)
+        .map(|frame| curve.value_at(frame))
+        .chain(keys.iter().map(|key| key.value))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    let margin = ((high - low) * 0.1).max(0.5);
+    Rect::from_min_max(
+        pos2(first as f32, (low - margin) as f32),
+        pos2(last as f32, (high + margin) as f32),
+    )
+}
+
+fn format_value(value: f64) -> String {
+    let value = (value * 1000.0).round() / 1000.0;
+    format!("{value}")
+}
@@ -1,5 +1,13 @@
 mod app;
+mod batch_dialog;
+mod cli;
+mod curve_editor;
 mod egui_tools;
+mod scopes;
+mod timeline;
+mod viewer;
+
+use std::process::ExitCode;
 
 use winit::event_loop::{ControlFlow, EventLoop};
 
@@ -13,9 +21,16 @@ async fn run() {
     event_loop.run_app(&mut app).expect("Failed to run app");
 }
 
-fn main() {
+fn main() -> ExitCode {
+    // Commands run without a window
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     {
         pollster::block_on(run());
     }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,366 @@
+use std::sync::Arc;
+
+use cas_graph::eval::{EvalError, Value};
+use cas_graph::image::ImageBuffer;
+use cas_graph::ops::keyer::LUMA;
+use egui::{
+    pos2, vec2, Color32, ColorImage, Pos2, Rect, Sense, Shape, Stroke, TextureHandle,
+    TextureOptions, Ui,
+};
+
+/// Number of value bins of the histogram and the waveforms.
+const BINS: usize = 256;
+/// Width and height of the vectorscope in bins.
+const VECTOR_SIZE: usize = 256;
+/// Most columns and rows sampled from an image, so large images stay interactive.
+const MAX_SAMPLES: usize = 512;
+/// Angle of the skin tone line, counterclockwise from the positive Cb axis.
+const SKIN_TONE_ANGLE: f32 = 123.0;
+
+const CHANNEL_COLORS: [Color32; 3] = [
+    Color32::from_rgb(230, 70, 60),
+    Color32::from_rgb(70, 210, 80),
+    Color32::from_rgb(70, 120, 240),
+];
+const TRACE_COLOR: Color32 = Color32::from_rgb(200, 230, 200);
+const GRATICULE_COLOR: Color32 = Color32::from_gray(70);
+const SKIN_TONE_COLOR: Color32 = Color32::from_rgb(0xe0, 0xa0, 0x70);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scope {
+    Histogram,
+    Waveform,
+    Parade,
+    Vectorscope,
+}
+
+impl Scope {
+    const ALL: [Scope; 4] = [
+        Scope::Histogram,
+        Scope::Waveform,
+        Scope::Parade,
+        Scope::Vectorscope,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Scope::Histogram => "Histogram",
+            Scope::Waveform => "Waveform",
+            Scope::Parade => "RGB Parade",
+            Scope::Vectorscope => "Vectorscope",
+        }
+    }
+}
+
+/// Shows signal scopes of the image the viewer shows.
+///
+/// The scope is only recomputed when the evaluator returns a different
+/// image or another scope is chosen, so it follows every re-evaluation
+/// without slowing down the frames in between.
+pub struct Scopes {
+    scope: Scope,
+    /// Whether the histogram height is logarithmic in the pixel count.
+    log: bool,
+    image: Option<Arc<ImageBuffer>>,
+    /// Pixel counts of the red, green and blue histogram bins.
+    histogram: [Vec<u32>; 3],
+    /// Density plot of the waveform, parade or vectorscope.
+    texture: Option<TextureHandle>,
+    dirty: bool,
+}
+
+impl Scopes {
+    pub fn new() -> Self {
+        Scopes {
+            scope: Scope::Histogram,
+            log: false,
+            image: None,
+            histogram: [vec![0; BINS], vec![0; BINS], vec![0; BINS]],
+            texture: None,
+            dirty: true,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, result: Option<&Result<Value, EvalError>>) {
+        ui.horizontal(|ui| {
+            for scope in Scope::ALL {
+                if ui
+                    .selectable_label(self.scope == scope, scope.name())
+                    .clicked()
+                    && self.scope != scope
+                {
+                    self.scope = scope;
+                    self.dirty = true;
+                }
+            }
+            if self.scope == Scope::Histogram {
+                ui.separator();
+                ui.checkbox(&mut self.log, "Log");
+            }
+        });
+
+        let Some(Ok(Value::Image(image))) = result else {
+            ui.label("Scopes show the output of image nodes");
+            return;
+        };
+        if !self
+            .image
+            .as_ref()
+            .is_some_and(|shown| Arc::ptr_eq(shown, image))
+        {
+            self.image = Some(image.clone());
+            self.dirty = true;
+        }
+        if self.dirty {
+            self.update(ui.ctx(), image);
+            self.dirty = false;
+        }
+
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+        match self.scope {
+            Scope::Histogram => self.draw_histogram(&painter, rect.shrink(4.0)),
+            Scope::Waveform | Scope::Parade => {
+                let rect = rect.shrink(4.0);
+                self.draw_texture(&painter, rect);
+                for step in 0..=4 {
+                    let y = rect.bottom() - rect.height() * step as f32 / 4.0;
+                    painter.hline(rect.x_range(), y, Stroke::new(1.0, GRATICULE_COLOR));
+                }
+            }
+            Scope::Vectorscope => {
+                let side = rect.width().min(rect.height()) - 8.0;
+                let rect = Rect::from_center_size(rect.center(), vec2(side, side));
+                draw_vectorscope_graticule(&painter, rect);
+                self.draw_texture(&painter, rect);
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, image: &ImageBuffer) {
+        let samples = Samples::new(image);
+        let color_image = match self.scope {
+            Scope::Histogram => {
+                for bins in &mut self.histogram {
+                    bins.fill(0);
+                }
+                samples.for_each(|_, rgb| {
+                    for (bins, value) in self.histogram.iter_mut().zip(rgb) {
+                        bins[bin(value)] += 1;
+                    }
+                });
+                return;
+            }
+            Scope::Waveform => {
+                let mut counts = vec![0u32; samples.columns * BINS];
+                samples.for_each(|column, [r, g, b]| {
+                    let luma = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+                    counts[(BINS - 1 - bin(luma)) * samples.columns + column] += 1;
+                });
+                density_image(
+                    [samples.columns, BINS],
+                    &counts,
+                    samples.rows as f32 / BINS as f32,
+                    |_| TRACE_COLOR,
+                )
+            }
+            Scope::Parade => {
+                // Three waveforms side by side, one per channel
+                let width = samples.columns * 3;
+                let mut counts = vec![0u32; width * BINS];
+                samples.for_each(|column, rgb| {
+                    for (c, value) in rgb.into_iter().enumerate() {
+                        let x = c * samples.columns + column;
+                        counts[(BINS - 1 - bin(value)) * width + x] += 1;
+                    }
+                });
+                density_image(
+                    [width, BINS],
+                    &counts,
+                    samples.rows as f32 / BINS as f32,
+                    |x| CHANNEL_COLORS[x / samples.columns],
+                )
+            }
+            Scope::Vectorscope => {
+                let mut counts = vec![0u32; VECTOR_SIZE * VECTOR_SIZE];
+                samples.for_each(|_, rgb| {
+                    let (x, y) = vector_position(rgb);
+                    let x = (x * VECTOR_SIZE as f32) as usize;
+                    let y = (y * VECTOR_SIZE as f32) as usize;
+                    counts[y.min(VECTOR_SIZE - 1) * VECTOR_SIZE + x.min(VECTOR_SIZE - 1)] += 1;
+                });
+                // Most bins of a vectorscope stay empty, so a few samples are visible
+                let expected = (samples.columns * samples.rows) as f32 / 2048.0;
+                density_image([VECTOR_SIZE, VECTOR_SIZE], &counts, expected, |_| {
+                    TRACE_COLOR
+                })
+            }
+        };
+
+        match &mut self.texture {
+            Some(texture) => texture.set(color_image, TextureOptions::LINEAR),
+            None => {
+                self.texture =
+                    Some(ctx.load_texture("scope-image", color_image, TextureOptions::LINEAR));
+            }
+        }
+    }
+
+    fn draw_texture(&self, painter: &egui::Painter, rect: Rect) {
+        if let Some(texture) = &self.texture {
+            painter.image(
+                texture.id(),
+                rect,
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+    }
+
+    fn draw_histogram(&self, painter: &egui::Painter, rect: Rect) {
+        let scale = |count: u32| {
+            if self.log {
+                (count as f32).ln_1p()
+            } else {
+                count as f32
+            }
+        };
+        let max = self
+            .histogram
+            .iter()
+            .flatten()
+            .map(|&count| scale(count))
+            .fold(0.0, f32::max)
+            .max(1.0);
+
+        for (bins, color) in self.histogram.iter().zip(CHANNEL_COLORS) {
+            let points = bins
+                .iter()
+                .enumerate()
+                .map(|(idx, &count)| {
+                    pos2(
+                        rect.left() + rect.width() * idx as f32 / (BINS - 1) as f32,
+                        rect.bottom() - rect.height() * scale(count) / max,
+                    )
+                })
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.0, color)));
+        }
+    }
+}
+
+/// Evenly spaced pixels of the display window of an image.
+struct Samples<'a> {
+    image: &'a ImageBuffer,
+    step_x: usize,
+    step_y: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl<'a> Samples<'a> {
+    fn new(image: &'a ImageBuffer) -> Self {
+        let display = image.display_window();
+        let step_x = display.width.div_ceil(MAX_SAMPLES).max(1);
+        let step_y = display.height.div_ceil(MAX_SAMPLES).max(1);
+        Samples {
+            image,
+            step_x,
+            step_y,
+            columns: display.width.div_ceil(step_x).max(1),
+            rows: display.height.div_ceil(step_y),
+        }
+    }
+
+    /// Calls `f` with the column index and RGB of every sample.
+    fn for_each(&self, mut f: impl FnMut(usize, [f32; 3])) {
+        let display = self.image.display_window();
+        for row in 0..self.rows {
+            let y = display.y + (row * self.step_y) as isize;
+            for column in 0..display.width.div_ceil(self.step_x) {
+                let x = display.x + (column * self.step_x) as isize;
+                let [r, g, b, _] = self.image.rgba(x, y);
+                f(column, [r, g, b]);
+            }
+        }
+    }
+}
+
+/// Bin of a value in `0..=1`. Values outside the range pile up in the end bins.
+fn bin(value: f32) -> usize {
+    (value.clamp(0.0, 1.0) * (BINS - 1) as f32).round() as usize
+}
+
+/// Position of a colour on the vectorscope in `0..=1`, with Cb to the right
+/// and Cr up. The neutral axis is at the centre and a saturated colour at the edge.
+fn vector_position([r, g, b]: [f32; 3]) -> (f32, f32) {
+    let y = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+    let cb = (b - y) / 1.8556;
+    let cr = (r - y) / 1.5748;
+    ((cb + 0.5).clamp(0.0, 1.0), (0.5 - cr).clamp(0.0, 1.0))
+}
+
+/// Shades each bin by its count, reaching about two thirds of full
+/// brightness at `expected` samples.
+fn density_image(
+    size: [usize; 2],
+    counts: &[u32],
+    expected: f32,
+    color: impl Fn(usize) -> Color32,
+) -> ColorImage {
+    let expected = expected.max(1.0);
+    let pixels = counts
+        .iter()
+        .enumerate()
+        .map(|(idx, &count)| {
+            let t = 1.0 - (-(count as f32) / expected).exp();
+            let base = color(idx % size[0]);
+            Color32::from_rgba_unmultiplied(base.r(), base.g(), base.b(), (t * 255.0) as u8)
+        })
+        .collect();
+    ColorImage { size, pixels }
+}
+
+/// Draws the outer circle, the axes, the targets of the primary and
+/// secondary colours at 75% and the skin tone line.
+fn draw_vectorscope_graticule(painter: &egui::Painter, rect: Rect) {
+    let to_screen =
+        |(x, y): (f32, f32)| -> Pos2 { rect.min + vec2(x * rect.width(), y * rect.height()) };
+    let centre = rect.center();
+    let radius = rect.width() / 2.0;
+    let stroke = Stroke::new(1.0, GRATICULE_COLOR);
+
+    painter.circle_stroke(centre, radius, stroke);
+    painter.hline(rect.x_range(), centre.y, stroke);
+    painter.vline(centre.x, rect.y_range(), stroke);
+
+    for rgb in [
+        [0.75, 0.0, 0.0],
+        [0.75, 0.75, 0.0],
+        [0.0, 0.75, 0.0],
+        [0.0, 0.75, 0.75],
+        [0.0, 0.0, 0.75],
+        [0.75, 0.0, 0.75],
+    ] {
+        let target = to_screen(vector_position(rgb));
+        let color = Color32::from_rgb(
+            (rgb[0] * 255.0) as u8,
+            (rgb[1] * 255.0) as u8,
+            (rgb[2] * 255.0) as u8,
+        );
+        painter.rect_stroke(
+            Rect::from_center_size(target, vec2(8.0, 8.0)),
+            0.0,
+            Stroke::new(1.0, color),
+            egui::StrokeKind::Middle,
+        );
+    }
+
+    let angle = SKIN_TONE_ANGLE.to_radians();
+    painter.line_segment(
+        [centre, centre + vec2(angle.cos(), -angle.sin()) * radius],
+        Stroke::new(1.0, SKIN_TONE_COLOR),
+    );
+}
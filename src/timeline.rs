@@ -0,0 +1,237 @@
+use std::time::Instant;
+
+use cas_graph::sequence::{FrameRange, LoopMode, Playback};
+use cas_graph::worker::Worker;
+use egui::{pos2, vec2, Align2, Color32, FontId, Rect, Sense, Stroke, Ui};
+
+const BAR_HEIGHT: f32 = 22.0;
+const PLAY_RANGE_COLOR: Color32 = Color32::from_gray(55);
+const CACHED_COLOR: Color32 = Color32::from_rgb(70, 170, 90);
+const PLAYHEAD_COLOR: Color32 = Color32::from_rgb(0xe0, 0xc0, 0x40);
+
+/// Frame controls and playback of image sequences.
+///
+/// Playback follows the clock rather than the evaluator: the frame shown is
+/// the one due at the target frame rate, and frames that aren't evaluated
+/// by the time they are due are dropped. The bar marks the frames the
+/// worker has cached, which play back without evaluating again.
+pub struct Timeline {
+    /// Time playback started at and the frame it started from, while playing.
+    playing: Option<(Instant, i64)>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Timeline { playing: None }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    /// Moves `frame` to the frame that is due while playing. Called once per
+    /// UI frame, before the graph is submitted.
+    pub fn update(&mut self, frame: &mut i64, range: FrameRange, playback: &Playback) {
+        let Some((started, start)) = self.playing else {
+            return;
+        };
+        let steps = (started.elapsed().as_secs_f64() * playback.fps.max(1.0)) as u64;
+        let (due, stopped) = playback.frame_after(start, steps, playback.range(range));
+        *frame = due;
+        if stopped {
+            self.playing = None;
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        frame: &mut i64,
+        range: &mut FrameRange,
+        playback: &mut Playback,
+        worker: &mut Worker,
+    ) {
+        let play_range = playback.range(*range);
+        let shown = *frame;
+
+        ui.horizontal(|ui| {
+            if ui.button("⏮").on_hover_text("Go to the in point").clicked() {
+                self.playing = None;
+                *frame = play_range.first;
+            }
+            if ui.button("⏴").on_hover_text("Previous frame").clicked() {
+                self.playing = None;
+                *frame = range.clamp(*frame - 1);
+            }
+            let play = if self.is_playing() { "⏸" } else { "▶" };
+            if ui.button(play).on_hover_text("Play or pause").clicked() {
+                self.playing = match self.playing {
+                    Some(_) => None,
+                    None => Some((Instant::now(), *frame)),
+                };
+            }
+            if ui.button("⏵").on_hover_text("Next frame").clicked() {
+                self.playing = None;
+                *frame = range.clamp(*frame + 1);
+            }
+            if ui
+                .button("⏭")
+                .on_hover_text("Go to the out point")
+                .clicked()
+            {
+                self.playing = None;
+                *frame = play_range.last;
+            }
+
+            let (first, last) = (range.first, range.last);
+            let edited = ui
+                .add(egui::DragValue::new(frame).range(first..=last))
+                .on_hover_text("Frame of image sequences shown in the viewer")
+                .changed();
+            if edited {
+                self.playing = None;
+            }
+
+            ui.separator();
+            let mut changed = false;
+            egui::ComboBox::from_id_salt("loop_mode")
+                .selected_text(playback.loop_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in LoopMode::ALL {
+                        changed |= ui
+                            .selectable_value(&mut playback.loop_mode, mode, mode.name())
+                            .changed();
+                    }
+                });
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut playback.fps)
+                        .range(1.0..=120.0)
+                        .suffix(" fps"),
+                )
+                .on_hover_text("Target frame rate. Frames that aren't ready in time are dropped")
+                .changed();
+            // Playback goes on from the frame shown at the new settings
+            if changed && self.is_playing() {
+                self.playing = Some((Instant::now(), *frame));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Set In").clicked() {
+                playback.in_point = Some(*frame);
+                playback.out_point = playback.out_point.map(|out| out.max(*frame));
+            }
+            if ui.button("Set Out").clicked() {
+                playback.out_point = Some(*frame);
+                playback.in_point = playback.in_point.map(|point| point.min(*frame));
+            }
+            if ui
+                .add_enabled(
+                    playback.in_point.is_some() || playback.out_point.is_some(),
+                    egui::Button::new("Clear In/Out"),
+                )
+                .clicked()
+            {
+                playback.in_point = None;
+                playback.out_point = None;
+            }
+
+            ui.separator();
+            ui.label("Range");
+            let (first, last) = (range.first, range.last);
+            ui.add(egui::DragValue::new(&mut range.first).range(i64::MIN..=last));
+            ui.add(egui::DragValue::new(&mut range.last).range(first..=i64::MAX))
+                .on_hover_text("Frames written by renders of Write nodes to sequences");
+
+            ui.separator();
+            let frames = worker.frames();
+            ui.label(format!(
+                "Cached {} frames, {} / {} MiB",
+                frames.len(),
+                frames.used() >> 20,
+                frames.budget() >> 20
+            ));
+            if ui
+                .button("Clear")
+                .on_hover_text("Drop the cached frames")
+                .clicked()
+            {
+                worker.clear_frames();
+            }
+        });
+
+        if let Some(scrubbed) = self.bar(ui, shown, *range, play_range, worker) {
+            self.playing = None;
+            *frame = scrubbed;
+        }
+        *frame = range.clamp(*frame);
+    }
+
+    /// Draws the frame range with the play range, the cached frames and the
+    /// current frame. Returns the frame clicked or dragged to.
+    fn bar(
+        &self,
+        ui: &mut Ui,
+        frame: i64,
+        range: FrameRange,
+        play_range: FrameRange,
+        worker: &Worker,
+    ) -> Option<i64> {
+        let size = vec2(ui.available_width(), BAR_HEIGHT);
+        let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+        let width = rect.width() / range.len().max(1) as f32;
+        let x = |frame: i64| rect.left() + (frame - range.first) as f32 * width;
+        let span = |first: i64, last: i64, top: f32, bottom: f32| {
+            Rect::from_min_max(pos2(x(first), top), pos2(x(last) + width, bottom))
+        };
+
+        painter.rect_filled(
+            span(play_range.first, play_range.last, rect.top(), rect.bottom()),
+            0.0,
+            PLAY_RANGE_COLOR,
+        );
+        // Runs of consecutive cached frames are drawn as one rectangle
+        let strip_top = rect.bottom() - 4.0;
+        let mut cached = worker
+            .frames()
+            .frames()
+            .filter(|&cached| range.contains(cached))
+            .peekable();
+        while let Some(first) = cached.next() {
+            let mut last = first;
+            while cached.next_if_eq(&(last + 1)).is_some() {
+                last += 1;
+            }
+            painter.rect_filled(
+                span(first, last, strip_top, rect.bottom()),
+                0.0,
+                CACHED_COLOR,
+            );
+        }
+
+        if range.contains(frame) {
+            let playhead = x(frame) + width / 2.0;
+            painter.vline(playhead, rect.y_range(), Stroke::new(2.0, PLAYHEAD_COLOR));
+        }
+        for (label, x, align) in [
+            (range.first, rect.left() + 4.0, Align2::LEFT_CENTER),
+            (range.last, rect.right() - 4.0, Align2::RIGHT_CENTER),
+        ] {
+            painter.text(
+                pos2(x, rect.center().y),
+                align,
+                label.to_string(),
+                FontId::monospace(11.0),
+                Color32::from_gray(160),
+            );
+        }
+
+        let pointer = response.interact_pointer_pos()?;
+        let offset = ((pointer.x - rect.left()) / width).floor() as i64;
+        Some(range.clamp(range.first + offset))
+    }
+}
@@ -0,0 +1,336 @@
+use std::sync::Arc;
+
+use cas_graph::eval::{EvalError, Value};
+use cas_graph::image::{ImageBuffer, Window};
+use cas_graph::node::DemoNode;
+use cas_graph::ops::transform::Affine;
+use cas_graph::ops::OpType;
+use egui::{
+    pos2, vec2, Color32, Pos2, Rect, Sense, Stroke, StrokeKind, TextureHandle, TextureOptions, Ui,
+    Vec2,
+};
+use egui_snarl::{NodeId, Snarl};
+
+/// Length of the transform handle arms in screen points.
+const HANDLE_ARM: f32 = 60.0;
+const HANDLE_RADIUS: f32 = 6.0;
+const HANDLE_COLOR: Color32 = Color32::from_rgb(0xe0, 0xc0, 0x40);
+/// The visible region is rounded out to multiples of this many pixels, so
+/// small pans don't ask for a new evaluation.
+const REGION_GRID: f32 = 256.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Handle {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+/// Shows the output of the viewed node and the on-canvas handles of Transform nodes.
+pub struct ImageViewer {
+    image: Option<Arc<ImageBuffer>>,
+    texture: Option<TextureHandle>,
+    /// Screen points per image pixel. Zero fits the image on the next frame.
+    zoom: f32,
+    /// Position of the pixel coordinate origin relative to the centre of the view.
+    offset: Vec2,
+    drag: Option<Handle>,
+    /// Pixels the view showed on the last frame, once it showed an image.
+    visible: Option<Window>,
+}
+
+impl ImageViewer {
+    pub fn new() -> Self {
+        ImageViewer {
+            image: None,
+            texture: None,
+            zoom: 0.0,
+            offset: Vec2::ZERO,
+            drag: None,
+            visible: None,
+        }
+    }
+
+    /// Region of pixel coordinates the view shows, so only that region of
+    /// the viewed node needs to be evaluated. `None` until an image was shown.
+    pub fn visible_region(&self) -> Option<Window> {
+        self.visible
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        result: Option<&Result<Value, EvalError>>,
+        snarl: &mut Snarl<DemoNode>,
+        viewed: Option<NodeId>,
+    ) {
+        ui.horizontal(|ui| {
+            if ui.button("Fit").clicked() {
+                self.zoom = 0.0;
+            }
+            if ui.button("1:1").clicked() {
+                self.zoom = 1.0;
+            }
+            if let Some(image) = &self.image {
+                let display = image.display_window();
+                let data = image.data_window();
+                ui.label(format!(
+                    "{} x {}, data {} {} {} x {}, {} channels, {:.0}%",
+                    display.width,
+                    display.height,
+                    data.x,
+                    data.y,
+                    data.width,
+                    data.height,
+                    image.channels(),
+                    self.zoom * 100.0
+                ));
+            }
+        });
+
+        match result {
+            None => {
+                ui.label("Choose \"View\" in a node menu to show its output");
+                return;
+            }
+            Some(Err(err)) => {
+                ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+                return;
+            }
+            Some(Ok(Value::Number(value))) => {
+                ui.label(value.to_string());
+                return;
+            }
+            Some(Ok(Value::String(value))) => {
+                ui.label(format!("{value:?}"));
+                return;
+            }
+            Some(Ok(Value::Image(image))) => self.set_image(ui.ctx(), image),
+        }
+
+        let (Some(image), Some(texture)) = (&self.image, &self.texture) else {
+            return;
+        };
+        let display = image.display_window();
+        let data = image.data_window();
+
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+        if self.zoom <= 0.0 {
+            self.zoom = (rect.width() / display.width.max(1) as f32)
+                .min(rect.height() / display.height.max(1) as f32);
+            self.offset = -vec2(
+                display.x as f32 + display.width as f32 / 2.0,
+                display.y as f32 + display.height as f32 / 2.0,
+            ) * self.zoom;
+        }
+
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            if scroll != 0.0 {
+                let factor = (scroll * 0.002).exp();
+                let anchor = pointer - rect.center();
+                self.offset = anchor - (anchor - self.offset) * factor;
+                self.zoom *= factor;
+            }
+        }
+
+        // Screen position of the pixel coordinate origin
+        let origin = rect.center() + self.offset;
+        let min = ((rect.min - origin) / (self.zoom * REGION_GRID)).floor() * REGION_GRID;
+        let max = ((rect.max - origin) / (self.zoom * REGION_GRID)).ceil() * REGION_GRID;
+        self.visible = Some(Window::from_corners(
+            min.x as isize,
+            min.y as isize,
+            max.x as isize,
+            max.y as isize,
+        ));
+        let to_rect = |window: Window| {
+            Rect::from_min_size(
+                origin + vec2(window.x as f32, window.y as f32) * self.zoom,
+                vec2(window.width as f32, window.height as f32) * self.zoom,
+            )
+        };
+
+        painter.image(
+            texture.id(),
+            to_rect(data),
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+            Color32::WHITE,
+        );
+        painter.rect_stroke(
+            to_rect(display),
+            0.0,
+            Stroke::new(1.0, Color32::from_gray(120)),
+            StrokeKind::Outside,
+        );
+        if data != display {
+            painter.rect_stroke(
+                to_rect(data),
+                0.0,
+                Stroke::new(1.0, Color32::from_gray(70)),
+                StrokeKind::Outside,
+            );
+        }
+
+        let transform = viewed.filter(|&node| {
+            matches!(snarl.get_node(node), Some(DemoNode::Op(op)) if op.op_type == OpType::Transform)
+        });
+
+        match transform {
+            Some(node) => self.transform_handles(ui, &response, &painter, origin, snarl, node),
+            None => self.drag = None,
+        }
+
+        if response.dragged() && self.drag.is_none() {
+            self.offset += response.drag_delta();
+        }
+    }
+
+    fn set_image(&mut self, ctx: &egui::Context, image: &Arc<ImageBuffer>) {
+        if self
+            .image
+            .as_ref()
+            .is_some_and(|shown| Arc::ptr_eq(shown, image))
+        {
+            return;
+        }
+
+        let color_image = image.to_color_image();
+        match &mut self.texture {
+            Some(texture) => texture.set(color_image, TextureOptions::NEAREST),
+            None => {
+                self.texture =
+                    Some(ctx.load_texture("viewer-image", color_image, TextureOptions::NEAREST));
+            }
+        }
+        self.image = Some(image.clone());
+    }
+
+    /// Draws the handles of a Transform node and applies drags to its properties.
+    ///
+    /// The centre handle moves the image, or the pivot while Ctrl is held.
+    /// The upper handle rotates and the corner handle scales, uniformly while Shift is held.
+    fn transform_handles(
+        &mut self,
+        ui: &Ui,
+        response: &egui::Response,
+        painter: &egui::Painter,
+        origin: Pos2,
+        snarl: &mut Snarl<DemoNode>,
+        node: NodeId,
+    ) {
+        let op = snarl[node].op_node();
+        let mut params = op.transform_params();
+
+        let zoom = self.zoom as f64;
+        let to_screen = |(x, y): (f64, f64)| origin + vec2((x * zoom) as f32, (y * zoom) as f32);
+        let rotation = Affine::rotate(params.rotate);
+        let arm = |(x, y): (f64, f64)| {
+            let (x, y) = rotation.apply_vector(x, y);
+            vec2(x as f32, y as f32)
+        };
+
+        let centre = to_screen((
+            params.pivot.0 + params.translate.0,
+            params.pivot.1 + params.translate.1,
+        ));
+        let rotate_pos = centre + arm((0.0, -1.5 * HANDLE_ARM as f64));
+        let scale_pos = centre
+            + arm((
+                params.scale.0 * HANDLE_ARM as f64,
+                params.scale.1 * HANDLE_ARM as f64,
+            ));
+
+        if response.drag_started() {
+            self.drag = response.interact_pointer_pos().and_then(|pointer| {
+                [
+                    (Handle::Translate, centre),
+                    (Handle::Rotate, rotate_pos),
+                    (Handle::Scale, scale_pos),
+                ]
+                .into_iter()
+                .find(|(_, pos)| pos.distance(pointer) <= HANDLE_RADIUS * 2.0)
+                .map(|(handle, _)| handle)
+            });
+        }
+
+        let modifiers = ui.input(|i| i.modifiers);
+        if let (true, Some(handle), Some(pointer)) = (
+            response.dragged(),
+            self.drag,
+            response.interact_pointer_pos(),
+        ) {
+            let delta = response.drag_delta() / self.zoom;
+            let (dx, dy) = (delta.x as f64, delta.y as f64);
+            let to_pointer = pointer - centre;
+
+            match handle {
+                Handle::Translate if modifiers.command => {
+                    // Move the pivot so its on-screen position follows the pointer
+                    let linear = Affine {
+                        c: 0.0,
+                        f: 0.0,
+                        ..params.matrix()
+                    };
+                    if let Some(inverse) = linear.inverse() {
+                        let (px, py) = inverse.apply_vector(dx, dy);
+                        params.set_pivot((params.pivot.0 + px, params.pivot.1 + py));
+                    }
+                }
+                Handle::Translate => {
+                    params.translate.0 += dx;
+                    params.translate.1 += dy;
+                }
+                Handle::Rotate => {
+                    let angle = (-to_pointer.y).atan2(to_pointer.x).to_degrees() as f64;
+                    params.rotate = (angle - 90.0 + 180.0).rem_euclid(360.0) - 180.0;
+                }
+                Handle::Scale => {
+                    let (x, y) = Affine::rotate(-params.rotate)
+                        .apply_vector(to_pointer.x as f64, to_pointer.y as f64);
+                    let (sx, sy) = (x / HANDLE_ARM as f64, y / HANDLE_ARM as f64);
+                    params.scale = if modifiers.shift {
+                        let uniform = (sx + sy) / 2.0;
+                        (uniform, uniform)
+                    } else {
+                        (sx, sy)
+                    };
+                }
+            }
+
+            snarl[node].op_node().set_transform_params(&params);
+        }
+
+        if response.drag_stopped() {
+            self.drag = None;
+        }
+
+        let stroke = Stroke::new(1.5, HANDLE_COLOR);
+        painter.line_segment([centre, rotate_pos], stroke);
+        painter.line_segment([centre, scale_pos], stroke);
+        painter.circle_stroke(centre, HANDLE_RADIUS, stroke);
+        painter.line_segment(
+            [
+                centre - vec2(HANDLE_RADIUS, 0.0),
+                centre + vec2(HANDLE_RADIUS, 0.0),
+            ],
+            stroke,
+        );
+        painter.line_segment(
+            [
+                centre - vec2(0.0, HANDLE_RADIUS),
+                centre + vec2(0.0, HANDLE_RADIUS),
+            ],
+            stroke,
+        );
+        painter.circle_filled(rotate_pos, HANDLE_RADIUS, HANDLE_COLOR);
+        painter.rect_filled(
+            Rect::from_center_size(scale_pos, Vec2::splat(HANDLE_RADIUS * 2.0)),
+            0.0,
+            HANDLE_COLOR,
+        );
+    }
+}